@@ -0,0 +1,96 @@
+/**
+ * Generated by the protoc-gen-ts.  DO NOT EDIT!
+ * compiler version: 3.19.4
+ * source: payload/v1/album/order.proto
+ * git: https://github.com/thesayyn/protoc-gen-ts */
+import * as pb_1 from "google-protobuf";
+export class ReorderAlbumRequest extends pb_1.Message {
+    #one_of_decls: number[][] = [];
+    constructor(data?: any[] | {
+        albumId?: string;
+        photoIds?: string[];
+    }) {
+        super();
+        pb_1.Message.initialize(this, Array.isArray(data) ? data : [], 0, -1, [2], this.#one_of_decls);
+        if (!Array.isArray(data) && typeof data == "object") {
+            if ("albumId" in data && data.albumId != undefined) {
+                this.albumId = data.albumId;
+            }
+            if ("photoIds" in data && data.photoIds != undefined) {
+                this.photoIds = data.photoIds;
+            }
+        }
+    }
+    get albumId() {
+        return pb_1.Message.getFieldWithDefault(this, 1, "") as string;
+    }
+    set albumId(value: string) {
+        pb_1.Message.setField(this, 1, value);
+    }
+    get photoIds() {
+        return pb_1.Message.getFieldWithDefault(this, 2, []) as string[];
+    }
+    set photoIds(value: string[]) {
+        pb_1.Message.setField(this, 2, value);
+    }
+    static fromObject(data: {
+        albumId?: string;
+        photoIds?: string[];
+    }): ReorderAlbumRequest {
+        const message = new ReorderAlbumRequest({});
+        if (data.albumId != null) {
+            message.albumId = data.albumId;
+        }
+        if (data.photoIds != null) {
+            message.photoIds = data.photoIds;
+        }
+        return message;
+    }
+    toObject() {
+        const data: {
+            albumId?: string;
+            photoIds?: string[];
+        } = {};
+        if (this.albumId != null) {
+            data.albumId = this.albumId;
+        }
+        if (this.photoIds != null) {
+            data.photoIds = this.photoIds;
+        }
+        return data;
+    }
+    serialize(): Uint8Array;
+    serialize(w: pb_1.BinaryWriter): void;
+    serialize(w?: pb_1.BinaryWriter): Uint8Array | void {
+        const writer = w || new pb_1.BinaryWriter();
+        if (this.albumId.length)
+            writer.writeString(1, this.albumId);
+        if (this.photoIds.length)
+            writer.writeRepeatedString(2, this.photoIds);
+        if (!w)
+            return writer.getResultBuffer();
+    }
+    static deserialize(bytes: Uint8Array | pb_1.BinaryReader): ReorderAlbumRequest {
+        const reader = bytes instanceof pb_1.BinaryReader ? bytes : new pb_1.BinaryReader(bytes), message = new ReorderAlbumRequest();
+        while (reader.nextField()) {
+            if (reader.isEndGroup())
+                break;
+            switch (reader.getFieldNumber()) {
+                case 1:
+                    message.albumId = reader.readString();
+                    break;
+                case 2:
+                    pb_1.Message.addToRepeatedField(message, 2, reader.readString());
+                    break;
+                default: reader.skipField();
+            }
+        }
+        return message;
+    }
+    serializeBinary(): Uint8Array {
+        return this.serialize();
+    }
+    static deserializeBinary(bytes: Uint8Array): ReorderAlbumRequest {
+        return ReorderAlbumRequest.deserialize(bytes);
+    }
+}
@@ -4,6 +4,7 @@ import {GetAlbumResponse} from "@/generated/payload/v1/album/get";
 import {CreateAlbumRequest, CreateAlbumResponse} from "@/generated/payload/v1/album/create";
 import {UpdateAlbumRequest} from "@/generated/payload/v1/album/update";
 import {DeleteAlbumRequest} from "@/generated/payload/v1/album/delete";
+import {ReorderAlbumRequest} from "@/generated/payload/v1/album/order";
 import {PhotoModel, protoPhotoToPhotoModel} from "@/views/photo/photo";
 import {AlbumWithCoverPhoto} from "@/generated/entity/album";
 
@@ -93,6 +94,22 @@ export async function saveEditedAlbum(album: AlbumModel): Promise<boolean | unde
     return true;
 }
 
+/**
+ * Set the order of the photos in an album.
+ * @param albumId The ID of the album
+ * @param photoIds The IDs of all photos in the album, in their new order.
+ * If empty, the photos are ordered by the time they were taken again.
+ * @return `true` on success. `undefined` on failure
+ */
+export async function reorderAlbum(albumId: string, photoIds: string[]): Promise<boolean | undefined> {
+    const result = await Http.patch('/api/v1/album/order', new ReorderAlbumRequest({
+        albumId,
+        photoIds,
+    }), null);
+
+    return result.ok ? true : undefined;
+}
+
 /**
  * List available albums
  *
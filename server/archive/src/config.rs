@@ -0,0 +1,70 @@
+use anyhow::{Error, Result};
+use serde::Deserialize;
+
+use dal::database::DbConfig;
+use dal::storage_engine::KeyLayout;
+
+/// The configuration of the instance to export from or import into.
+/// Uses the same environment variables as the server.
+#[derive(Debug, Deserialize)]
+pub struct Config {
+    /// Database host.
+    /// Non-standard ports are not supported and should not be provided.
+    pub db_host: Option<String>,
+    /// Database name
+    pub db_database: Option<String>,
+    /// Database username
+    pub db_username: Option<String>,
+    /// Database password
+    pub db_password: Option<String>,
+    /// Database connection url
+    pub db_url: Option<String>,
+
+    /// The name of the S3 bucket that should be used
+    pub s3_bucket_name: String,
+    /// The S3 region the endpoint is located in
+    pub s3_region: String,
+    /// S3 endpoint URL
+    pub s3_endpoint_url: String,
+    /// S3 secret key ID
+    pub s3_access_key_id: String,
+    /// S3 secret access key
+    pub s3_secret_access_key: String,
+    /// Force the use of path style bucket addressing.
+    pub s3_force_path_style: Option<bool>,
+    /// Create a bucket on startup. This should only
+    /// be used when working with MinIO.
+    pub s3_create_bucket_on_startup: Option<bool>,
+    /// The layout of the keys under which photos are stored.
+    /// Imported photos are stored in this layout.
+    s3_key_layout: Option<String>,
+}
+
+impl Config {
+    pub fn parse() -> envy::Result<Self> {
+        envy::from_env()
+    }
+
+    pub fn database_config(&self) -> Result<DbConfig> {
+        if let Some(url) = &self.db_url {
+            Ok(DbConfig::Url { url })
+        } else {
+            match (&self.db_host, &self.db_username, &self.db_password, &self.db_database) {
+                (Some(host), Some(user), Some(passw), Some(database)) => Ok(DbConfig::Parameters { host, user, passw, database }),
+                _ => Err(Error::msg("Database is configured incorrectly. You must specify either a `db_url` OR `db_host`, `db_username`, `db_password` and `db_database`"))
+            }
+        }
+    }
+
+    pub fn s3_force_path_style(&self) -> bool {
+        self.s3_force_path_style.unwrap_or(false)
+    }
+
+    pub fn s3_create_bucket_on_startup(&self) -> bool {
+        self.s3_create_bucket_on_startup.unwrap_or(false)
+    }
+
+    pub fn s3_key_layout(&self) -> &str {
+        self.s3_key_layout.as_deref().unwrap_or(KeyLayout::LEGACY)
+    }
+}
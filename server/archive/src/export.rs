@@ -0,0 +1,112 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use time::OffsetDateTime;
+use tracing::{info, warn};
+
+use dal::archive::ArchiveMetadata;
+use dal::database::{Database, PhotoObject, PhotoQuality};
+use dal::storage_engine::Storage;
+
+use crate::manifest::{
+    Manifest, ObjectEntry, FORMAT, MANIFEST_FILE, METADATA_FILE, OBJECTS_DIR, QUALITIES, VERSION,
+};
+
+/// Export all metadata and stored objects into a new archive directory.
+///
+/// # Errors
+///
+/// - If the directory already exists
+/// - If writing to the directory fails
+/// - If a database or storage error occurs
+pub async fn export(db: &Database, storage: &Storage, directory: &Path) -> Result<()> {
+    if directory.exists() {
+        bail!("'{}' already exists", directory.display());
+    }
+    tokio::fs::create_dir_all(directory.join(OBJECTS_DIR)).await?;
+
+    let created_at = OffsetDateTime::now_utc().unix_timestamp();
+
+    info!("Exporting metadata");
+    let metadata = ArchiveMetadata::export(db).await?;
+
+    let stored = storage
+        .list_objects()
+        .await?
+        .into_iter()
+        .map(|object| object.key)
+        .collect::<HashSet<_>>();
+    let keys = PhotoObject::list(db)
+        .await?
+        .into_iter()
+        .map(|object| ((object.photo_id, object.quality), object.object_key))
+        .collect::<HashMap<_, _>>();
+
+    let mut objects = Vec::new();
+    for (idx, photo) in metadata.photos.iter().enumerate() {
+        tokio::fs::create_dir_all(directory.join(OBJECTS_DIR).join(&photo.id)).await?;
+
+        for quality in QUALITIES {
+            let key = keys
+                .get(&(photo.id.clone(), quality.clone()))
+                .cloned()
+                .unwrap_or_else(|| PhotoObject::legacy_key(&photo.id, &quality));
+
+            // Qualities which were never created have nothing to export
+            if !stored.contains(&key) {
+                if quality == PhotoQuality::Original {
+                    warn!("Photo '{}' has no stored original, skipping", photo.id);
+                }
+                continue;
+            }
+
+            let bytes = storage.get_photo_bytes(&key).await?;
+            let path = format!("{OBJECTS_DIR}/{}/{quality}.webp", photo.id);
+            tokio::fs::write(directory.join(&path), &bytes).await?;
+
+            objects.push(ObjectEntry {
+                photo_id: photo.id.clone(),
+                quality: quality.to_string(),
+                path,
+                size: bytes.len() as u64,
+            });
+        }
+
+        if (idx + 1) % 100 == 0 {
+            info!("Exported {} of {} photos", idx + 1, metadata.photos.len());
+        }
+    }
+
+    tokio::fs::write(
+        directory.join(METADATA_FILE),
+        serde_json::to_vec_pretty(&metadata)?,
+    )
+    .await?;
+
+    let manifest = Manifest {
+        format: FORMAT.to_string(),
+        version: VERSION,
+        created_at,
+        albums: metadata.albums.len(),
+        photos: metadata.photos.len(),
+        collections: metadata.collections.len(),
+        users: metadata.users.len(),
+        objects,
+    };
+    tokio::fs::write(
+        directory.join(MANIFEST_FILE),
+        serde_json::to_vec_pretty(&manifest)?,
+    )
+    .await?;
+
+    info!(
+        "Exported {} albums, {} photos and {} objects to '{}'",
+        manifest.albums,
+        manifest.photos,
+        manifest.objects.len(),
+        directory.display()
+    );
+
+    Ok(())
+}
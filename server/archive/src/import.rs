@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Context, Result};
+use tracing::{info, warn};
+
+use dal::archive::{ArchiveMetadata, ImportedObject};
+use dal::database::Database;
+use dal::storage_engine::Storage;
+
+use crate::manifest::{is_valid_id, Manifest, FORMAT, MANIFEST_FILE, METADATA_FILE, VERSION};
+
+/// Import an archive into this instance.
+/// The objects are uploaded in the configured key layout first, after which the metadata
+/// and the keys of the objects are imported in a single transaction.
+/// If the import fails, the uploaded objects are removed again, so the import can be retried.
+///
+/// # Errors
+///
+/// - If the directory is not a valid archive
+/// - If an object lies outside of the archive, or an ID cannot be used in a storage key
+/// - If a photo in the archive already exists
+/// - If an album or collection in the archive already exists
+/// - If a database or storage error occurs
+pub async fn import(db: &Database, storage: &Storage, directory: &Path) -> Result<()> {
+    let manifest: Manifest = serde_json::from_slice(
+        &tokio::fs::read(directory.join(MANIFEST_FILE))
+            .await
+            .with_context(|| format!("'{}' is not an archive", directory.display()))?,
+    )?;
+
+    if manifest.format.ne(FORMAT) {
+        bail!("Unknown archive format '{}'", manifest.format);
+    }
+    if manifest.version > VERSION {
+        bail!(
+            "Archive version {} is newer than the supported version {VERSION}",
+            manifest.version
+        );
+    }
+
+    let metadata: ArchiveMetadata =
+        serde_json::from_slice(&tokio::fs::read(directory.join(METADATA_FILE)).await?)?;
+
+    let album_ids = metadata
+        .photos
+        .iter()
+        .map(|photo| (photo.id.as_str(), photo.album_id.as_str()))
+        .collect::<HashMap<_, _>>();
+
+    // The IDs are used in the keys of the uploaded objects
+    for (photo_id, album_id) in &album_ids {
+        if !is_valid_id(photo_id) {
+            bail!("Photo ID '{photo_id}' is not valid");
+        }
+        if !is_valid_id(album_id) {
+            bail!("Album ID '{album_id}' of photo '{photo_id}' is not valid");
+        }
+    }
+
+    // Check the archive is complete before touching the database
+    for object in &manifest.objects {
+        let path = object
+            .relative_path()
+            .ok_or_else(|| anyhow!("Object '{}' is outside of the archive", object.path))?;
+        if object.quality().is_none() {
+            bail!("Object '{}' has an unknown quality", object.path);
+        }
+        if !album_ids.contains_key(object.photo_id.as_str()) {
+            bail!("Object '{}' belongs to an unknown photo", object.path);
+        }
+
+        // Links could point outside of the archive
+        let file = tokio::fs::symlink_metadata(directory.join(path))
+            .await
+            .with_context(|| format!("Object '{}' is missing", object.path))?;
+        if !file.is_file() {
+            bail!("Object '{}' is not a file", object.path);
+        }
+
+        let size = file.len();
+        if size != object.size {
+            bail!(
+                "Object '{}' is {size} bytes, expected {} bytes",
+                object.path,
+                object.size
+            );
+        }
+    }
+
+    // Objects of existing photos may be stored under the same keys, they must not be overwritten
+    let existing = metadata.existing_photo_ids(db).await?;
+    if let Some(photo_id) = existing.first() {
+        bail!(
+            "{} photos in the archive already exist, including '{photo_id}'",
+            existing.len()
+        );
+    }
+
+    let mut imported = Vec::with_capacity(manifest.objects.len());
+    let result = async {
+        for (idx, object) in manifest.objects.iter().enumerate() {
+            let quality = object
+                .quality()
+                .ok_or_else(|| anyhow!("Object '{}' has an unknown quality", object.path))?;
+            let album_id = album_ids[object.photo_id.as_str()];
+
+            let path = object
+                .relative_path()
+                .ok_or_else(|| anyhow!("Object '{}' is outside of the archive", object.path))?;
+            let bytes = tokio::fs::read(directory.join(path)).await?;
+            let key =
+                storage
+                    .key_layout()
+                    .render(album_id, &object.photo_id, &quality, Some(&bytes));
+            storage.create_photo(&key, bytes).await?;
+
+            imported.push(ImportedObject {
+                photo_id: object.photo_id.clone(),
+                quality,
+                url: storage.get_photo_url(&key),
+                object_key: key,
+            });
+
+            if (idx + 1) % 100 == 0 {
+                info!("Uploaded {} of {} objects", idx + 1, manifest.objects.len());
+            }
+        }
+
+        info!("Importing metadata");
+        metadata.import(db, &imported).await?;
+
+        Ok::<_, anyhow::Error>(())
+    }
+    .await;
+
+    if let Err(e) = result {
+        remove_objects(storage, &imported).await;
+        return Err(e);
+    }
+
+    info!(
+        "Imported {} albums, {} photos and {} objects from '{}'",
+        manifest.albums,
+        manifest.photos,
+        manifest.objects.len(),
+        directory.display()
+    );
+
+    Ok(())
+}
+
+/// Remove the objects uploaded by a failed import.
+/// Objects which cannot be removed are reported, they are found by a consistency check as well.
+async fn remove_objects(storage: &Storage, objects: &[ImportedObject]) {
+    info!("Removing {} uploaded objects", objects.len());
+    for object in objects {
+        if let Err(e) = storage.delete_photo(&object.object_key).await {
+            warn!(
+                "Failed to remove uploaded object '{}': {e}",
+                object.object_key
+            );
+        }
+    }
+}
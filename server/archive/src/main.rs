@@ -0,0 +1,108 @@
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use clap::{Parser, Subcommand};
+use dotenv::dotenv;
+use tracing::level_filters::LevelFilter;
+use tracing::{info, warn};
+use tracing_subscriber::fmt::layer;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::EnvFilter;
+
+use dal::database::Database;
+use dal::storage_engine::{KeyLayout, S3Config, Storage};
+
+use crate::config::Config;
+
+mod config;
+mod export;
+mod import;
+mod manifest;
+
+/// Back up a chroma instance to an archive, or restore an archive into an instance.
+/// Albums with their members, photos, collections, users with their scopes and roles
+/// and all stored photos are included, with their original ids and timestamps.
+///
+/// The instance is configured with the same environment variables as the server.
+#[derive(Parser)]
+#[command(version)]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Export the instance into a new archive directory
+    Export {
+        /// The directory to write the archive to, must not exist yet
+        directory: PathBuf,
+    },
+    /// Import an archive into the instance.
+    /// Fails without changes if any of the archived albums, photos or collections already exist
+    Import {
+        /// The directory containing the archive
+        directory: PathBuf,
+    },
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+
+    // Try to load the environment variables from the .env file
+    let dotenv_err = dotenv().err();
+
+    init_tracing();
+
+    if let Some(err) = dotenv_err {
+        warn!("failed to load .env file: {:#}", err);
+    }
+
+    let config = Config::parse().map_err(|err| anyhow!("failed to parse config: {:#}", err))?;
+    let db = init_database(&config).await?;
+    let storage = init_storage(&config).await?;
+
+    match args.command {
+        Command::Export { directory } => export::export(&db, &storage, &directory).await,
+        Command::Import { directory } => import::import(&db, &storage, &directory).await,
+    }
+}
+
+fn init_tracing() {
+    tracing_subscriber::registry()
+        .with(layer().compact())
+        .with(
+            EnvFilter::builder()
+                .with_default_directive(LevelFilter::INFO.into())
+                .from_env_lossy(),
+        )
+        .init();
+}
+
+async fn init_database(config: &Config) -> Result<Database> {
+    info!("initializing database connection");
+    Database::new(config.database_config()?)
+        .await
+        .map_err(|err| anyhow!("failed to initialize database connection: {:#}", err))
+}
+
+async fn init_storage(config: &Config) -> Result<Storage> {
+    info!("initializing S3 storage engine");
+    let key_layout = KeyLayout::new(config.s3_key_layout())
+        .map_err(|err| anyhow!("invalid S3 key layout: {:#}", err))?;
+
+    Storage::new(S3Config {
+        bucket_name: config.s3_bucket_name.clone(),
+        endpoint_url: config.s3_endpoint_url.clone(),
+        region: config.s3_region.clone(),
+        access_key_id: config.s3_access_key_id.clone(),
+        secret_access_key: config.s3_secret_access_key.clone(),
+        use_path_style: config.s3_force_path_style(),
+        create_bucket: config.s3_create_bucket_on_startup(),
+        key_layout,
+    })
+    .await
+    .map_err(|err| anyhow!("failed to initialize S3 storage engine: {:#}", err))
+}
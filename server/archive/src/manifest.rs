@@ -0,0 +1,133 @@
+use std::path::{Component, Path};
+
+use serde::{Deserialize, Serialize};
+
+use dal::database::{Photo, PhotoQuality};
+
+/// Identifies a directory as a chroma archive
+pub const FORMAT: &str = "chroma-archive";
+/// The version of the archive layout, incremented on incompatible changes
+pub const VERSION: u32 = 1;
+
+/// Describes the archive, written last so an incomplete export is not mistaken for an archive
+pub const MANIFEST_FILE: &str = "manifest.json";
+/// Holds the [dal::archive::ArchiveMetadata]
+pub const METADATA_FILE: &str = "metadata.json";
+/// Holds the stored objects, as `<photo id>/<quality>.webp`
+pub const OBJECTS_DIR: &str = "objects";
+
+/// The qualities in which a photo may be stored
+pub const QUALITIES: [PhotoQuality; 3] = [
+    PhotoQuality::Original,
+    PhotoQuality::W400,
+    PhotoQuality::W1600,
+];
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Always [FORMAT]
+    pub format: String,
+    /// The [VERSION] of the tool which wrote the archive
+    pub version: u32,
+    /// UNIX timestamp at which the export started
+    pub created_at: i64,
+    pub albums: usize,
+    pub photos: usize,
+    pub collections: usize,
+    pub users: usize,
+    /// Every stored object in the archive.
+    /// Photos of which a quality was never created have no object for it.
+    pub objects: Vec<ObjectEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ObjectEntry {
+    pub photo_id: String,
+    pub quality: String,
+    /// Path relative to the root of the archive
+    pub path: String,
+    /// Size in bytes
+    pub size: u64,
+}
+
+impl ObjectEntry {
+    /// The quality of the object, or `None` if it is not known to this version
+    pub fn quality(&self) -> Option<PhotoQuality> {
+        QUALITIES
+            .into_iter()
+            .find(|quality| quality.to_string().eq(&self.quality))
+    }
+
+    /// The path of the object relative to the root of the archive,
+    /// or `None` if it does not point to a file in [OBJECTS_DIR].
+    pub fn relative_path(&self) -> Option<&Path> {
+        let path = Path::new(&self.path);
+        let mut components = path.components();
+        if components.next() != Some(Component::Normal(OBJECTS_DIR.as_ref())) {
+            return None;
+        }
+
+        let mut is_empty = true;
+        for component in components {
+            if !matches!(component, Component::Normal(_)) {
+                return None;
+            }
+            is_empty = false;
+        }
+
+        (!is_empty).then_some(path)
+    }
+}
+
+/// Whether an ID from an archive may be used in a storage key.
+/// IDs generated by chroma consist of letters, digits and underscores.
+pub fn is_valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= Photo::MAX_ID_LEN
+        && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(path: &str) -> ObjectEntry {
+        ObjectEntry {
+            photo_id: "PH_abc".into(),
+            quality: "Original".into(),
+            path: path.into(),
+            size: 0,
+        }
+    }
+
+    #[test]
+    fn relative_path_stays_in_objects_dir() {
+        assert!(entry("objects/PH_abc/Original.webp")
+            .relative_path()
+            .is_some());
+
+        for path in [
+            "",
+            "objects",
+            "metadata.json",
+            "/etc/passwd",
+            "./objects/PH_abc/Original.webp",
+            "objects/../metadata.json",
+            "objects/PH_abc/../../../etc/passwd",
+            "other/PH_abc/Original.webp",
+        ] {
+            assert!(entry(path).relative_path().is_none(), "{path}");
+        }
+    }
+
+    #[test]
+    fn ids_are_validated() {
+        assert!(is_valid_id("PH_abc123"));
+        assert!(is_valid_id("ALB_abc123"));
+
+        assert!(!is_valid_id(""));
+        assert!(!is_valid_id("../PH_abc"));
+        assert!(!is_valid_id("PH_abc/Original"));
+        assert!(!is_valid_id(&"a".repeat(Photo::MAX_ID_LEN + 1)));
+    }
+}
@@ -1,8 +1,15 @@
+use std::net::IpAddr;
+use std::num::NonZeroU32;
+use std::time::Duration;
+
 use anyhow::{Error, Result};
 use serde::Deserialize;
 use tracing::{info, warn};
 
 use dal::database::DbConfig;
+use dal::storage_engine::KeyLayout;
+
+use crate::identity::{KoalaConfig, OidcConfig};
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct Config {
@@ -43,11 +50,24 @@ pub struct Config {
     /// be used when working with MinIO.
     /// The provided access key should have bucket creation privileges.
     pub s3_create_bucket_on_startup: Option<bool>,
+    /// The layout of the keys under which photos are stored.
+    /// May contain the placeholders `{album_id}`, `{photo_id}`, `{quality}` and `{hash}`,
+    /// of which `{photo_id}` and `{quality}` are required.
+    /// E.g. `albums/{album_id}/{photo_id}/{quality}.webp`.
+    /// Existing objects are moved to a new layout with the `/api/v1/admin/relayout` endpoint.
+    /// If not provided, the default [Config::DEFAULT_S3_KEY_LAYOUT] will be used.
+    s3_key_layout: Option<String>,
 
-    /// OAuth2 client ID created in Koala
-    pub koala_client_id: String,
+    /// The identity provider users log in with, either `koala` or `oidc`.
+    /// If not provided, Koala is used.
+    identity_provider: Option<String>,
+
+    /// OAuth2 client ID created in Koala.
+    /// Required if `identity_provider` is `koala`.
+    pub koala_client_id: Option<String>,
     /// OAuth2 client secret created in Koala.
-    pub koala_client_secret: String,
+    /// Required if `identity_provider` is `koala`.
+    pub koala_client_secret: Option<String>,
     /// Koala's base URL for redirecting a client
     /// Used to redirect clients to Koala's OAuth.
     /// Useful if the public route is different from the route from
@@ -62,15 +82,42 @@ pub struct Config {
     /// Used for making requests to Koala.
     /// Should not end with a trailing `/`.
     /// E.g. `https://koala.svsticky.nl`
-    pub koala_base_uri: String,
+    /// Required if `identity_provider` is `koala`.
+    pub koala_base_uri: Option<String>,
     /// The URI to which Koala should redirect back after login.
     /// This should consist of the base url, i.e. where Chroma is accessed via the browser,
     /// with the path `/api/v1/login` appended to it.
     /// E.g. `https://chroma.example.com/api/v1/login` would be a correct value.
-    pub koala_oauth_redirect_uri: String,
+    /// Required if `identity_provider` is `koala`.
+    pub koala_oauth_redirect_uri: Option<String>,
     /// The user agent to be sent when making requests to Koala.
     /// If not provided, the default [Config::DEFAULT_KOALA_USER_AGENT] will be used.
     koala_user_agent: Option<String>,
+
+    /// The issuer URL of the OpenID Connect provider.
+    /// The provider's configuration is discovered from
+    /// `{oidc_issuer_url}/.well-known/openid-configuration`.
+    /// E.g. `https://auth.example.com/realms/sticky`.
+    /// Required if `identity_provider` is `oidc`.
+    pub oidc_issuer_url: Option<String>,
+    /// OAuth2 client ID created at the OpenID Connect provider.
+    /// Required if `identity_provider` is `oidc`.
+    pub oidc_client_id: Option<String>,
+    /// OAuth2 client secret created at the OpenID Connect provider.
+    /// Required if `identity_provider` is `oidc`.
+    pub oidc_client_secret: Option<String>,
+    /// The URI to which the OpenID Connect provider should redirect back after login.
+    /// Like `koala_oauth_redirect_uri`, this is where Chroma is accessed via the browser,
+    /// with the path `/api/v1/login` appended to it.
+    /// Required if `identity_provider` is `oidc`.
+    pub oidc_redirect_uri: Option<String>,
+    /// The scopes requested from the OpenID Connect provider, separated by spaces.
+    /// If not provided, the default [Config::DEFAULT_OIDC_SCOPES] will be used.
+    oidc_scopes: Option<String>,
+    /// Users in this group, according to the `groups` claim, are admins.
+    /// If not provided, no user logging in through OpenID Connect is an admin.
+    pub oidc_admin_group: Option<String>,
+
     /// The URI to which Chroma should redirect after the user
     /// has logged in. Refer to the UIs documentation
     /// for what this value should be.
@@ -79,29 +126,124 @@ pub struct Config {
     /// E.g. `https://foo.example.com/logged_in` will become
     /// `https://foo.example.com/logged_in?session_id={AN ID}&is_admin=[true|false]`.
     pub login_complete_redirect_uri: String,
+    /// Grants which members of Koala groups or committees receive automatically,
+    /// in the form `group=grant,grant;group=grant`.
+    /// Grants are scopes, which may be wildcards, or role names prefixed with `role:`.
+    /// E.g. `Fotocommissie=nl.svsticky.chroma.photo.*,role:Photographer;Bestuur=role:Album editor`.
+    /// Synced grants are kept apart from grants made by admins, and are updated on login
+    /// and periodically afterwards. If not provided, no grants are synced.
+    /// With `identity_provider` set to `oidc`, the groups are those of the `groups` claim.
+    koala_group_mappings: Option<String>,
 
-    /// Comma-seperated list of service tokens.
-    /// When making a request, use the following for the `Authorization` header:
-    /// ```
-    /// Authorization: Service <YOUR TOKEN>
-    /// ```
-    ///
-    /// # Warning
-    /// Service tokens can access all APIs, even admin ones!
-    pub service_tokens: String,
+    /// The number of days albums and photos are kept in the trash
+    /// before they are permanently deleted.
+    /// If not provided, the default [Config::DEFAULT_TRASH_RETENTION_DAYS] will be used.
+    trash_retention_days: Option<u64>,
+
+    /// The maximum total size in megabytes of the photos in an album export.
+    /// Exports are limited to 4 GiB regardless of this value.
+    /// If not provided, the default [Config::DEFAULT_EXPORT_MAX_SIZE_MB] will be used.
+    export_max_size_mb: Option<u64>,
+
+    /// The number of photos a user or API key may upload per minute.
+    /// If not provided, the default [Config::DEFAULT_RATELIMIT_PHOTO_CREATE_PER_MINUTE] will be used.
+    ratelimit_photo_create_per_minute: Option<NonZeroU32>,
+    /// The number of photos a user or API key may retrieve converted to PNG or JPEG,
+    /// or as bytes instead of a URL, per minute.
+    /// If not provided, the default [Config::DEFAULT_RATELIMIT_PHOTO_CONVERT_PER_MINUTE] will be used.
+    ratelimit_photo_convert_per_minute: Option<NonZeroU32>,
+    /// The number of albums a user or API key may export per hour.
+    /// If not provided, the default [Config::DEFAULT_RATELIMIT_ALBUM_EXPORT_PER_HOUR] will be used.
+    ratelimit_album_export_per_hour: Option<NonZeroU32>,
+    /// How many times more requests than a single user may be made from a single IP address.
+    /// Users behind the same network share an IP address.
+    /// If not provided, the default [Config::DEFAULT_RATELIMIT_IP_FACTOR] will be used.
+    ratelimit_ip_factor: Option<NonZeroU32>,
+    /// IP addresses of reverse proxies in front of Chroma, separated by commas.
+    /// Requests from these addresses are ratelimited by the address in the
+    /// `X-Forwarded-For` header the proxies add, rather than by the address of the proxy.
+    /// If not provided, the `X-Forwarded-For` header is ignored.
+    ratelimit_trusted_proxies: Option<Vec<IpAddr>>,
     // ANCHOR_END: config
 }
 
 impl Config {
+    /// The default identity provider when none is configured
+    const DEFAULT_IDENTITY_PROVIDER: &'static str = "koala";
     /// The default user agent for Koala when none is configured
     const DEFAULT_KOALA_USER_AGENT: &'static str = "Chroma server";
+    /// The default number of days items are kept in the trash when none is configured
+    const DEFAULT_TRASH_RETENTION_DAYS: u64 = 30;
+    /// The default maximum size of an album export when none is configured
+    const DEFAULT_EXPORT_MAX_SIZE_MB: u64 = 2048;
+    /// The default number of photos uploaded per minute when none is configured
+    const DEFAULT_RATELIMIT_PHOTO_CREATE_PER_MINUTE: u32 = 60;
+    /// The default number of photos converted per minute when none is configured
+    const DEFAULT_RATELIMIT_PHOTO_CONVERT_PER_MINUTE: u32 = 60;
+    /// The default number of albums exported per hour when none is configured
+    const DEFAULT_RATELIMIT_ALBUM_EXPORT_PER_HOUR: u32 = 10;
+    /// The default factor of the ratelimits per IP address when none is configured
+    const DEFAULT_RATELIMIT_IP_FACTOR: u32 = 10;
+    /// The default storage key layout when none is configured
+    const DEFAULT_S3_KEY_LAYOUT: &'static str = KeyLayout::LEGACY;
+    /// The default scopes requested from an OpenID Connect provider when none are configured
+    const DEFAULT_OIDC_SCOPES: &'static str = "openid profile email";
 
-    pub fn oauth_client_config(&self) -> cabbage::oauth::ClientConfig {
-        cabbage::oauth::ClientConfig::new(
-            self.koala_client_id.clone(),
-            self.koala_client_secret.clone(),
-            self.koala_oauth_redirect_uri.clone(),
-        )
+    /// The identity provider users log in with.
+    ///
+    /// See also: `identity_provider` field.
+    pub fn identity_provider(&self) -> &str {
+        self.identity_provider
+            .as_deref()
+            .unwrap_or(Self::DEFAULT_IDENTITY_PROVIDER)
+    }
+
+    /// Get the Koala configuration specified by the configuration
+    ///
+    /// # Errors
+    ///
+    /// If not all required Koala variables are present
+    pub fn koala_config(&self) -> Result<KoalaConfig> {
+        match (
+            &self.koala_base_uri,
+            &self.koala_client_id,
+            &self.koala_client_secret,
+            &self.koala_oauth_redirect_uri,
+        ) {
+            (Some(base_uri), Some(client_id), Some(client_secret), Some(redirect_uri)) => Ok(KoalaConfig {
+                base_redirect_uri: self.koala_base_redirect_uri.clone().unwrap_or_else(|| base_uri.clone()),
+                base_uri: base_uri.clone(),
+                client_id: client_id.clone(),
+                client_secret: client_secret.clone(),
+                redirect_uri: redirect_uri.clone(),
+                user_agent: self.koala_user_agent().to_string(),
+            }),
+            _ => Err(Error::msg("Koala is configured incorrectly. You must specify `koala_base_uri`, `koala_client_id`, `koala_client_secret` and `koala_oauth_redirect_uri`"))
+        }
+    }
+
+    /// Get the OpenID Connect configuration specified by the configuration
+    ///
+    /// # Errors
+    ///
+    /// If not all required OpenID Connect variables are present
+    pub fn oidc_config(&self) -> Result<OidcConfig> {
+        match (
+            &self.oidc_issuer_url,
+            &self.oidc_client_id,
+            &self.oidc_client_secret,
+            &self.oidc_redirect_uri,
+        ) {
+            (Some(issuer_url), Some(client_id), Some(client_secret), Some(redirect_uri)) => Ok(OidcConfig {
+                issuer_url: issuer_url.clone(),
+                client_id: client_id.clone(),
+                client_secret: client_secret.clone(),
+                redirect_uri: redirect_uri.clone(),
+                scopes: self.oidc_scopes.clone().unwrap_or_else(|| Self::DEFAULT_OIDC_SCOPES.to_string()),
+                admin_group: self.oidc_admin_group.clone(),
+            }),
+            _ => Err(Error::msg("OpenID Connect is configured incorrectly. You must specify `oidc_issuer_url`, `oidc_client_id`, `oidc_client_secret` and `oidc_redirect_uri`"))
+        }
     }
 
     /// Get the database configuration specified by the configuration
@@ -124,6 +266,10 @@ impl Config {
     pub fn parse() -> envy::Result<Self> {
         let this: Self = envy::from_env()?;
 
+        if this.identity_provider().ne(Self::DEFAULT_IDENTITY_PROVIDER) {
+            return Ok(this);
+        }
+
         if this.koala_base_redirect_uri.is_none() {
             info!("'koala_base_redirect_uri' is not provided, using 'koala_base_uri' instead");
         }
@@ -141,20 +287,17 @@ impl Config {
     /// Get the User-Agent to use when sending requests to Koala
     ///
     /// See also: `koala_user_agent` fields
-    #[allow(unused)] // TODO
     pub fn koala_user_agent(&self) -> &str {
         self.koala_user_agent
             .as_deref()
             .unwrap_or(Self::DEFAULT_KOALA_USER_AGENT)
     }
 
-    /// Koala's base URL for redirecting a client.
+    /// Mappings from Koala groups to the grants their members receive.
     ///
-    /// See also: `koala_base_redirect_uri` field
-    pub fn koala_base_redirect_uri(&self) -> &String {
-        self.koala_base_redirect_uri
-            .as_ref()
-            .unwrap_or(&self.koala_base_uri)
+    /// See also: `koala_group_mappings` field.
+    pub fn koala_group_mappings(&self) -> &str {
+        self.koala_group_mappings.as_deref().unwrap_or_default()
     }
 
     /// Force S3 path styles instead of virtual hosts.
@@ -171,9 +314,79 @@ impl Config {
         self.s3_create_bucket_on_startup.unwrap_or(false)
     }
 
-    /// Get configured service tokens
-    pub fn service_tokens(&self) -> Vec<&str> {
-        self.service_tokens.split(',').collect()
+    /// The layout of the keys under which photos are stored.
+    ///
+    /// See also: `s3_key_layout` field.
+    pub fn s3_key_layout(&self) -> &str {
+        self.s3_key_layout
+            .as_deref()
+            .unwrap_or(Self::DEFAULT_S3_KEY_LAYOUT)
+    }
+
+    /// How long albums and photos are kept in the trash before they are permanently deleted.
+    ///
+    /// See also: `trash_retention_days` field.
+    pub fn trash_retention(&self) -> Duration {
+        Duration::from_secs(
+            self.trash_retention_days
+                .unwrap_or(Self::DEFAULT_TRASH_RETENTION_DAYS)
+                * 24
+                * 60
+                * 60,
+        )
+    }
+
+    /// The maximum total size in bytes of the photos in an album export.
+    ///
+    /// See also: `export_max_size_mb` field.
+    pub fn export_max_size(&self) -> u64 {
+        self.export_max_size_mb
+            .unwrap_or(Self::DEFAULT_EXPORT_MAX_SIZE_MB)
+            .saturating_mul(1024 * 1024)
+    }
+
+    /// The number of photos a user or API key may upload per minute.
+    ///
+    /// See also: `ratelimit_photo_create_per_minute` field.
+    pub fn ratelimit_photo_create_per_minute(&self) -> NonZeroU32 {
+        self.ratelimit_photo_create_per_minute.unwrap_or_else(|| {
+            NonZeroU32::new(Self::DEFAULT_RATELIMIT_PHOTO_CREATE_PER_MINUTE).unwrap()
+        })
+    }
+
+    /// The number of photos a user or API key may retrieve converted per minute.
+    ///
+    /// See also: `ratelimit_photo_convert_per_minute` field.
+    pub fn ratelimit_photo_convert_per_minute(&self) -> NonZeroU32 {
+        self.ratelimit_photo_convert_per_minute.unwrap_or_else(|| {
+            NonZeroU32::new(Self::DEFAULT_RATELIMIT_PHOTO_CONVERT_PER_MINUTE).unwrap()
+        })
+    }
+
+    /// The number of albums a user or API key may export per hour.
+    ///
+    /// See also: `ratelimit_album_export_per_hour` field.
+    pub fn ratelimit_album_export_per_hour(&self) -> NonZeroU32 {
+        self.ratelimit_album_export_per_hour.unwrap_or_else(|| {
+            NonZeroU32::new(Self::DEFAULT_RATELIMIT_ALBUM_EXPORT_PER_HOUR).unwrap()
+        })
+    }
+
+    /// How many times more requests than a single user may be made from a single IP address.
+    ///
+    /// See also: `ratelimit_ip_factor` field.
+    pub fn ratelimit_ip_factor(&self) -> NonZeroU32 {
+        self.ratelimit_ip_factor
+            .unwrap_or_else(|| NonZeroU32::new(Self::DEFAULT_RATELIMIT_IP_FACTOR).unwrap())
+    }
+
+    /// IP addresses of reverse proxies whose `X-Forwarded-For` header is trusted.
+    ///
+    /// See also: `ratelimit_trusted_proxies` field.
+    pub fn ratelimit_trusted_proxies(&self) -> &[IpAddr] {
+        self.ratelimit_trusted_proxies
+            .as_deref()
+            .unwrap_or_default()
     }
 
     /// Check if the configuration is valid.
@@ -0,0 +1,305 @@
+use async_trait::async_trait;
+use cabbage::oauth::ClientConfig;
+use cabbage::KoalaApi;
+use reqwest::header::USER_AGENT;
+use serde::Deserialize;
+
+use dal::database::OAuthAccess;
+
+use crate::identity::{
+    IdentityError, IdentityProvider, LoginStart, LoginTokens, PendingLogin, UserInfo,
+};
+
+/// The path of Koala's OpenID userinfo endpoint, which lists the groups of the user
+const USERINFO_PATH: &str = "/oauth/userinfo";
+
+pub struct KoalaConfig {
+    pub base_uri: String,
+    pub base_redirect_uri: String,
+    pub client_id: String,
+    pub client_secret: String,
+    pub redirect_uri: String,
+    pub user_agent: String,
+}
+
+/// Koala, the member administration of Sticky.
+/// Koala IDs are used as user IDs.
+#[derive(Debug)]
+pub struct KoalaProvider {
+    koala: KoalaApi,
+    client: reqwest::Client,
+    base_uri: String,
+    client_id: String,
+    client_secret: String,
+    redirect_uri: String,
+    user_agent: String,
+}
+
+/// A userinfo response without groups is an error rather than no groups,
+/// so grants synced from groups are kept if Koala stops listing them.
+#[derive(Deserialize)]
+struct KoalaGroups {
+    groups: Vec<String>,
+}
+
+impl KoalaProvider {
+    /// The issuer under which Koala users would be linked
+    const ISSUER: &'static str = "koala";
+
+    /// # Errors
+    ///
+    /// If the base redirect URI is invalid
+    pub fn new(config: KoalaConfig) -> anyhow::Result<Self> {
+        Ok(Self {
+            koala: KoalaApi::new(config.base_redirect_uri)?,
+            client: reqwest::Client::new(),
+            base_uri: config.base_uri,
+            client_id: config.client_id,
+            client_secret: config.client_secret,
+            redirect_uri: config.redirect_uri,
+            user_agent: config.user_agent,
+        })
+    }
+
+    fn client_config(&self) -> ClientConfig {
+        ClientConfig::new(
+            self.client_id.clone(),
+            self.client_secret.clone(),
+            self.redirect_uri.clone(),
+        )
+    }
+}
+
+#[async_trait]
+impl IdentityProvider for KoalaProvider {
+    fn issuer(&self) -> &str {
+        Self::ISSUER
+    }
+
+    fn login_uri(&self) -> String {
+        self.koala
+            .oauth_api(self.client_config())
+            .get_login_redirect_uri()
+    }
+
+    fn start_login(&self) -> LoginStart {
+        LoginStart {
+            url: self.login_uri(),
+            pending: None,
+        }
+    }
+
+    async fn exchange_code(
+        &self,
+        code: &str,
+        _: Option<&PendingLogin>,
+    ) -> Result<LoginTokens, IdentityError> {
+        let tokens = self
+            .koala
+            .oauth_api(self.client_config())
+            .exchange_login_code(code)
+            .await?;
+
+        Ok(LoginTokens {
+            access: OAuthAccess {
+                access_token: tokens.access_token,
+                refresh_token: tokens.refresh_token,
+                expires_at: tokens.expires_at,
+            },
+            subject: None,
+        })
+    }
+
+    async fn refresh(&self, refresh_token: &str) -> Result<OAuthAccess, IdentityError> {
+        let tokens = self
+            .koala
+            .oauth_api(self.client_config())
+            .exchange_refresh_token(refresh_token)
+            .await?;
+
+        Ok(OAuthAccess {
+            access_token: tokens.access_token,
+            refresh_token: tokens.refresh_token,
+            expires_at: tokens.expires_at,
+        })
+    }
+
+    async fn userinfo(&self, access_token: &str) -> Result<UserInfo, IdentityError> {
+        let userinfo = self
+            .koala
+            .oauth_api(self.client_config())
+            .get_userinfo(access_token)
+            .await?;
+
+        Ok(UserInfo {
+            subject: userinfo.koala_id.to_string(),
+            user_id: Some(userinfo.koala_id),
+            name: userinfo.full_name,
+            is_admin: userinfo.is_admin,
+        })
+    }
+
+    async fn groups(&self, access_token: &str) -> Result<Vec<String>, IdentityError> {
+        let groups: KoalaGroups = self
+            .client
+            .get(format!("{}{USERINFO_PATH}", self.base_uri))
+            .bearer_auth(access_token)
+            .header(USER_AGENT, &self.user_agent)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(groups.groups)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mock_koala::{MockKoala, MockUser};
+
+    use crate::identity::{IdentityError, IdentityProvider, KoalaProvider};
+    use crate::testing;
+
+    const KOALA_ID: i32 = 42;
+
+    async fn setup() -> (MockKoala, KoalaProvider) {
+        let koala = testing::start_koala(vec![MockUser::new(KOALA_ID, "Jan", "Jansen")
+            .admin()
+            .with_groups(&["Fotocommissie"])])
+        .await;
+        let provider = testing::koala_provider(&koala);
+        (koala, provider)
+    }
+
+    #[actix_web::test]
+    async fn login_with_code() {
+        let (koala, provider) = setup().await;
+
+        let tokens = provider
+            .exchange_code(&koala.issue_code(KOALA_ID), None)
+            .await
+            .unwrap();
+        let userinfo = provider
+            .userinfo(&tokens.access.access_token)
+            .await
+            .unwrap();
+
+        assert_eq!(userinfo.user_id, Some(KOALA_ID));
+        assert_eq!(userinfo.subject, KOALA_ID.to_string());
+        assert!(userinfo.is_admin);
+    }
+
+    #[actix_web::test]
+    async fn codes_are_single_use() {
+        let (koala, provider) = setup().await;
+        let code = koala.issue_code(KOALA_ID);
+
+        assert!(provider.exchange_code(&code, None).await.is_ok());
+        assert!(matches!(
+            provider.exchange_code(&code, None).await,
+            Err(IdentityError::Rejected)
+        ));
+        assert!(matches!(
+            provider.exchange_code("invalid", None).await,
+            Err(IdentityError::Rejected)
+        ));
+    }
+
+    #[actix_web::test]
+    async fn refresh_replaces_expired_tokens() {
+        let (koala, provider) = setup().await;
+        let tokens = provider
+            .exchange_code(&koala.issue_code(KOALA_ID), None)
+            .await
+            .unwrap();
+
+        koala.expire_access_tokens(KOALA_ID);
+        assert!(matches!(
+            provider.userinfo(&tokens.access.access_token).await,
+            Err(IdentityError::Rejected)
+        ));
+
+        let refreshed = provider
+            .refresh(&tokens.access.refresh_token)
+            .await
+            .unwrap();
+        assert!(provider.userinfo(&refreshed.access_token).await.is_ok());
+
+        // Refresh tokens are rotated
+        assert!(matches!(
+            provider.refresh(&tokens.access.refresh_token).await,
+            Err(IdentityError::Rejected)
+        ));
+    }
+
+    #[actix_web::test]
+    async fn revoked_tokens_are_rejected() {
+        let (koala, provider) = setup().await;
+        let tokens = provider
+            .exchange_code(&koala.issue_code(KOALA_ID), None)
+            .await
+            .unwrap();
+
+        koala.revoke_tokens(KOALA_ID);
+
+        assert!(matches!(
+            provider.userinfo(&tokens.access.access_token).await,
+            Err(IdentityError::Rejected)
+        ));
+        assert!(matches!(
+            provider.refresh(&tokens.access.refresh_token).await,
+            Err(IdentityError::Rejected)
+        ));
+    }
+
+    #[actix_web::test]
+    async fn forbidden_users_are_forbidden() {
+        let (koala, provider) = setup().await;
+        let tokens = provider
+            .exchange_code(&koala.issue_code(KOALA_ID), None)
+            .await
+            .unwrap();
+
+        koala.update_user(KOALA_ID, |user| user.forbidden = true);
+
+        assert!(matches!(
+            provider.userinfo(&tokens.access.access_token).await,
+            Err(IdentityError::Forbidden)
+        ));
+    }
+
+    #[actix_web::test]
+    async fn groups_follow_koala() {
+        let (koala, provider) = setup().await;
+        let tokens = provider
+            .exchange_code(&koala.issue_code(KOALA_ID), None)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            provider.groups(&tokens.access.access_token).await.unwrap(),
+            vec!["Fotocommissie".to_string()]
+        );
+
+        koala.update_user(KOALA_ID, |user| user.groups.clear());
+        assert!(provider
+            .groups(&tokens.access.access_token)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[actix_web::test]
+    async fn missing_groups_are_an_error() {
+        let (koala, provider) = setup().await;
+        let tokens = provider
+            .exchange_code(&koala.issue_code(KOALA_ID), None)
+            .await
+            .unwrap();
+
+        koala.update_user(KOALA_ID, |user| user.omit_groups = true);
+        assert!(provider.groups(&tokens.access.access_token).await.is_err());
+    }
+}
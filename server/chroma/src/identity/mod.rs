@@ -0,0 +1,117 @@
+use std::fmt::Debug;
+
+use async_trait::async_trait;
+use thiserror::Error;
+
+use dal::database::OAuthAccess;
+
+pub use koala::{KoalaConfig, KoalaProvider};
+pub use oidc::{OidcConfig, OidcProvider};
+
+mod koala;
+mod oidc;
+
+/// A service users log in with, e.g. Koala or an OpenID Connect provider.
+/// Chroma keeps the tokens it receives, to check whether the user may still log in
+/// and to refresh their details.
+#[async_trait]
+pub trait IdentityProvider: Debug + Send + Sync {
+    /// Identifies the provider, users of the provider are linked by it.
+    fn issuer(&self) -> &str;
+
+    /// The URI users who are not logged in are sent to.
+    fn login_uri(&self) -> String;
+
+    /// Start a login.
+    /// The returned pending login should be kept until the user returns with a code.
+    fn start_login(&self) -> LoginStart;
+
+    /// Exchange the code the user returned with for tokens.
+    ///
+    /// # Errors
+    ///
+    /// - If the code is rejected, or the login was not started by chroma
+    /// - If an ID token is returned which is not valid
+    /// - If the provider could not be reached
+    async fn exchange_code(
+        &self,
+        code: &str,
+        pending: Option<&PendingLogin>,
+    ) -> Result<LoginTokens, IdentityError>;
+
+    /// Exchange a refresh token for new tokens.
+    ///
+    /// # Errors
+    ///
+    /// - If the refresh token is rejected, in which case the user has to log in again
+    /// - If the provider could not be reached
+    async fn refresh(&self, refresh_token: &str) -> Result<OAuthAccess, IdentityError>;
+
+    /// Get the details of the user the access token belongs to.
+    ///
+    /// # Errors
+    ///
+    /// - If the access token is rejected
+    /// - If the provider could not be reached
+    async fn userinfo(&self, access_token: &str) -> Result<UserInfo, IdentityError>;
+
+    /// Get the groups the user the access token belongs to is a member of.
+    ///
+    /// # Errors
+    ///
+    /// - If the access token is rejected
+    /// - If the provider could not be reached
+    async fn groups(&self, access_token: &str) -> Result<Vec<String>, IdentityError>;
+}
+
+/// Where to send a user to log in.
+pub struct LoginStart {
+    pub url: String,
+    /// `None` if the provider does not require any state to complete the login
+    pub pending: Option<PendingLogin>,
+}
+
+/// What has to be remembered from the start of a login until it is completed.
+pub struct PendingLogin {
+    pub state: String,
+    pub pkce_verifier: String,
+    pub nonce: String,
+}
+
+pub struct LoginTokens {
+    pub access: OAuthAccess,
+    /// The subject of the validated ID token, if the provider returned one
+    pub subject: Option<String>,
+}
+
+pub struct UserInfo {
+    /// Identifies the user at the provider
+    pub subject: String,
+    /// Set if the provider's user IDs are chroma user IDs, as is the case for Koala.
+    /// Otherwise users are linked by the subject.
+    pub user_id: Option<i32>,
+    pub name: String,
+    pub is_admin: bool,
+}
+
+#[derive(Debug, Error)]
+pub enum IdentityError {
+    #[error("Rejected by the identity provider")]
+    Rejected,
+    #[error("Forbidden by the identity provider")]
+    Forbidden,
+    #[error("Invalid ID token: {0}")]
+    InvalidIdToken(String),
+    #[error("Identity provider could not be reached: {0}")]
+    Upstream(String),
+}
+
+impl From<reqwest::Error> for IdentityError {
+    fn from(e: reqwest::Error) -> Self {
+        match e.status() {
+            Some(v) if v.as_u16() == 403 => Self::Forbidden,
+            Some(v) if v.is_client_error() => Self::Rejected,
+            _ => Self::Upstream(e.to_string()),
+        }
+    }
+}
@@ -1,11 +1,10 @@
 extern crate core;
 
-use std::time::Duration;
+use std::sync::Arc;
 
 use actix_cors::Cors;
-use actix_web::{web, App, HttpServer};
+use actix_web::{App, HttpServer};
 use anyhow::{anyhow, bail, Result};
-use cabbage::KoalaApi;
 use dotenv::dotenv;
 use noiseless_tracing_actix_web::NoiselessRootSpanBuilder;
 use tracing::level_filters::LevelFilter;
@@ -16,17 +15,23 @@ use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
 use tracing_subscriber::EnvFilter;
 
-use dal::database::Database;
-use dal::storage_engine::{S3Config, Storage};
+use dal::database::{ApiKey, Database};
+use dal::storage_engine::{KeyLayout, S3Config, Storage};
 
 use crate::config::Config;
 use crate::exit::Exit;
-use crate::routes::appdata::{AlbumIdCache, AppData, Ratelimits, SessionIdCache, WebData};
+use crate::identity::{IdentityProvider, KoalaProvider, OidcProvider};
+use crate::routes::appdata::{AppData, Caches, Ratelimits, WebData};
+use crate::routes::group_sync::GroupMapping;
 use crate::routes::routable::Routable;
 
 mod config;
 mod exit;
+mod identity;
 mod routes;
+mod tasks;
+#[cfg(test)]
+mod testing;
 
 /// Run the chroma server and will block until the server is stopped or crashes
 ///
@@ -59,24 +64,35 @@ async fn main() -> Exit {
         Ok(v) => v,
         Err(err) => return Exit::Err(err),
     };
+    if let Err(err) = migrate_service_tokens(&db).await {
+        return Exit::Err(err);
+    }
     let storage = match init_storage(&config).await {
         Ok(v) => v,
         Err(err) => return Exit::Err(err),
     };
-    let koala = match KoalaApi::new(config.koala_base_redirect_uri().clone()) {
+    let group_mappings = match GroupMapping::parse_all(config.koala_group_mappings()) {
         Ok(v) => v,
-        Err(err) => return Exit::Err(err.into()),
+        Err(err) => return Exit::Err(anyhow!("invalid Koala group mappings: {:#}", err)),
+    };
+    let identity = match init_identity_provider(&config).await {
+        Ok(v) => v,
+        Err(err) => return Exit::Err(err),
     };
 
     // Package the core components up into the AppData struct
     let app_data = AppData {
-        koala,
+        identity,
         db,
         storage,
+        ratelimits: Ratelimits::new(&config),
         config,
-        ratelimits: Ratelimits::new(),
+        group_mappings,
     };
 
+    // Start the background tasks, these run alongside the webserver
+    tasks::spawn_all(&app_data);
+
     // Run the webserver using the AppData until stopped or crash
     match start_webserver(app_data).await {
         Ok(_) => Exit::Ok,
@@ -104,10 +120,6 @@ fn init_config() -> anyhow::Result<Config> {
         bail!("config is not valid");
     }
 
-    if !config.service_tokens.is_empty() {
-        warn!("there are service tokens configured; make sure these are, and stay, confidential!");
-    }
-
     Ok(config)
 }
 
@@ -118,8 +130,58 @@ async fn init_database(config: &Config) -> anyhow::Result<Database> {
         .map_err(|err| anyhow!("failed to initialize database connection: {:#}", err))
 }
 
+/// Migrate the service tokens which are still configured to admin API keys.
+/// Tokens which had been used were migrated along with the database,
+/// tokens which were never used are only known to the configuration.
+async fn migrate_service_tokens(db: &Database) -> anyhow::Result<()> {
+    let tokens = match std::env::var("SERVICE_TOKENS") {
+        Ok(tokens) => tokens,
+        Err(_) => return Ok(()),
+    };
+
+    let mut migrated = 0;
+    for token in tokens.split(',').filter(|token| !token.is_empty()) {
+        if ApiKey::create_from_service_token(db, token)
+            .await
+            .map_err(|err| anyhow!("failed to migrate service tokens: {:#}", err))?
+        {
+            migrated += 1;
+        }
+    }
+
+    if migrated > 0 {
+        info!("migrated {} service tokens to admin API keys", migrated);
+    }
+
+    warn!("'SERVICE_TOKENS' is no longer supported; its tokens are API keys now and it should be removed");
+    Ok(())
+}
+
+async fn init_identity_provider(config: &Config) -> anyhow::Result<Arc<dyn IdentityProvider>> {
+    match config.identity_provider() {
+        "koala" => {
+            info!("initializing Koala identity provider");
+            Ok(Arc::new(KoalaProvider::new(config.koala_config()?)?))
+        }
+        "oidc" => {
+            info!("initializing OpenID Connect identity provider");
+            let provider = OidcProvider::discover(config.oidc_config()?)
+                .await
+                .map_err(|err| anyhow!("failed to discover OpenID Connect provider: {:#}", err))?;
+            Ok(Arc::new(provider))
+        }
+        other => bail!(
+            "unknown identity provider '{}', expected 'koala' or 'oidc'",
+            other
+        ),
+    }
+}
+
 async fn init_storage(config: &Config) -> anyhow::Result<Storage> {
     info!("initializing S3 storage engine");
+    let key_layout = KeyLayout::new(config.s3_key_layout())
+        .map_err(|err| anyhow!("invalid S3 key layout: {:#}", err))?;
+
     Storage::new(S3Config {
         bucket_name: config.s3_bucket_name.clone().unwrap(),
         endpoint_url: config.s3_endpoint_url.clone().unwrap(),
@@ -128,6 +190,7 @@ async fn init_storage(config: &Config) -> anyhow::Result<Storage> {
         secret_access_key: config.s3_secret_access_key.clone().unwrap(),
         use_path_style: config.s3_force_path_style(),
         create_bucket: config.s3_create_bucket_on_startup(),
+        key_layout,
     })
     .await
     .map_err(|err| anyhow!("failed to initialize S3 storage engine: {:#}", err))
@@ -135,20 +198,15 @@ async fn init_storage(config: &Config) -> anyhow::Result<Storage> {
 
 async fn start_webserver(app_data: AppData) -> Result<()> {
     info!("starting web server");
+    // Created once, so all workers share the caches
+    let caches = Caches::new();
     HttpServer::new(move || {
         App::new()
             .wrap(Cors::permissive())
             .wrap(TracingLogger::<NoiselessRootSpanBuilder>::new())
             .app_data(WebData::new(app_data.clone()))
-            .app_data(web::Data::new(
-                SessionIdCache::builder()
-                    .max_capacity(10000)
-                    .time_to_live(Duration::from_secs(30))
-                    .build(),
-            ))
-            .app_data(web::Data::new(
-                AlbumIdCache::builder().max_capacity(10000).build(),
-            ))
+            .app_data(caches.sessions.clone())
+            .app_data(caches.albums.clone())
             .configure(routes::Router::configure)
     })
     .bind(&format!(
@@ -1,43 +1,113 @@
-use std::num::NonZeroU32;
 use std::sync::Arc;
+use std::time::Duration;
 
 use actix_web::web;
-use cabbage::KoalaApi;
-use governor::clock::DefaultClock;
-use governor::state::{InMemoryState, NotKeyed};
-use governor::{Quota, RateLimiter};
+use governor::Quota;
 use moka::future::Cache;
 
 use dal::database::{Album, Database};
 use dal::storage_engine::Storage;
 
 use crate::config::Config;
+use crate::identity::IdentityProvider;
 use crate::routes::authorization::Authorization;
+use crate::routes::group_sync::GroupMapping;
+use crate::routes::ratelimit::Ratelimit;
 
 pub type WebData = web::Data<AppData>;
 pub type SessionIdCache = Cache<String, Authorization>;
 pub type AlbumIdCache = Cache<String, Album>;
 
+/// The caches of the server.
+/// They are shared by all workers, so invalidating an entry takes effect on every worker.
+#[derive(Clone)]
+pub struct Caches {
+    pub sessions: web::Data<SessionIdCache>,
+    pub albums: web::Data<AlbumIdCache>,
+}
+
+impl Caches {
+    const MAX_CAPACITY: u64 = 10000;
+    /// The time after which a cached session or API key is checked again
+    const SESSION_TTL: Duration = Duration::from_secs(30);
+    /// The time after which a cached album is retrieved again.
+    /// Albums are evicted when they change, this covers changes made outside the request handlers,
+    /// e.g. by the background purge of the trash.
+    const ALBUM_TTL: Duration = Duration::from_secs(5 * 60);
+
+    pub fn new() -> Self {
+        Self {
+            sessions: web::Data::new(
+                SessionIdCache::builder()
+                    .max_capacity(Self::MAX_CAPACITY)
+                    .time_to_live(Self::SESSION_TTL)
+                    .support_invalidation_closures()
+                    .build(),
+            ),
+            albums: web::Data::new(
+                AlbumIdCache::builder()
+                    .max_capacity(Self::MAX_CAPACITY)
+                    .time_to_live(Self::ALBUM_TTL)
+                    .build(),
+            ),
+        }
+    }
+}
+
+impl Default for Caches {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct AppData {
     pub db: Database,
     pub storage: Storage,
     pub config: Config,
-    pub koala: KoalaApi,
+    pub identity: Arc<dyn IdentityProvider>,
     pub ratelimits: Ratelimits,
+    pub group_mappings: Vec<GroupMapping>,
 }
 
 #[derive(Debug, Clone)]
 pub struct Ratelimits {
-    pub photo_create: Arc<RateLimiter<NotKeyed, InMemoryState, DefaultClock>>,
+    /// Uploading photos
+    pub photo_create: Arc<Ratelimit>,
+    /// Converting photos to another format
+    pub photo_convert: Arc<Ratelimit>,
+    /// Exporting albums
+    pub album_export: Arc<Ratelimit>,
 }
 
 impl Ratelimits {
-    pub fn new() -> Self {
+    pub fn new(config: &Config) -> Self {
+        let ip_factor = config.ratelimit_ip_factor();
+        let trusted_proxies = config.ratelimit_trusted_proxies();
+
         Self {
-            photo_create: Arc::new(RateLimiter::direct(Quota::per_second(
-                NonZeroU32::new(1).unwrap(),
-            ))),
+            photo_create: Arc::new(Ratelimit::new(
+                Quota::per_minute(config.ratelimit_photo_create_per_minute()),
+                ip_factor,
+                trusted_proxies.to_vec(),
+            )),
+            photo_convert: Arc::new(Ratelimit::new(
+                Quota::per_minute(config.ratelimit_photo_convert_per_minute()),
+                ip_factor,
+                trusted_proxies.to_vec(),
+            )),
+            album_export: Arc::new(Ratelimit::new(
+                Quota::per_hour(config.ratelimit_album_export_per_hour()),
+                ip_factor,
+                trusted_proxies.to_vec(),
+            )),
         }
     }
+
+    /// Forget users and IP addresses which have not made requests recently.
+    pub fn prune(&self) {
+        self.photo_create.prune();
+        self.photo_convert.prune();
+        self.album_export.prune();
+    }
 }
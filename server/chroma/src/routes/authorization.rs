@@ -7,63 +7,125 @@ use actix_web::http::StatusCode;
 use actix_web::{web, FromRequest, HttpRequest, HttpResponse, ResponseError};
 use tap::TapFallible;
 use thiserror::Error;
-use tracing::{info, trace};
+use time::OffsetDateTime;
+use tracing::{info, trace, warn};
 
-use dal::database::{ChromaScope, Database, DbResult, ServiceTokenUser, User, UserType};
+use dal::database::{
+    AlbumMember, AlbumRole, ApiKey, ChromaScope, Database, DbResult, Session, User, UserType,
+};
 
+use crate::identity::IdentityError;
 use crate::routes::appdata::{SessionIdCache, WebData};
+use crate::routes::group_sync;
+use crate::routes::scope::Scope;
 
 #[derive(Clone)]
 pub struct Authorization {
     pub user: AuthorizedUser,
     pub is_admin: bool,
+    /// The grants of the user, resolved when the authorization was created.
+    /// Cached along with the authorization, so any change to them must invalidate
+    /// the cached authorizations of the user, see [invalidate_cached_user].
+    scopes: Vec<String>,
 }
 
 #[derive(Clone)]
 pub enum AuthorizedUser {
-    Koala { koala_id: i32 },
-    Service { token: String },
+    Koala { koala_id: i32, session_id: String },
+    ApiKey { key: ApiKey },
 }
 
 impl Authorization {
-    pub async fn to_dal_user_type(&self, db: &Database) -> DbResult<UserType> {
-        let user_type = match &self.user {
-            AuthorizedUser::Koala { koala_id } => UserType::Koala(*koala_id),
-            AuthorizedUser::Service { token } => {
-                match ServiceTokenUser::get_by_token(db, token).await? {
-                    Some(stu) => UserType::ServiceToken(stu.id),
-                    None => {
-                        let stu = ServiceTokenUser::create(db, token).await?;
-                        UserType::ServiceToken(stu.id)
-                    }
-                }
+    pub fn to_dal_user_type(&self) -> UserType {
+        match &self.user {
+            AuthorizedUser::Koala { koala_id, .. } => UserType::Koala(*koala_id),
+            AuthorizedUser::ApiKey { key } => UserType::ApiKey(key.id),
+        }
+    }
+
+    /// List the names of all scopes the user holds, separated by spaces.
+    /// For Koala users this includes the scopes of their roles.
+    pub fn list_scopes(&self) -> String {
+        self.scopes.join(" ")
+    }
+
+    /// Whether the user holds the scope.
+    /// Koala users hold it if it was granted to them directly or through one of their roles,
+    /// admin API keys hold every scope. Grants may be wildcards, see [Scope].
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        let is_admin_key = matches!(&self.user, AuthorizedUser::ApiKey { key } if key.is_admin);
+        is_admin_key || self.scopes.iter().any(|f| scope.is_granted_by(f))
+    }
+
+    /// Whether the user holds the scope for the album.
+    /// Besides holding the scope for all albums, members of the album hold the scopes
+    /// of their role for that album, see [Scope::is_granted_by_album_role].
+    pub async fn has_album_scope(
+        &self,
+        db: &Database,
+        album_id: &str,
+        scope: Scope,
+    ) -> DbResult<bool> {
+        if self.has_scope(scope) {
+            return Ok(true);
+        }
+
+        Ok(self
+            .album_role(db, album_id)
+            .await?
+            .map(|role| scope.is_granted_by_album_role(role))
+            .unwrap_or(false))
+    }
+
+    /// The role of the user in the album, or `None` if they are not a member.
+    /// API keys are never members of albums.
+    pub async fn album_role(&self, db: &Database, album_id: &str) -> DbResult<Option<AlbumRole>> {
+        match &self.user {
+            AuthorizedUser::Koala { koala_id, .. } => {
+                AlbumMember::get_role(db, album_id, *koala_id).await
             }
-        };
+            AuthorizedUser::ApiKey { .. } => Ok(None),
+        }
+    }
 
-        Ok(user_type)
+    /// List the IDs of the albums the user is a member of.
+    pub async fn list_member_albums(&self, db: &Database) -> DbResult<Vec<String>> {
+        match &self.user {
+            AuthorizedUser::Koala { koala_id, .. } => {
+                AlbumMember::list_album_ids_for_user(db, *koala_id).await
+            }
+            AuthorizedUser::ApiKey { .. } => Ok(Vec::new()),
+        }
     }
 
-    pub async fn list_scopes(&self, db: &Database) -> DbResult<String> {
-        Ok(match self.user {
-            AuthorizedUser::Koala { koala_id } => ChromaScope::list_for_user(db, koala_id)
-                .await?
-                .into_iter()
-                .map(|scope| scope.scope)
-                .collect::<Vec<_>>()
-                .join(" ")
-                .to_string(),
-            AuthorizedUser::Service { .. } => String::new(),
-        })
+    /// Whether the user may act on the album.
+    /// API keys may be bound to specific albums, everyone else may act on all albums
+    /// their scopes allow.
+    pub async fn may_access_album(&self, db: &Database, album_id: &str) -> DbResult<bool> {
+        match &self.user {
+            AuthorizedUser::Koala { .. } => Ok(true),
+            AuthorizedUser::ApiKey { key } => key.may_access_album(db, album_id).await,
+        }
     }
 
-    pub async fn has_scope<S: AsRef<str>>(&self, db: &Database, scope: S) -> DbResult<bool> {
-        Ok(match self.user {
-            AuthorizedUser::Koala { koala_id } => ChromaScope::list_for_user(db, koala_id)
-                .await?
-                .into_iter()
-                .any(|f| f.scope.eq(scope.as_ref())),
-            AuthorizedUser::Service { .. } => true,
-        })
+    /// Whether the user is restricted to specific albums.
+    /// Such users may not act on anything outside those albums, e.g. create new albums.
+    pub fn is_album_bound(&self) -> bool {
+        match &self.user {
+            AuthorizedUser::Koala { .. } => false,
+            AuthorizedUser::ApiKey { key } => key.album_bound,
+        }
+    }
+
+    /// List the IDs of the albums the user is bound to.
+    /// `None` if the user is not bound to specific albums, see [Self::is_album_bound].
+    pub async fn list_bound_albums(&self, db: &Database) -> DbResult<Option<Vec<String>>> {
+        match &self.user {
+            AuthorizedUser::ApiKey { key } if key.album_bound => {
+                key.list_albums(db).await.map(Some)
+            }
+            _ => Ok(None),
+        }
     }
 }
 
@@ -75,15 +137,15 @@ impl FromRequest for Authorization {
         let req = req.clone();
         Box::pin(async move {
             let data: &WebData = req.app_data().unwrap();
-            let oauth = data.koala.oauth_api(data.config.oauth_client_config());
+            let login_uri = data.identity.login_uri();
 
             let authorization_id = req
                 .headers()
                 .get("authorization")
-                .ok_or(AuthorizationError::NoHeader(oauth.get_login_redirect_uri()))
+                .ok_or_else(|| AuthorizationError::NoHeader(login_uri.clone()))
                 .tap_err(|_| trace!("Request is missing authorization header"))?
                 .to_str()
-                .map_err(|_| AuthorizationError::NoHeader(oauth.get_login_redirect_uri()))
+                .map_err(|_| AuthorizationError::NoHeader(login_uri.clone()))
                 .tap_err(|_| {
                     trace!("Value of authorization header could not be converter to UTF-8 String")
                 })?;
@@ -94,52 +156,81 @@ impl FromRequest for Authorization {
                 return Ok(v);
             }
 
-            // Check if we're dealing with a service token
-            if authorization_id.starts_with("Service ") {
-                let token = authorization_id.chars().skip(8).collect::<String>();
-                if token.is_empty() {
-                    return Err(AuthorizationError::InvalidServiceToken);
+            // Check if we're dealing with an API key
+            if let Some(key) = authorization_id.strip_prefix("Service ") {
+                if key.is_empty() {
+                    return Err(AuthorizationError::InvalidApiKey);
                 }
 
-                return if data.config.service_tokens().contains(&token.as_str()) {
-                    Ok(Self {
-                        is_admin: true,
-                        user: AuthorizedUser::Service { token },
-                    })
-                } else {
-                    Err(AuthorizationError::InvalidServiceToken)
+                let mut key = ApiKey::get_by_key(&data.db, key)
+                    .await?
+                    .filter(ApiKey::is_active)
+                    .ok_or(AuthorizationError::InvalidApiKey)
+                    .tap_err(|_| trace!("Invalid, expired or revoked API key provided"))?;
+                key.touch(&data.db).await?;
+
+                let authorization = Self {
+                    is_admin: key.is_admin,
+                    scopes: key.list_scopes(&data.db).await?,
+                    user: AuthorizedUser::ApiKey { key },
                 };
+
+                // Revoking a key or changing its scopes removes it from the cache
+                session_cache
+                    .insert(authorization_id.to_string(), authorization.clone())
+                    .await;
+
+                return Ok(authorization);
             }
 
             let mut user = User::get_by_session_id(&data.db, authorization_id)
                 .await?
-                .ok_or(AuthorizationError::InvalidSession(
-                    oauth.get_login_redirect_uri(),
-                ))
+                .ok_or_else(|| AuthorizationError::InvalidSession(login_uri.clone()))
                 .tap_err(|_| trace!("Invalid session ID provided ('{authorization_id}')"))?;
 
+            // Refresh the tokens ahead of time if they have expired
+            let mut refreshed = false;
+            if user.oauth_expires_at <= OffsetDateTime::now_utc().unix_timestamp() {
+                trace!("Identity provider tokens have expired, refreshing");
+                refresh_tokens(data, &mut user, &login_uri).await?;
+                refreshed = true;
+            }
+
             // Check if the access token is still valid
-            // E.g. it could have been revoked by Koala
-            let user_info =
-                oauth
-                    .get_userinfo(&user.access_token)
-                    .await
-                    .map_err(|e| match e.status() {
-                        Some(v) if v.as_u16() == 401 => {
-                            info!("Stored session was valid, tokens for koala were not.");
-                            AuthorizationError::InvalidSession(oauth.get_login_redirect_uri())
-                        }
-                        Some(v) if v.as_u16() == 403 => AuthorizationError::Forbidden,
-                        _ => AuthorizationError::KoalaUpstream,
-                    })?;
+            // E.g. it could have been revoked by the identity provider
+            let user_info = match data.identity.userinfo(&user.access_token).await {
+                Ok(v) => v,
+                Err(IdentityError::Rejected) if !refreshed => {
+                    info!(
+                        "Stored session was valid, tokens for the identity provider were not. Refreshing tokens."
+                    );
+                    refresh_tokens(data, &mut user, &login_uri).await?;
+                    data.identity
+                        .userinfo(&user.access_token)
+                        .await
+                        .map_err(|e| identity_error(e, &login_uri))?
+                }
+                Err(e) => return Err(identity_error(e, &login_uri)),
+            };
 
+            let was_admin = user.is_admin;
             user.set_is_admin(user_info.is_admin).await?;
+            Session::touch(&data.db, authorization_id).await?;
+            let grants_changed =
+                group_sync::sync_if_due(data, user.koala_id, &user.access_token).await?;
+
+            // Other sessions of the user may have cached the permissions they had before
+            if was_admin != user.is_admin || grants_changed {
+                invalidate_cached_user(session_cache, user.koala_id);
+            }
 
             let authorization = Self {
                 user: AuthorizedUser::Koala {
                     koala_id: user.koala_id,
+                    session_id: authorization_id.to_string(),
                 },
                 is_admin: user.is_admin,
+                scopes: ChromaScope::list_effective_for_user(&data.db, user.koala_id).await?,
             };
 
             session_cache
@@ -151,6 +242,80 @@ impl FromRequest for Authorization {
     }
 }
 
+/// Remove the cached authorizations of all sessions of the user,
+/// so changes to their scopes, roles or admin status apply immediately.
+pub fn invalidate_cached_user(session_cache: &SessionIdCache, koala_id: i32) {
+    if let Err(e) = session_cache.invalidate_entries_if(move |_, auth| {
+        matches!(&auth.user, AuthorizedUser::Koala { koala_id: id, .. } if *id == koala_id)
+    }) {
+        warn!("Failed to remove authorizations of user {koala_id} from the session cache: {e}");
+    }
+}
+
+/// Remove the cached authorizations of the API key,
+/// so changes to its scopes or its revocation apply immediately.
+/// The key itself is not known, so cached authorizations are found by the ID of their key.
+pub fn invalidate_cached_api_key(session_cache: &SessionIdCache, api_key_id: i32) {
+    if let Err(e) = session_cache.invalidate_entries_if(
+        move |_, auth| matches!(&auth.user, AuthorizedUser::ApiKey { key } if key.id == api_key_id),
+    ) {
+        warn!("Failed to remove API key {api_key_id} from the session cache: {e}");
+    }
+}
+
+/// Exchange the stored refresh token for a new token pair and store it.
+/// Concurrent requests of the user may find the tokens have expired at the same time.
+/// Their refreshes are serialized, a request which finds the tokens were refreshed
+/// while it was waiting uses those instead.
+///
+/// # Errors
+///
+/// - If the identity provider rejects the refresh token, in which case the user has to log in again
+/// - If the identity provider could not be reached
+/// - If a database error occurs
+async fn refresh_tokens(
+    data: &WebData,
+    user: &mut User<'_>,
+    login_uri: &str,
+) -> Result<(), AuthorizationError> {
+    let stale_access_token = user.access_token.clone();
+
+    let mut tx = data.db.begin().await?;
+    user.lock_tokens(&mut tx).await?;
+    if user.access_token != stale_access_token {
+        trace!("Identity provider tokens were refreshed by another request");
+        return Ok(());
+    }
+
+    let tokens = data
+        .identity
+        .refresh(&user.refresh_token)
+        .await
+        .map_err(|e| identity_error(e, login_uri))?;
+
+    user.set_tokens(
+        &mut tx,
+        tokens.access_token,
+        tokens.refresh_token,
+        tokens.expires_at,
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+fn identity_error(e: IdentityError, login_uri: &str) -> AuthorizationError {
+    match e {
+        IdentityError::Rejected | IdentityError::InvalidIdToken(_) => {
+            info!("Identity provider tokens were rejected, session is no longer valid.");
+            AuthorizationError::InvalidSession(login_uri.to_string())
+        }
+        IdentityError::Forbidden => AuthorizationError::Forbidden,
+        IdentityError::Upstream(_) => AuthorizationError::Upstream,
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum AuthorizationError {
     #[error("Internal server error")]
@@ -161,10 +326,10 @@ pub enum AuthorizationError {
     InvalidSession(String),
     #[error("Forbidden")]
     Forbidden,
-    #[error("Koala has an issue")]
-    KoalaUpstream,
-    #[error("Provided service token is empty or invalid")]
-    InvalidServiceToken,
+    #[error("Identity provider has an issue")]
+    Upstream,
+    #[error("Provided API key is empty, invalid, expired or revoked")]
+    InvalidApiKey,
 }
 
 impl ResponseError for AuthorizationError {
@@ -174,8 +339,8 @@ impl ResponseError for AuthorizationError {
             Self::NoHeader(_) => StatusCode::UNAUTHORIZED,
             Self::InvalidSession(_) => StatusCode::UNAUTHORIZED,
             Self::Forbidden => StatusCode::FORBIDDEN,
-            Self::KoalaUpstream => StatusCode::BAD_GATEWAY,
-            Self::InvalidServiceToken => StatusCode::UNAUTHORIZED,
+            Self::Upstream => StatusCode::BAD_GATEWAY,
+            Self::InvalidApiKey => StatusCode::UNAUTHORIZED,
         }
     }
 
@@ -188,3 +353,265 @@ impl ResponseError for AuthorizationError {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::header::AUTHORIZATION;
+    use actix_web::http::StatusCode;
+    use actix_web::{test, App};
+    use mock_koala::MockUser;
+
+    use dal::database::User;
+    use proto::LogoutRequest;
+
+    use crate::routes::appdata::Caches;
+    use crate::testing;
+
+    /// An endpoint any logged in user may use
+    const SESSIONS_PATH: &str = "/api/v1/user/sessions";
+
+    #[actix_web::test]
+    #[ignore = "requires the database and S3 from docker-compose.yml"]
+    async fn missing_header_redirects_to_koala() {
+        let koala = testing::start_koala(Vec::new()).await;
+        let data = testing::app_data(&koala).await;
+        let app = test::init_service(App::new().configure(testing::configure(data))).await;
+
+        let req = test::TestRequest::get().uri(SESSIONS_PATH).to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        assert!(testing::location(&resp).starts_with(koala.base_uri()));
+
+        let req = test::TestRequest::get()
+            .uri(SESSIONS_PATH)
+            .insert_header((AUTHORIZATION, "invalid"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        assert!(testing::location(&resp).starts_with(koala.base_uri()));
+    }
+
+    #[actix_web::test]
+    #[ignore = "requires the database and S3 from docker-compose.yml"]
+    async fn session_is_authorized() {
+        let koala_id = testing::random_koala_id();
+        let koala = testing::start_koala(vec![MockUser::new(koala_id, "Jan", "Jansen")]).await;
+        let data = testing::app_data(&koala).await;
+        let app = test::init_service(App::new().configure(testing::configure(data))).await;
+
+        let req = test::TestRequest::get()
+            .uri(&format!(
+                "/api/v1/login?code={}",
+                koala.issue_code(koala_id)
+            ))
+            .to_request();
+        let session_id = testing::session_id(&test::call_service(&app, req).await);
+
+        let req = test::TestRequest::get()
+            .uri(SESSIONS_PATH)
+            .insert_header((AUTHORIZATION, session_id))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    #[ignore = "requires the database and S3 from docker-compose.yml"]
+    async fn logout_applies_to_all_workers() {
+        let koala_id = testing::random_koala_id();
+        let koala = testing::start_koala(vec![MockUser::new(koala_id, "Jan", "Jansen")]).await;
+        let data = testing::app_data(&koala).await;
+        let caches = Caches::new();
+        let worker = test::init_service(
+            App::new().configure(testing::configure_with_caches(data.clone(), caches.clone())),
+        )
+        .await;
+        let other_worker =
+            test::init_service(App::new().configure(testing::configure_with_caches(data, caches)))
+                .await;
+
+        let session_id = testing::session_id(
+            &test::call_service(
+                &worker,
+                testing::login_request(&koala, koala_id).to_request(),
+            )
+            .await,
+        );
+
+        // Caches the session
+        let req = test::TestRequest::get()
+            .uri(SESSIONS_PATH)
+            .insert_header((AUTHORIZATION, session_id.as_str()))
+            .to_request();
+        assert_eq!(
+            test::call_service(&other_worker, req).await.status(),
+            StatusCode::OK
+        );
+
+        let req = test::TestRequest::post()
+            .uri("/api/v1/user/logout")
+            .insert_header((AUTHORIZATION, session_id.as_str()))
+            .set_json(LogoutRequest {
+                all_sessions: false,
+            })
+            .to_request();
+        assert!(test::call_service(&worker, req).await.status().is_success());
+
+        let req = test::TestRequest::get()
+            .uri(SESSIONS_PATH)
+            .insert_header((AUTHORIZATION, session_id.as_str()))
+            .to_request();
+        assert_eq!(
+            test::call_service(&other_worker, req).await.status(),
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    #[actix_web::test]
+    #[ignore = "requires the database and S3 from docker-compose.yml"]
+    async fn expired_tokens_are_refreshed() {
+        let koala_id = testing::random_koala_id();
+        let koala = testing::start_koala(vec![MockUser::new(koala_id, "Jan", "Jansen")]).await;
+        let data = testing::app_data(&koala).await;
+        let app = test::init_service(App::new().configure(testing::configure(data.clone()))).await;
+
+        let req = test::TestRequest::get()
+            .uri(&format!(
+                "/api/v1/login?code={}",
+                koala.issue_code(koala_id)
+            ))
+            .to_request();
+        let session_id = testing::session_id(&test::call_service(&app, req).await);
+        let before = User::get_by_id(&data.db, koala_id).await.unwrap().unwrap();
+
+        koala.expire_access_tokens(koala_id);
+
+        let req = test::TestRequest::get()
+            .uri(SESSIONS_PATH)
+            .insert_header((AUTHORIZATION, session_id))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+        let after = User::get_by_id(&data.db, koala_id).await.unwrap().unwrap();
+        assert_ne!(after.access_token, before.access_token);
+        assert_ne!(after.refresh_token, before.refresh_token);
+    }
+
+    #[actix_web::test]
+    #[ignore = "requires the database and S3 from docker-compose.yml"]
+    async fn concurrent_requests_refresh_once() {
+        let koala_id = testing::random_koala_id();
+        let koala = testing::start_koala(vec![MockUser::new(koala_id, "Jan", "Jansen")]).await;
+        let data = testing::app_data(&koala).await;
+        let app = test::init_service(App::new().configure(testing::configure(data))).await;
+
+        let session_id = testing::session_id(
+            &test::call_service(&app, testing::login_request(&koala, koala_id).to_request()).await,
+        );
+
+        koala.expire_access_tokens(koala_id);
+
+        // The refresh token can only be used once, the request which waited for the other
+        // has to use the refreshed tokens
+        let request = || {
+            test::TestRequest::get()
+                .uri(SESSIONS_PATH)
+                .insert_header((AUTHORIZATION, session_id.as_str()))
+                .to_request()
+        };
+        let (first, second) = futures::join!(
+            test::call_service(&app, request()),
+            test::call_service(&app, request())
+        );
+
+        assert_eq!(first.status(), StatusCode::OK);
+        assert_eq!(second.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    #[ignore = "requires the database and S3 from docker-compose.yml"]
+    async fn revoked_tokens_invalidate_session() {
+        let koala_id = testing::random_koala_id();
+        let koala = testing::start_koala(vec![MockUser::new(koala_id, "Jan", "Jansen")]).await;
+        let data = testing::app_data(&koala).await;
+        let app = test::init_service(App::new().configure(testing::configure(data))).await;
+
+        let req = test::TestRequest::get()
+            .uri(&format!(
+                "/api/v1/login?code={}",
+                koala.issue_code(koala_id)
+            ))
+            .to_request();
+        let session_id = testing::session_id(&test::call_service(&app, req).await);
+
+        koala.revoke_tokens(koala_id);
+
+        let req = test::TestRequest::get()
+            .uri(SESSIONS_PATH)
+            .insert_header((AUTHORIZATION, session_id))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        assert!(testing::location(&resp).starts_with(koala.base_uri()));
+    }
+
+    #[actix_web::test]
+    #[ignore = "requires the database and S3 from docker-compose.yml"]
+    async fn admin_flag_follows_koala() {
+        let koala_id = testing::random_koala_id();
+        let koala =
+            testing::start_koala(vec![MockUser::new(koala_id, "Jan", "Jansen").admin()]).await;
+        let data = testing::app_data(&koala).await;
+        let app = test::init_service(App::new().configure(testing::configure(data))).await;
+
+        let req = test::TestRequest::get()
+            .uri(&format!(
+                "/api/v1/login?code={}",
+                koala.issue_code(koala_id)
+            ))
+            .to_request();
+        let session_id = testing::session_id(&test::call_service(&app, req).await);
+
+        // Only admins may view other users, checked before the session is cached
+        koala.update_user(koala_id, |user| user.is_admin = false);
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/api/v1/user?id={koala_id}"))
+            .insert_header((AUTHORIZATION, session_id))
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            StatusCode::FORBIDDEN
+        );
+    }
+
+    #[actix_web::test]
+    #[ignore = "requires the database and S3 from docker-compose.yml"]
+    async fn forbidden_users_are_forbidden() {
+        let koala_id = testing::random_koala_id();
+        let koala = testing::start_koala(vec![MockUser::new(koala_id, "Jan", "Jansen")]).await;
+        let data = testing::app_data(&koala).await;
+        let app = test::init_service(App::new().configure(testing::configure(data))).await;
+
+        let req = test::TestRequest::get()
+            .uri(&format!(
+                "/api/v1/login?code={}",
+                koala.issue_code(koala_id)
+            ))
+            .to_request();
+        let session_id = testing::session_id(&test::call_service(&app, req).await);
+
+        koala.update_user(koala_id, |user| user.forbidden = true);
+
+        let req = test::TestRequest::get()
+            .uri(SESSIONS_PATH)
+            .insert_header((AUTHORIZATION, session_id))
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            StatusCode::FORBIDDEN
+        );
+    }
+}
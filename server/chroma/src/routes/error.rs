@@ -3,6 +3,9 @@ use actix_web::http::StatusCode;
 use actix_web::{HttpResponse, ResponseError};
 use thiserror::Error;
 
+use crate::identity::IdentityError;
+use crate::routes::ratelimit::{RATELIMIT_LIMIT, RATELIMIT_REMAINING, RATELIMIT_RESET};
+
 pub type WebResult<T> = Result<T, Error>;
 
 #[derive(Debug, Error)]
@@ -15,8 +18,8 @@ pub enum Error {
     BadRequest(String),
     #[error("Internal server error")]
     StorageEngine(#[from] dal::storage_engine::error::StorageError),
-    #[error("Something went wrong on Koala's end")]
-    Koala(reqwest::Error),
+    #[error("{0}")]
+    Identity(#[from] IdentityError),
     #[error("The requested resource may not be accessed by the authorized user.")]
     Forbidden,
     #[error("Failed to parse timestamp")]
@@ -30,7 +33,11 @@ pub enum Error {
     #[error("Failed to decode WebP image")]
     WebpDecode,
     #[error("Slow down. Too many requests")]
-    Ratelimit { retry_after: u64 },
+    Ratelimit {
+        limit: u32,
+        retry_after: u64,
+        reset: u64,
+    },
 }
 
 impl ResponseError for Error {
@@ -40,7 +47,13 @@ impl ResponseError for Error {
             Self::NotFound => StatusCode::NOT_FOUND,
             Self::BadRequest(_) => StatusCode::BAD_REQUEST,
             Self::StorageEngine(_) => StatusCode::INTERNAL_SERVER_ERROR,
-            Self::Koala(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::Identity(e) => match e {
+                IdentityError::Rejected | IdentityError::InvalidIdToken(_) => {
+                    StatusCode::BAD_REQUEST
+                }
+                IdentityError::Forbidden => StatusCode::FORBIDDEN,
+                IdentityError::Upstream(_) => StatusCode::BAD_GATEWAY,
+            },
             Self::Forbidden => StatusCode::FORBIDDEN,
             Self::ChronoParse(_) => StatusCode::BAD_GATEWAY,
             Self::ImagePipeline(e) => e.status_code(),
@@ -53,8 +66,15 @@ impl ResponseError for Error {
 
     fn error_response(&self) -> HttpResponse<BoxBody> {
         match self {
-            Self::Ratelimit { retry_after } => HttpResponse::build(self.status_code())
+            Self::Ratelimit {
+                limit,
+                retry_after,
+                reset,
+            } => HttpResponse::build(self.status_code())
                 .insert_header(("Retry-After".to_string(), format!("{retry_after}")))
+                .insert_header((RATELIMIT_LIMIT, format!("{limit}")))
+                .insert_header((RATELIMIT_REMAINING, "0"))
+                .insert_header((RATELIMIT_RESET, format!("{reset}")))
                 .body("Too many requests"),
             _ => ResponseError::error_response(self),
         }
@@ -0,0 +1,242 @@
+use thiserror::Error;
+use time::OffsetDateTime;
+use tracing::{trace, warn};
+
+use dal::database::{AuditAction, AuditLogEntry, AuditTarget, DbResult, Role, SyncedGrants};
+
+use crate::routes::appdata::AppData;
+use crate::routes::scope::Scope;
+
+/// How long synced grants are used before they are synced again.
+/// Users are always synced when they log in.
+pub const SYNC_INTERVAL_SECS: i64 = 15 * 60;
+/// Prefix of grants in a mapping which refer to a role rather than a scope
+const ROLE_PREFIX: &str = "role:";
+
+/// Grants which every member of a group or committee at the identity provider receives.
+#[derive(Debug, Clone)]
+pub struct GroupMapping {
+    pub group: String,
+    pub grants: Vec<GroupGrant>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GroupGrant {
+    /// A scope, which may be a wildcard
+    Scope(String),
+    /// A role, by its name
+    Role(String),
+}
+
+#[derive(Debug, Error)]
+pub enum GroupMappingError {
+    #[error("Mapping '{0}' should be of the form 'group=grant,grant'")]
+    Malformed(String),
+    #[error("Mapping for group '{0}' has no grants")]
+    NoGrants(String),
+    #[error("Unknown scope '{0}'")]
+    UnknownScope(String),
+}
+
+impl GroupMapping {
+    /// Parse mappings of the form `group=grant,grant;group=grant`.
+    /// Grants are scopes, or role names prefixed with `role:`.
+    /// Whether roles exist is only checked when syncing, as roles may be created later on.
+    ///
+    /// # Errors
+    ///
+    /// - If a mapping is malformed or has no grants
+    /// - If a scope is unknown
+    pub fn parse_all(mappings: &str) -> Result<Vec<GroupMapping>, GroupMappingError> {
+        mappings
+            .split(';')
+            .map(str::trim)
+            .filter(|mapping| !mapping.is_empty())
+            .map(Self::parse)
+            .collect()
+    }
+
+    fn parse(mapping: &str) -> Result<GroupMapping, GroupMappingError> {
+        let (group, grants) = mapping
+            .split_once('=')
+            .map(|(group, grants)| (group.trim(), grants))
+            .filter(|(group, _)| !group.is_empty())
+            .ok_or_else(|| GroupMappingError::Malformed(mapping.to_string()))?;
+
+        let grants = grants
+            .split(',')
+            .map(str::trim)
+            .filter(|grant| !grant.is_empty())
+            .map(|grant| match grant.strip_prefix(ROLE_PREFIX) {
+                Some(role) => Ok(GroupGrant::Role(role.trim().to_string())),
+                None if Scope::is_valid_grant(grant) => Ok(GroupGrant::Scope(grant.to_string())),
+                None => Err(GroupMappingError::UnknownScope(grant.to_string())),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if grants.is_empty() {
+            return Err(GroupMappingError::NoGrants(group.to_string()));
+        }
+
+        Ok(GroupMapping {
+            group: group.to_string(),
+            grants,
+        })
+    }
+}
+
+/// Sync the grants of a user from their groups, if they have not been synced recently.
+/// Returns whether the synced grants of the user changed.
+///
+/// # Errors
+///
+/// If a database error occurs
+pub async fn sync_if_due(data: &AppData, koala_id: i32, access_token: &str) -> DbResult<bool> {
+    if data.group_mappings.is_empty() {
+        return Ok(false);
+    }
+
+    let now = OffsetDateTime::now_utc().unix_timestamp();
+    match SyncedGrants::last_synced_at(&data.db, koala_id).await? {
+        Some(synced_at) if now - synced_at < SYNC_INTERVAL_SECS => Ok(false),
+        _ => sync(data, koala_id, access_token).await,
+    }
+}
+
+/// Replace the synced grants of a user with those of the groups they are currently in.
+/// If the identity provider cannot be reached, the previously synced grants are kept.
+/// Returns whether the synced grants of the user changed.
+///
+/// # Errors
+///
+/// If a database error occurs
+pub async fn sync(data: &AppData, koala_id: i32, access_token: &str) -> DbResult<bool> {
+    if data.group_mappings.is_empty() {
+        return Ok(false);
+    }
+
+    let groups = match data.identity.groups(access_token).await {
+        Ok(v) => v,
+        Err(e) => {
+            warn!("Failed to fetch groups of user {koala_id}, keeping synced grants: {e}");
+            return Ok(false);
+        }
+    };
+
+    let mut grants = SyncedGrants::default();
+    for mapping in data
+        .group_mappings
+        .iter()
+        .filter(|mapping| groups.contains(&mapping.group))
+    {
+        for grant in &mapping.grants {
+            match grant {
+                GroupGrant::Scope(scope) => grants.scopes.push(scope.clone()),
+                GroupGrant::Role(name) => match Role::get_by_name(&data.db, name).await? {
+                    Some(role) => grants.role_ids.push(role.id),
+                    None => warn!(
+                        "Group '{}' is mapped to role '{name}', which does not exist",
+                        mapping.group
+                    ),
+                },
+            }
+        }
+    }
+
+    let mut previous = SyncedGrants::get_for_user(&data.db, koala_id).await?;
+    previous.sort();
+    grants.sort();
+    let changed = previous.scopes.ne(&grants.scopes) || previous.role_ids.ne(&grants.role_ids);
+
+    trace!(
+        "Syncing {} scopes and {} roles for user {koala_id} from groups {groups:?}",
+        grants.scopes.len(),
+        grants.role_ids.len()
+    );
+
+    let mut tx = data.db.begin().await?;
+    grants.set_for_user(&mut tx, koala_id).await?;
+    if changed {
+        AuditLogEntry::record(
+            &mut tx,
+            None,
+            AuditAction::GroupGrantsSynced,
+            AuditTarget::User(koala_id),
+            Some(previous.describe()),
+            Some(grants.describe()),
+        )
+        .await?;
+    }
+    tx.commit().await?;
+
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GroupGrant, GroupMapping, GroupMappingError};
+
+    #[test]
+    fn parses_scopes_and_roles() {
+        let mappings = GroupMapping::parse_all(
+            "Fotocommissie=nl.svsticky.chroma.album.*, role:Uploaders;Bestuur=role:Admins",
+        )
+        .unwrap();
+
+        assert_eq!(mappings.len(), 2);
+        assert_eq!(mappings[0].group, "Fotocommissie");
+        assert_eq!(
+            mappings[0].grants,
+            vec![
+                GroupGrant::Scope("nl.svsticky.chroma.album.*".to_string()),
+                GroupGrant::Role("Uploaders".to_string()),
+            ]
+        );
+        assert_eq!(mappings[1].group, "Bestuur");
+        assert_eq!(
+            mappings[1].grants,
+            vec![GroupGrant::Role("Admins".to_string())]
+        );
+    }
+
+    #[test]
+    fn ignores_empty_mappings_and_grants() {
+        assert!(GroupMapping::parse_all("").unwrap().is_empty());
+
+        let mappings =
+            GroupMapping::parse_all(" ; Fotocommissie = nl.svsticky.chroma.photo.create, ;")
+                .unwrap();
+        assert_eq!(mappings.len(), 1);
+        assert_eq!(mappings[0].group, "Fotocommissie");
+        assert_eq!(
+            mappings[0].grants,
+            vec![GroupGrant::Scope(
+                "nl.svsticky.chroma.photo.create".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_mappings() {
+        assert!(matches!(
+            GroupMapping::parse_all("Fotocommissie"),
+            Err(GroupMappingError::Malformed(_))
+        ));
+        assert!(matches!(
+            GroupMapping::parse_all("=nl.svsticky.chroma.album.*"),
+            Err(GroupMappingError::Malformed(_))
+        ));
+        assert!(matches!(
+            GroupMapping::parse_all("Fotocommissie= , "),
+            Err(GroupMappingError::NoGrants(group)) if group == "Fotocommissie"
+        ));
+    }
+
+    #[test]
+    fn rejects_unknown_scopes() {
+        assert!(matches!(
+            GroupMapping::parse_all("Fotocommissie=nl.svsticky.chroma.video.*"),
+            Err(GroupMappingError::UnknownScope(scope)) if scope == "nl.svsticky.chroma.video.*"
+        ));
+    }
+}
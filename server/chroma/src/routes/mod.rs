@@ -7,8 +7,11 @@ pub mod appdata;
 mod authorization;
 mod empty;
 mod error;
+pub mod group_sync;
+mod ratelimit;
 mod redirect;
 pub mod routable;
+mod scope;
 mod v1;
 
 pub struct Router;
@@ -0,0 +1,338 @@
+use std::net::IpAddr;
+use std::num::NonZeroU32;
+
+use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue};
+use actix_web::{HttpRequest, HttpResponse, Responder};
+use governor::clock::{Clock, DefaultClock};
+use governor::middleware::{StateInformationMiddleware, StateSnapshot};
+use governor::state::keyed::DefaultKeyedStateStore;
+use governor::{NotUntil, Quota, RateLimiter};
+
+use crate::routes::authorization::{Authorization, AuthorizedUser};
+use crate::routes::error::{Error, WebResult};
+
+pub const RATELIMIT_LIMIT: &str = "ratelimit-limit";
+pub const RATELIMIT_REMAINING: &str = "ratelimit-remaining";
+pub const RATELIMIT_RESET: &str = "ratelimit-reset";
+/// The addresses a request was forwarded for by reverse proxies
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+
+type KeyedRateLimiter = RateLimiter<
+    RatelimitKey,
+    DefaultKeyedStateStore<RatelimitKey>,
+    DefaultClock,
+    StateInformationMiddleware,
+>;
+
+/// Who a request is counted against
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RatelimitKey {
+    User(i32),
+    ApiKey(i32),
+    Ip(IpAddr),
+}
+
+impl RatelimitKey {
+    fn for_user(auth: &Authorization) -> Self {
+        match &auth.user {
+            AuthorizedUser::Koala { koala_id, .. } => Self::User(*koala_id),
+            AuthorizedUser::ApiKey { key } => Self::ApiKey(key.id),
+        }
+    }
+}
+
+/// Limits how often an expensive operation may be performed.
+/// Requests are counted against the user or API key making them,
+/// and against the IP address they come from. The IP address may make more requests
+/// than a single user, as multiple users may share it.
+///
+/// The IP address is that of the peer, like the ratelimit of the `/access` endpoint.
+/// Requests from trusted reverse proxies are counted against the address the proxies
+/// forwarded them for, according to the `X-Forwarded-For` header.
+#[derive(Debug)]
+pub struct Ratelimit {
+    by_user: KeyedRateLimiter,
+    by_ip: KeyedRateLimiter,
+    trusted_proxies: Vec<IpAddr>,
+}
+
+impl Ratelimit {
+    /// Create a ratelimit allowing `quota` requests per user,
+    /// and `ip_factor` times as many requests per IP address.
+    pub fn new(quota: Quota, ip_factor: NonZeroU32, trusted_proxies: Vec<IpAddr>) -> Self {
+        Self {
+            by_user: RateLimiter::keyed(quota).with_middleware::<StateInformationMiddleware>(),
+            by_ip: RateLimiter::keyed(ip_quota(quota, ip_factor))
+                .with_middleware::<StateInformationMiddleware>(),
+            trusted_proxies,
+        }
+    }
+
+    /// Count a request by the user.
+    /// The returned state should be sent along with the response, see [Ratelimited].
+    ///
+    /// # Errors
+    ///
+    /// If the user or their IP address has made too many requests
+    pub fn check(&self, auth: &Authorization, req: &HttpRequest) -> WebResult<RatelimitState> {
+        self.check_keys(&RatelimitKey::for_user(auth), self.client_ip(req))
+    }
+
+    /// Count a request against the IP address first,
+    /// so requests refused because of their IP address do not count against the user.
+    fn check_keys(&self, user: &RatelimitKey, ip: Option<IpAddr>) -> WebResult<RatelimitState> {
+        if let Some(ip) = ip {
+            self.by_ip
+                .check_key(&RatelimitKey::Ip(ip))
+                .map_err(ratelimit_error)?;
+        }
+
+        let user_state = self.by_user.check_key(user).map_err(ratelimit_error)?;
+
+        Ok(RatelimitState::from(user_state))
+    }
+
+    /// The address the request comes from.
+    /// Proxies append the address they received a request from to `X-Forwarded-For`,
+    /// so the last address which is not a trusted proxy is that of the client.
+    /// Addresses before it may have been made up by the client.
+    fn client_ip(&self, req: &HttpRequest) -> Option<IpAddr> {
+        let peer = req.peer_addr()?.ip();
+        if !self.trusted_proxies.contains(&peer) {
+            return Some(peer);
+        }
+
+        let forwarded_for = req
+            .headers()
+            .get_all(X_FORWARDED_FOR)
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(|addr| addr.trim().parse::<IpAddr>())
+            .collect::<Vec<_>>();
+
+        // Stop at an address a proxy could not have added, anything before it is untrusted
+        let mut client = peer;
+        for addr in forwarded_for.into_iter().rev() {
+            match addr {
+                Ok(addr) if self.trusted_proxies.contains(&addr) => client = addr,
+                Ok(addr) => return Some(addr),
+                Err(_) => break,
+            }
+        }
+
+        Some(client)
+    }
+
+    /// Forget users and IP addresses which have not made requests recently,
+    /// so the ratelimit does not grow without bounds.
+    pub fn prune(&self) {
+        self.by_user.retain_recent();
+        self.by_user.shrink_to_fit();
+        self.by_ip.retain_recent();
+        self.by_ip.shrink_to_fit();
+    }
+}
+
+/// The quota of an IP address, allowing `ip_factor` times as many requests as `quota`,
+/// both in a burst and over time.
+fn ip_quota(quota: Quota, ip_factor: NonZeroU32) -> Quota {
+    Quota::with_period(quota.replenish_interval() / ip_factor.get())
+        .unwrap_or(quota)
+        .allow_burst(quota.burst_size().saturating_mul(ip_factor))
+}
+
+fn ratelimit_error(e: NotUntil<<DefaultClock as Clock>::Instant>) -> Error {
+    let quota = e.quota();
+    let limit = quota.burst_size().get();
+    let retry_after = e.wait_time_from(DefaultClock::default().now());
+
+    Error::Ratelimit {
+        limit,
+        retry_after: retry_after.as_secs() + 1,
+        // Like for allowed requests, the time until the whole burst may be made again
+        reset: (retry_after + quota.replenish_interval() * (limit - 1)).as_secs() + 1,
+    }
+}
+
+/// The state of a user's ratelimit after a request,
+/// sent to the client in the `RateLimit-*` headers.
+#[derive(Debug, Clone, Copy)]
+pub struct RatelimitState {
+    /// The number of requests which may be made in a burst
+    pub limit: u32,
+    /// The number of requests which may still be made right now
+    pub remaining: u32,
+    /// The number of seconds until the whole burst of requests may be made again
+    pub reset: u64,
+}
+
+impl From<StateSnapshot> for RatelimitState {
+    fn from(snapshot: StateSnapshot) -> Self {
+        let quota = snapshot.quota();
+        let limit = quota.burst_size().get();
+        let remaining = snapshot.remaining_burst_capacity();
+
+        Self {
+            limit,
+            remaining,
+            reset: (quota.replenish_interval() * (limit - remaining)).as_secs(),
+        }
+    }
+}
+
+impl RatelimitState {
+    pub fn insert_headers(&self, headers: &mut HeaderMap) {
+        headers.insert(
+            HeaderName::from_static(RATELIMIT_LIMIT),
+            HeaderValue::from(self.limit),
+        );
+        headers.insert(
+            HeaderName::from_static(RATELIMIT_REMAINING),
+            HeaderValue::from(self.remaining),
+        );
+        headers.insert(
+            HeaderName::from_static(RATELIMIT_RESET),
+            HeaderValue::from(self.reset),
+        );
+    }
+}
+
+/// A response to a ratelimited request, carrying the state of the ratelimit in its headers.
+pub struct Ratelimited<R>(pub R, pub RatelimitState);
+
+impl<R: Responder> Responder for Ratelimited<R> {
+    type Body = R::Body;
+
+    fn respond_to(self, req: &HttpRequest) -> HttpResponse<Self::Body> {
+        let mut response = self.0.respond_to(req);
+        self.1.insert_headers(response.headers_mut());
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+    use std::num::NonZeroU32;
+    use std::time::Duration;
+
+    use actix_web::http::header::RETRY_AFTER;
+    use actix_web::test::TestRequest;
+    use actix_web::ResponseError;
+    use governor::Quota;
+
+    use super::{ip_quota, Ratelimit, RatelimitKey, RATELIMIT_REMAINING, RATELIMIT_RESET};
+
+    fn nonzero(n: u32) -> NonZeroU32 {
+        NonZeroU32::new(n).unwrap()
+    }
+
+    fn ip(addr: &str) -> IpAddr {
+        addr.parse().unwrap()
+    }
+
+    fn header(response: &actix_web::HttpResponse, name: &str) -> u64 {
+        response
+            .headers()
+            .get(name)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn ip_quota_is_multiple_of_user_quota() {
+        let quota = ip_quota(Quota::per_minute(nonzero(60)), nonzero(10));
+        assert_eq!(quota.burst_size(), nonzero(600));
+        assert_eq!(quota.replenish_interval(), Duration::from_millis(100));
+
+        let quota = ip_quota(Quota::per_hour(nonzero(10)), nonzero(10));
+        assert_eq!(quota.burst_size(), nonzero(100));
+        assert_eq!(quota.replenish_interval(), Duration::from_secs(36));
+
+        let quota = ip_quota(Quota::per_minute(nonzero(60)), nonzero(1));
+        assert_eq!(quota, Quota::per_minute(nonzero(60)));
+    }
+
+    #[test]
+    fn ip_rejection_does_not_count_against_user() {
+        let ratelimit = Ratelimit::new(Quota::per_hour(nonzero(1)), nonzero(1), Vec::new());
+        let shared = Some(ip("192.0.2.1"));
+
+        assert!(ratelimit.check_keys(&RatelimitKey::User(1), shared).is_ok());
+        assert!(ratelimit
+            .check_keys(&RatelimitKey::User(2), shared)
+            .is_err());
+
+        // The second user was refused because of the shared address only
+        assert!(ratelimit
+            .check_keys(&RatelimitKey::User(2), Some(ip("192.0.2.2")))
+            .is_ok());
+    }
+
+    #[test]
+    fn state_of_allowed_request() {
+        let ratelimit = Ratelimit::new(Quota::per_minute(nonzero(60)), nonzero(10), Vec::new());
+        let state = ratelimit.check_keys(&RatelimitKey::User(1), None).unwrap();
+
+        assert_eq!(state.limit, 60);
+        assert_eq!(state.remaining, 59);
+        assert_eq!(state.reset, 1);
+    }
+
+    #[test]
+    fn headers_of_refused_request() {
+        let ratelimit = Ratelimit::new(Quota::per_minute(nonzero(2)), nonzero(10), Vec::new());
+        let user = RatelimitKey::User(1);
+        ratelimit.check_keys(&user, None).unwrap();
+        ratelimit.check_keys(&user, None).unwrap();
+
+        let response = ratelimit
+            .check_keys(&user, None)
+            .unwrap_err()
+            .error_response();
+
+        // One request may be made after 30 seconds, both after a minute.
+        // Rounded up, so the client does not retry too early
+        assert!((30..=31).contains(&header(&response, RETRY_AFTER.as_str())));
+        assert_eq!(header(&response, RATELIMIT_REMAINING), 0);
+        assert!((60..=61).contains(&header(&response, RATELIMIT_RESET)));
+    }
+
+    #[test]
+    fn forwarded_for_is_ignored_from_untrusted_peers() {
+        let ratelimit = Ratelimit::new(Quota::per_minute(nonzero(60)), nonzero(10), Vec::new());
+        let req = TestRequest::default()
+            .peer_addr("192.0.2.1:1234".parse().unwrap())
+            .insert_header(("X-Forwarded-For", "198.51.100.1"))
+            .to_http_request();
+
+        assert_eq!(ratelimit.client_ip(&req), Some(ip("192.0.2.1")));
+    }
+
+    #[test]
+    fn forwarded_for_is_used_from_trusted_proxies() {
+        let proxies = vec![ip("10.0.0.1"), ip("10.0.0.2")];
+        let ratelimit = Ratelimit::new(Quota::per_minute(nonzero(60)), nonzero(10), proxies);
+
+        // Addresses before the client's are made up by the client
+        let req = TestRequest::default()
+            .peer_addr("10.0.0.1:1234".parse().unwrap())
+            .insert_header(("X-Forwarded-For", "203.0.113.1, 198.51.100.1, 10.0.0.2"))
+            .to_http_request();
+        assert_eq!(ratelimit.client_ip(&req), Some(ip("198.51.100.1")));
+
+        let req = TestRequest::default()
+            .peer_addr("10.0.0.1:1234".parse().unwrap())
+            .insert_header(("X-Forwarded-For", "unknown, 10.0.0.2"))
+            .to_http_request();
+        assert_eq!(ratelimit.client_ip(&req), Some(ip("10.0.0.2")));
+
+        let req = TestRequest::default()
+            .peer_addr("10.0.0.1:1234".parse().unwrap())
+            .to_http_request();
+        assert_eq!(ratelimit.client_ip(&req), Some(ip("10.0.0.1")));
+    }
+}
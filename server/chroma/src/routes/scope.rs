@@ -0,0 +1,194 @@
+use std::fmt;
+
+use dal::database::AlbumRole;
+
+use crate::routes::error::{Error, WebResult};
+
+/// A permission which may be granted to users, roles and API keys.
+/// Scopes are stored by their name, see [Scope::name].
+///
+/// Besides the name of a scope, a grant may be a wildcard: a prefix of whole segments
+/// followed by `.*`. A wildcard grants every scope below the prefix, at any depth.
+/// E.g. `nl.svsticky.chroma.album.*` grants both `nl.svsticky.chroma.album.update`
+/// and `nl.svsticky.chroma.album.list.draft`, but not `nl.svsticky.chroma.albums.update`.
+/// A lone `*` grants nothing, admins should be used for that instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Scope {
+    AlbumCreate,
+    AlbumUpdate,
+    AlbumDelete,
+    AlbumListDraft,
+    CollectionCreate,
+    CollectionUpdate,
+    CollectionDelete,
+    PhotoCreate,
+    PhotoDelete,
+}
+
+impl Scope {
+    /// All scopes which may be granted
+    pub const ALL: &'static [Scope] = &[
+        Self::AlbumCreate,
+        Self::AlbumUpdate,
+        Self::AlbumDelete,
+        Self::AlbumListDraft,
+        Self::CollectionCreate,
+        Self::CollectionUpdate,
+        Self::CollectionDelete,
+        Self::PhotoCreate,
+        Self::PhotoDelete,
+    ];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::AlbumCreate => "nl.svsticky.chroma.album.create",
+            Self::AlbumUpdate => "nl.svsticky.chroma.album.update",
+            Self::AlbumDelete => "nl.svsticky.chroma.album.delete",
+            Self::AlbumListDraft => "nl.svsticky.chroma.album.list.draft",
+            Self::CollectionCreate => "nl.svsticky.chroma.collection.create",
+            Self::CollectionUpdate => "nl.svsticky.chroma.collection.update",
+            Self::CollectionDelete => "nl.svsticky.chroma.collection.delete",
+            Self::PhotoCreate => "nl.svsticky.chroma.photo.create",
+            Self::PhotoDelete => "nl.svsticky.chroma.photo.delete",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|scope| scope.name().eq(name))
+    }
+
+    /// Whether the grant, which may be a wildcard, grants this scope.
+    pub fn is_granted_by(self, grant: &str) -> bool {
+        match grant.strip_suffix(".*") {
+            Some(prefix) => self
+                .name()
+                .strip_prefix(prefix)
+                .map(|rest| rest.starts_with('.'))
+                .unwrap_or(false),
+            None => self.name().eq(grant),
+        }
+    }
+
+    /// Whether members of an album with the role hold this scope for that album.
+    pub fn is_granted_by_album_role(self, role: AlbumRole) -> bool {
+        let required = match self {
+            Self::AlbumListDraft => AlbumRole::Viewer,
+            Self::PhotoCreate | Self::PhotoDelete => AlbumRole::Contributor,
+            Self::AlbumUpdate | Self::AlbumDelete => AlbumRole::Owner,
+            // These scopes are not about a single album
+            Self::AlbumCreate
+            | Self::CollectionCreate
+            | Self::CollectionUpdate
+            | Self::CollectionDelete => return false,
+        };
+
+        role >= required
+    }
+
+    /// Whether the grant may be granted.
+    /// It must either be the name of a scope, or a wildcard granting at least one scope.
+    pub fn is_valid_grant(grant: &str) -> bool {
+        Self::ALL.iter().any(|scope| scope.is_granted_by(grant))
+    }
+
+    /// Check that all grants are valid, see [Self::is_valid_grant].
+    ///
+    /// # Errors
+    ///
+    /// If any of the grants is invalid
+    pub fn check_grants(grants: &[String]) -> WebResult<()> {
+        match grants.iter().find(|grant| !Self::is_valid_grant(grant)) {
+            Some(grant) => Err(Error::BadRequest(format!("Unknown scope '{grant}'"))),
+            None => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Scope;
+
+    #[test]
+    fn exact_grant() {
+        assert!(Scope::AlbumUpdate.is_granted_by("nl.svsticky.chroma.album.update"));
+        assert!(!Scope::AlbumUpdate.is_granted_by("nl.svsticky.chroma.album.create"));
+        assert!(!Scope::AlbumListDraft.is_granted_by("nl.svsticky.chroma.album.list"));
+    }
+
+    #[test]
+    fn wildcard_grants_all_descendants() {
+        let grant = "nl.svsticky.chroma.album.*";
+        assert!(Scope::AlbumCreate.is_granted_by(grant));
+        assert!(Scope::AlbumUpdate.is_granted_by(grant));
+        assert!(Scope::AlbumListDraft.is_granted_by(grant));
+        assert!(!Scope::PhotoCreate.is_granted_by(grant));
+
+        assert!(Scope::ALL
+            .iter()
+            .all(|scope| scope.is_granted_by("nl.svsticky.chroma.*")));
+    }
+
+    #[test]
+    fn wildcard_matches_whole_segments() {
+        assert!(!Scope::AlbumUpdate.is_granted_by("nl.svsticky.chroma.alb.*"));
+        assert!(!Scope::AlbumUpdate.is_granted_by("nl.svsticky.chroma.album*"));
+        assert!(!Scope::AlbumUpdate.is_granted_by("nl.svsticky.chroma.album.update.*"));
+    }
+
+    #[test]
+    fn malformed_wildcards_grant_nothing() {
+        for grant in ["*", ".*", "nl.*.album.update", "nl.svsticky.*.album.*", ""] {
+            assert!(
+                Scope::ALL.iter().all(|scope| !scope.is_granted_by(grant)),
+                "'{grant}' should not grant anything"
+            );
+        }
+    }
+
+    #[test]
+    fn valid_grants() {
+        assert!(Scope::is_valid_grant("nl.svsticky.chroma.photo.delete"));
+        assert!(Scope::is_valid_grant("nl.svsticky.chroma.photo.*"));
+        assert!(Scope::is_valid_grant("nl.svsticky.chroma.album.list.*"));
+        assert!(Scope::is_valid_grant("nl.svsticky.chroma.*"));
+
+        assert!(!Scope::is_valid_grant("nl.svsticky.chroma.photo.update"));
+        assert!(!Scope::is_valid_grant("nl.svsticky.chroma.user.*"));
+        assert!(!Scope::is_valid_grant("*"));
+    }
+
+    #[test]
+    fn check_grants_rejects_unknown() {
+        let grants = vec![
+            "nl.svsticky.chroma.album.*".to_string(),
+            "nl.svsticky.chroma.video.*".to_string(),
+        ];
+        assert!(Scope::check_grants(&grants).is_err());
+        assert!(Scope::check_grants(&grants[..1]).is_ok());
+    }
+
+    #[test]
+    fn album_roles_include_lesser_roles() {
+        assert!(Scope::AlbumListDraft.is_granted_by_album_role(AlbumRole::Viewer));
+        assert!(Scope::AlbumListDraft.is_granted_by_album_role(AlbumRole::Owner));
+        assert!(Scope::PhotoCreate.is_granted_by_album_role(AlbumRole::Contributor));
+        assert!(!Scope::PhotoCreate.is_granted_by_album_role(AlbumRole::Viewer));
+        assert!(Scope::AlbumDelete.is_granted_by_album_role(AlbumRole::Owner));
+        assert!(!Scope::AlbumDelete.is_granted_by_album_role(AlbumRole::Contributor));
+    }
+
+    #[test]
+    fn album_roles_grant_nothing_beyond_the_album() {
+        assert!(!Scope::AlbumCreate.is_granted_by_album_role(AlbumRole::Owner));
+        assert!(!Scope::CollectionUpdate.is_granted_by_album_role(AlbumRole::Owner));
+    }
+}
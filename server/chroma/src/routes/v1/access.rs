@@ -4,9 +4,9 @@ use serde::Deserialize;
 
 use proto::AccessResponse;
 
-use crate::routes::appdata::WebData;
 use crate::routes::authorization::Authorization;
 use crate::routes::error::WebResult;
+use crate::routes::scope::Scope;
 
 #[derive(Debug, Deserialize)]
 pub struct Query {
@@ -21,8 +21,8 @@ pub struct Query {
 /// middleware might.
 ///
 /// This endpoint can also be used to check if the logged in user has a certain scope, using hte `scope` query parameter.
+/// Unknown scopes are never held.
 pub async fn access(
-    data: WebData,
     auth: Authorization,
     query: web::Query<Query>,
 ) -> WebResult<Payload<AccessResponse>> {
@@ -33,14 +33,17 @@ pub async fn access(
     let response = if let Some(check_scope) = &query.scope {
         AccessResponse {
             admin: auth.is_admin,
-            has_requested_scope: Some(auth.has_scope(&data.db, check_scope).await?),
+            has_requested_scope: Some(match Scope::from_name(check_scope) {
+                Some(scope) => auth.has_scope(scope),
+                None => false,
+            }),
             all_scopes: None,
         }
     } else {
         AccessResponse {
             admin: auth.is_admin,
             has_requested_scope: None,
-            all_scopes: Some(auth.list_scopes(&data.db).await?),
+            all_scopes: Some(auth.list_scopes()),
         }
     };
 
@@ -0,0 +1,101 @@
+use std::str::FromStr;
+
+use actix_multiresponse::Payload;
+use actix_web::web;
+use serde::Deserialize;
+
+use dal::database::{AuditAction, AuditLogEntry, AuditLogFilter, UserType};
+use proto::ListAuditLogResponse;
+
+use crate::routes::appdata::WebData;
+use crate::routes::authorization::Authorization;
+use crate::routes::error::{Error, WebResult};
+
+/// The number of entries returned if no limit is provided
+const DEFAULT_LIMIT: i64 = 100;
+/// The maximum number of entries returned in one request
+const MAX_LIMIT: i64 = 1000;
+
+#[derive(Debug, Deserialize)]
+pub struct Query {
+    /// Either `Koala` or `Service`. Requires `actor_id` to be set
+    actor_type: Option<String>,
+    actor_id: Option<i32>,
+    action: Option<String>,
+    /// One of `User`, `Album`, `Photo`, `ApiKey` or `Role`
+    target_type: Option<String>,
+    target_id: Option<String>,
+    /// Only entries created at or after this UNIX timestamp
+    since: Option<i64>,
+    /// Only entries created before this UNIX timestamp
+    until: Option<i64>,
+    /// Only entries older than the entry with this ID, used to page through the log
+    before_id: Option<i64>,
+    limit: Option<i64>,
+}
+
+/// List entries in the audit log, newest first.
+///
+/// Only admins may read the audit log.
+///
+/// # Errors
+///
+/// - If the user is not an admin
+/// - If the actor type or action is unknown
+/// - If only one of the actor type and ID is provided
+/// - If the limit is not positive
+/// - If something went wrong
+pub async fn audit_log(
+    auth: Authorization,
+    data: WebData,
+    query: web::Query<Query>,
+) -> WebResult<Payload<ListAuditLogResponse>> {
+    if !auth.is_admin {
+        return Err(Error::Forbidden);
+    }
+
+    let actor = match (query.actor_type.as_deref(), query.actor_id) {
+        (Some("Koala"), Some(id)) => Some(UserType::Koala(id)),
+        (Some("Service"), Some(id)) => Some(UserType::ApiKey(id)),
+        (None, None) => None,
+        (Some("Koala" | "Service"), None) | (None, Some(_)) => {
+            return Err(Error::BadRequest(
+                "Actor type and actor ID must be provided together".into(),
+            ))
+        }
+        (Some(other), _) => return Err(Error::BadRequest(format!("Unknown actor type '{other}'"))),
+    };
+
+    let action = query
+        .action
+        .as_deref()
+        .map(|action| {
+            AuditAction::from_str(action)
+                .map_err(|_| Error::BadRequest(format!("Unknown action '{action}'")))
+        })
+        .transpose()?;
+
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+    if limit <= 0 {
+        return Err(Error::BadRequest("Limit must be positive".into()));
+    }
+
+    let filter = AuditLogFilter {
+        actor,
+        action,
+        target_type: query.target_type.clone(),
+        target_id: query.target_id.clone(),
+        since: query.since,
+        until: query.until,
+        before_id: query.before_id,
+    };
+
+    let entries = AuditLogEntry::list(&data.db, &filter, limit.min(MAX_LIMIT)).await?;
+
+    Ok(Payload(ListAuditLogResponse {
+        entries: entries
+            .into_iter()
+            .map(AuditLogEntry::to_proto)
+            .collect::<Vec<_>>(),
+    }))
+}
@@ -0,0 +1,81 @@
+use actix_multiresponse::Payload;
+use actix_web::web;
+
+use dal::consistency::ConsistencyReport;
+use dal::DalError;
+use proto::{ConsistencyCheckRequest, ConsistencyCheckResponse, StorageIncident};
+
+use crate::routes::appdata::{AlbumIdCache, WebData};
+use crate::routes::authorization::Authorization;
+use crate::routes::error::{Error, WebResult};
+
+/// Compare the contents of the storage bucket with the database and report any discrepancies.
+/// If `repair` is set, the discrepancies are repaired afterwards.
+/// Orphaned objects are deleted in the background, photos without an original are moved to
+/// the trash, or deleted permanently if `delete_missing_originals` is set,
+/// and stale URLs are removed. Objects which do not belong to a photo are never removed.
+/// Recorded incidents of missing cover photos are resolved.
+///
+/// Only admins may check consistency.
+///
+/// # Errors
+///
+/// - If something went wrong
+pub async fn fsck(
+    auth: Authorization,
+    data: WebData,
+    album_id_cache: web::Data<AlbumIdCache>,
+    payload: Payload<ConsistencyCheckRequest>,
+) -> WebResult<Payload<ConsistencyCheckResponse>> {
+    if !auth.is_admin {
+        return Err(Error::Forbidden);
+    }
+
+    let report = ConsistencyReport::check(&data.db, &data.storage)
+        .await
+        .map_err(|e| match e {
+            DalError::Storage(e) => Error::from(e),
+            DalError::Db(e) => Error::from(e),
+        })?;
+
+    let response = ConsistencyCheckResponse {
+        orphaned_object_keys: report.orphaned_objects.clone(),
+        unknown_object_keys: report.unknown_objects.clone(),
+        photos_missing_original: report
+            .photos_missing_original
+            .iter()
+            .map(|photo| photo.id.clone())
+            .collect(),
+        stale_url_object_keys: report
+            .stale_urls
+            .iter()
+            .map(|stale| stale.object_key.clone())
+            .collect(),
+        pending_deletions: report.pending_tombstones as i64,
+        repaired: payload.repair,
+        incidents: report
+            .incidents
+            .iter()
+            .map(|incident| StorageIncident {
+                album_id: incident.album_id.clone(),
+                photo_id: incident.photo_id.clone(),
+                object_key: incident.object_key.clone(),
+                created_at: incident.created_at,
+            })
+            .collect(),
+    };
+
+    if payload.repair {
+        report
+            .repair(
+                &data.db,
+                Some(&auth.to_dal_user_type()),
+                payload.delete_missing_originals,
+            )
+            .await?;
+        // Trashed and deleted photos may have been used as cover photo
+        album_id_cache.invalidate_all();
+    }
+
+    Ok(Payload(response))
+}
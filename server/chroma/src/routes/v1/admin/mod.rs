@@ -0,0 +1,21 @@
+use actix_web::web;
+use actix_web::web::ServiceConfig;
+
+use crate::routes::routable::Routable;
+
+mod audit_log;
+mod fsck;
+mod relayout;
+
+pub struct Router;
+
+impl Routable for Router {
+    fn configure(config: &mut ServiceConfig) {
+        config.service(
+            web::scope("/admin")
+                .route("/audit-log", web::get().to(audit_log::audit_log))
+                .route("/fsck", web::post().to(fsck::fsck))
+                .route("/relayout", web::post().to(relayout::relayout)),
+        );
+    }
+}
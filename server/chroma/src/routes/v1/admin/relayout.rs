@@ -0,0 +1,56 @@
+use actix_multiresponse::Payload;
+
+use dal::relayout::relayout as relayout_objects;
+use dal::DalError;
+use proto::{RelayoutStorageRequest, RelayoutStorageResponse};
+
+use crate::routes::appdata::WebData;
+use crate::routes::authorization::Authorization;
+use crate::routes::error::{Error, WebResult};
+
+/// The number of photos processed if no batch size is provided
+const DEFAULT_BATCH_SIZE: i64 = 100;
+
+/// Move the stored objects of a batch of photos to the configured storage key layout.
+/// Objects stored before the layout was changed keep working while they have not been moved,
+/// this should be called repeatedly with the returned cursor until no cursor is returned.
+///
+/// Only admins may relayout storage.
+///
+/// # Errors
+///
+/// - If the batch size is not positive
+/// - If something went wrong
+pub async fn relayout(
+    auth: Authorization,
+    data: WebData,
+    payload: Payload<RelayoutStorageRequest>,
+) -> WebResult<Payload<RelayoutStorageResponse>> {
+    if !auth.is_admin {
+        return Err(Error::Forbidden);
+    }
+
+    let batch_size = payload.batch_size.unwrap_or(DEFAULT_BATCH_SIZE);
+    if batch_size <= 0 {
+        return Err(Error::BadRequest(
+            "Provided value 'batchSize' must be positive".into(),
+        ));
+    }
+
+    let progress = relayout_objects(
+        &data.db,
+        &data.storage,
+        payload.cursor.as_deref(),
+        batch_size as usize,
+    )
+    .await
+    .map_err(|e| match e {
+        DalError::Storage(e) => Error::from(e),
+        DalError::Db(e) => Error::from(e),
+    })?;
+
+    Ok(Payload(RelayoutStorageResponse {
+        moved: progress.moved as i64,
+        next_cursor: progress.next_cursor,
+    }))
+}
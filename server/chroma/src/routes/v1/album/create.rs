@@ -1,19 +1,24 @@
 use actix_multiresponse::Payload;
 use tracing::trace;
 
-use dal::database::Album;
+use dal::database::{Album, AlbumMember, AlbumRole};
 use proto::{CreateAlbumRequest, CreateAlbumResponse};
 
 use crate::routes::appdata::WebData;
-use crate::routes::authorization::Authorization;
+use crate::routes::authorization::{Authorization, AuthorizedUser};
 use crate::routes::error::{Error, WebResult};
+use crate::routes::scope::Scope;
 
 /// Create a new empty album.
 /// The album will not contain any photos yet.
+/// The user creating the album becomes its owner, see [AlbumRole::Owner],
+/// if they may create albums. Users who may only create drafts become a viewer,
+/// so they can find their draft but gain no permissions through it.
 ///
 /// # Errors
 ///
 /// - If the provided `name`'s length is longer than [Album::MAX_NAME_LENGTH]
+/// - If the user is bound to specific albums
 /// - If something went wrong
 pub async fn create(
     auth: Authorization,
@@ -22,9 +27,7 @@ pub async fn create(
 ) -> WebResult<Payload<CreateAlbumResponse>> {
     if !auth.is_admin {
         let is_draft = payload.is_draft.unwrap_or(false);
-        let create_scope = auth
-            .has_scope(&data.db, "nl.svsticky.chroma.album.create")
-            .await?;
+        let create_scope = auth.has_scope(Scope::AlbumCreate);
         trace!("is_draft: {is_draft}");
         trace!("has_scope: {create_scope}");
         if !is_draft && !create_scope {
@@ -32,6 +35,11 @@ pub async fn create(
         }
     }
 
+    // The new album would be outside the albums the user is bound to
+    if auth.is_album_bound() {
+        return Err(Error::Forbidden);
+    }
+
     if payload.name.len() > Album::MAX_NAME_LENGTH {
         return Err(Error::BadRequest(format!(
             "Provided value 'name' with length '{}' exceeds the maximum length of '{}'",
@@ -40,12 +48,26 @@ pub async fn create(
         )));
     }
 
+    // The album is not created if its owner cannot be set
+    let mut tx = data.db.begin().await?;
     let album = Album::create(
-        &data.db,
+        &mut tx,
         &payload.name,
         payload.is_draft.unwrap_or(false),
-        auth.to_dal_user_type(&data.db).await?,
+        auth.to_dal_user_type(),
     )
     .await?;
+
+    if let AuthorizedUser::Koala { koala_id, .. } = &auth.user {
+        let role = if auth.is_admin || auth.has_scope(Scope::AlbumCreate) {
+            AlbumRole::Owner
+        } else {
+            AlbumRole::Viewer
+        };
+
+        AlbumMember::set(&mut tx, &album.id, *koala_id, role, *koala_id).await?;
+    }
+    tx.commit().await?;
+
     Ok(Payload(CreateAlbumResponse { id: album.id }))
 }
@@ -1,16 +1,19 @@
 use actix_multiresponse::Payload;
+use actix_web::web;
 
-use dal::database::PhotoQuality;
-use dal::database::{Album, Photo};
+use dal::database::{Album, AuditAction, AuditLogEntry, AuditTarget};
 use proto::DeleteAlbumRequest;
 
-use crate::routes::appdata::WebData;
+use crate::routes::appdata::{AlbumIdCache, WebData};
 use crate::routes::authorization::Authorization;
 use crate::routes::empty::Empty;
 use crate::routes::error::{Error, WebResult};
+use crate::routes::scope::Scope;
 
-/// Delete an existing album.
-/// All photos in this album will consequently also be deleted.
+/// Move an existing album to the trash.
+/// The album and all its photos are hidden, and will be permanently deleted
+/// once they have been in the trash for longer than the configured retention period.
+/// Until then, the album can be restored by an admin.
 ///
 /// # Errors
 ///
@@ -19,39 +22,44 @@ use crate::routes::error::{Error, WebResult};
 pub async fn delete(
     auth: Authorization,
     data: WebData,
+    album_id_cache: web::Data<AlbumIdCache>,
     payload: Payload<DeleteAlbumRequest>,
 ) -> WebResult<Empty> {
+    let mut album = Album::get_by_id(&data.db, &payload.id)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    if !auth.may_access_album(&data.db, &album.id).await? {
+        return Err(Error::Forbidden);
+    }
+
     if !auth.is_admin
         && !auth
-            .has_scope(&data.db, "nl.svsticky.chroma.album.delete")
+            .has_album_scope(&data.db, &album.id, Scope::AlbumDelete)
             .await?
     {
         return Err(Error::Forbidden);
     }
 
-    let album = Album::get_by_id(&data.db, &payload.id)
-        .await?
-        .ok_or(Error::NotFound)?;
-
     // Only admins may modify published albums.
     if !album.is_draft && !auth.is_admin {
         return Err(Error::Forbidden);
     }
 
-    let photos = Photo::list_in_album(&data.db, &album.id).await?;
-    for photo in photos {
-        data.storage
-            .delete_photo(&photo.id, &PhotoQuality::Original)
-            .await?;
-        data.storage
-            .delete_photo(&photo.id, &PhotoQuality::W1600)
-            .await?;
-        data.storage
-            .delete_photo(&photo.id, &PhotoQuality::W400)
-            .await?;
-    }
+    let mut tx = data.db.begin().await?;
+    album.trash(&mut tx).await?;
+    AuditLogEntry::record(
+        &mut tx,
+        Some(&auth.to_dal_user_type()),
+        AuditAction::AlbumTrashed,
+        AuditTarget::Album(album.id.clone()),
+        None,
+        None,
+    )
+    .await?;
+    tx.commit().await?;
 
-    album.delete(&data.db).await?;
+    album_id_cache.remove(&album.id).await;
 
     Ok(Empty)
 }
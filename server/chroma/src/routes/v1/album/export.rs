@@ -0,0 +1,259 @@
+use actix_web::http::header::ContentDisposition;
+use actix_web::http::StatusCode;
+use actix_web::web::Bytes;
+use actix_web::{web, HttpRequest, HttpResponse};
+use futures::channel::mpsc;
+use futures::{SinkExt, StreamExt, TryStreamExt};
+use serde::Deserialize;
+use thiserror::Error;
+use tracing::warn;
+
+use dal::database::{Album, Photo};
+use dal::storage_engine::error::StorageError;
+use dal::storage_engine::Storage;
+
+use crate::routes::appdata::WebData;
+use crate::routes::authorization::Authorization;
+use crate::routes::error::{Error, WebResult};
+use crate::routes::ratelimit::Ratelimited;
+use crate::routes::scope::Scope;
+use crate::routes::v1::album::zip::{self, ZipLimitExceeded, ZipWriter};
+use crate::routes::v1::PhotoQuality;
+
+/// The number of objects of which the size is retrieved concurrently
+const CONCURRENT_SIZE_REQUESTS: usize = 16;
+
+#[derive(Debug, Deserialize)]
+pub struct Query {
+    /// The ID of the album to export
+    id: String,
+    /// The quality in which photos are exported.
+    /// Photos which have not been created in this quality yet are exported in their original quality.
+    #[serde(default)]
+    quality: PhotoQuality,
+}
+
+/// Writing the archive failed after the response was started
+#[derive(Debug, Error)]
+enum ExportError {
+    #[error("{0}")]
+    Storage(#[from] StorageError),
+    #[error("{0}")]
+    Zip(#[from] ZipLimitExceeded),
+}
+
+impl From<ExportError> for Error {
+    fn from(value: ExportError) -> Self {
+        match value {
+            ExportError::Storage(e) => Self::StorageEngine(e),
+            ExportError::Zip(_) => Self::Other(StatusCode::INTERNAL_SERVER_ERROR),
+        }
+    }
+}
+
+/// A photo to be written to the archive
+struct ExportEntry {
+    name: String,
+    object_key: String,
+    created_at: i64,
+}
+
+/// Download all photos of an album as a ZIP archive.
+/// The archive is streamed while it is written, photos are read from storage one at a time.
+/// Photos are named after their position in the album.
+/// The archive is named after the album, with characters unsafe in file names replaced.
+///
+/// Draft albums may only be exported by admins, users who may list draft albums
+/// and members of the album.
+///
+/// # Errors
+///
+/// - If the album does not exist
+/// - If the album is a draft and the user may not list draft albums
+/// - If the photos exceed the maximum export size, or the archive would exceed the limits of ZIP
+/// - If the user has exported too many albums recently
+/// - If something went wrong
+pub async fn export(
+    auth: Authorization,
+    data: WebData,
+    req: HttpRequest,
+    query: web::Query<Query>,
+) -> WebResult<Ratelimited<HttpResponse>> {
+    let album = Album::get_by_id(&data.db, &query.id)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    if !auth.may_access_album(&data.db, &album.id).await? {
+        return Err(Error::Forbidden);
+    }
+
+    if album.is_draft
+        && !auth.is_admin
+        && !auth
+            .has_album_scope(&data.db, &album.id, Scope::AlbumListDraft)
+            .await?
+    {
+        return Err(Error::Forbidden);
+    }
+
+    // Checked before the size of every photo is retrieved from storage,
+    // so exports rejected for their size count towards the ratelimit as well
+    let ratelimit = data.ratelimits.album_export.check(&auth, &req)?;
+
+    let photos = Photo::list_in_album(&data.db, &album.id).await?;
+    if photos.len() > zip::MAX_ENTRIES {
+        return Err(Error::BadRequest(format!(
+            "Album contains more than {} photos and cannot be exported",
+            zip::MAX_ENTRIES
+        )));
+    }
+
+    let quality: dal::database::PhotoQuality = query.quality.clone().into();
+    let sized_entries = futures::stream::iter(photos.into_iter().enumerate())
+        .map(|(idx, photo)| {
+            let quality = quality.clone();
+            let storage = &data.storage;
+            async move {
+                let quality = if photo.is_quality_created(&quality).await? {
+                    quality
+                } else {
+                    dal::database::PhotoQuality::Original
+                };
+
+                let object_key = photo.object_key(&quality).await?;
+                let size = storage.get_photo_size(&object_key).await?;
+
+                Ok::<_, Error>((
+                    ExportEntry {
+                        name: format!("{:04}_{}.webp", idx + 1, photo.id),
+                        object_key,
+                        created_at: photo.created_at,
+                    },
+                    size,
+                ))
+            }
+        })
+        .buffered(CONCURRENT_SIZE_REQUESTS)
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    let max_size = data.config.export_max_size();
+    let total_size: u64 = sized_entries.iter().map(|(_, size)| size).sum();
+    if total_size > max_size {
+        return Err(Error::BadRequest(format!(
+            "Album export of {total_size} bytes exceeds the maximum size of {max_size} bytes"
+        )));
+    }
+
+    // The headers and central directory count towards the limit of ZIP as well
+    let archive_size = zip::archive_size(
+        sized_entries
+            .iter()
+            .map(|(entry, size)| (entry.name.as_str(), *size)),
+    );
+    if archive_size > zip::MAX_SIZE {
+        return Err(Error::BadRequest(format!(
+            "Album export of {archive_size} bytes exceeds the maximum archive size of {} bytes",
+            zip::MAX_SIZE
+        )));
+    }
+
+    let entries = sized_entries
+        .into_iter()
+        .map(|(entry, _)| entry)
+        .collect::<Vec<_>>();
+
+    let (mut tx, rx) = mpsc::channel(4);
+    let storage = data.storage.clone();
+    let album_id = album.id.clone();
+    tokio::spawn(async move {
+        if let Err(e) = write_archive(&storage, entries, &mut tx).await {
+            warn!("Failed to export album '{album_id}': {e}");
+            // Aborts the response, so the client does not receive a truncated archive
+            let _ = tx.send(Err(Error::from(e))).await;
+        }
+    });
+
+    Ok(Ratelimited(
+        HttpResponse::Ok()
+            .content_type("application/zip")
+            .insert_header(ContentDisposition::attachment(archive_name(&album.name)))
+            .streaming(rx),
+        ratelimit,
+    ))
+}
+
+/// Write the archive to the channel.
+/// Stops early without an error if the client stopped receiving the archive.
+async fn write_archive(
+    storage: &Storage,
+    entries: Vec<ExportEntry>,
+    tx: &mut mpsc::Sender<Result<Bytes, Error>>,
+) -> Result<(), ExportError> {
+    let mut zip = ZipWriter::default();
+
+    for entry in entries {
+        if tx
+            .send(Ok(zip.start_entry(&entry.name, entry.created_at)?))
+            .await
+            .is_err()
+        {
+            return Ok(());
+        }
+
+        let body = storage.get_photo_stream(&entry.object_key).await?;
+        futures::pin_mut!(body);
+        while let Some(chunk) = body.next().await {
+            let chunk = chunk?;
+            zip.write(&chunk);
+            if tx.send(Ok(chunk)).await.is_err() {
+                return Ok(());
+            }
+        }
+
+        if tx.send(Ok(zip.finish_entry()?)).await.is_err() {
+            return Ok(());
+        }
+    }
+
+    let _ = tx.send(Ok(zip.finish()?)).await;
+    Ok(())
+}
+
+/// The file name of the archive of an album.
+/// Only printable ASCII is kept, other characters and characters with a meaning
+/// in paths or headers are replaced, so the name is safe to use in a header and on any file system.
+fn archive_name(album_name: &str) -> String {
+    let name = album_name
+        .trim()
+        .chars()
+        .map(|c| match c {
+            '"' | '\\' | '/' | ':' | '*' | '?' | '<' | '>' | '|' | ';' | '%' => '_',
+            c if c.is_ascii_graphic() || c == ' ' => c,
+            _ => '_',
+        })
+        .collect::<String>();
+
+    if name.is_empty() {
+        "album.zip".to_string()
+    } else {
+        format!("{name}.zip")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::archive_name;
+
+    #[test]
+    fn archive_name_is_sanitized() {
+        assert_eq!(archive_name("Summer camp 2023"), "Summer camp 2023.zip");
+        assert_eq!(archive_name("../etc/passwd"), ".._etc_passwd.zip");
+        assert_eq!(
+            archive_name("a\"b\r\nSet-Cookie: c"),
+            "a_b__Set-Cookie_ c.zip"
+        );
+        assert_eq!(archive_name("Café"), "Caf_.zip");
+        assert_eq!(archive_name("  "), "album.zip");
+    }
+}
@@ -1,5 +1,5 @@
 use actix_multiresponse::Payload;
-use actix_web::web;
+use actix_web::{web, Either, HttpRequest};
 use futures::future::join_all;
 use serde::Deserialize;
 
@@ -11,6 +11,7 @@ use proto::{AlbumWithCoverPhoto, GetAlbumResponse};
 use crate::routes::appdata::{AlbumIdCache, WebData};
 use crate::routes::authorization::Authorization;
 use crate::routes::error::{Error, WebResult};
+use crate::routes::ratelimit::{RatelimitState, Ratelimited};
 
 #[derive(Debug, Deserialize)]
 pub struct Query {
@@ -23,17 +24,21 @@ pub struct Query {
 }
 
 /// Retrieve an album and all its photos by its ID.
+/// Retrieving the photos is ratelimited like retrieving the bytes of a photo.
 ///
 /// # Errors
 ///
 /// - If the requested album does not exist
+/// - If the user is bound to other albums
+/// - If the user has retrieved too many photos recently
 /// - If something went wrong
 pub async fn get(
-    _: Authorization,
+    auth: Authorization,
     data: WebData,
     album_id_cache: web::Data<AlbumIdCache>,
+    req: HttpRequest,
     query: web::Query<Query>,
-) -> WebResult<Payload<GetAlbumResponse>> {
+) -> WebResult<Either<Payload<GetAlbumResponse>, Ratelimited<Payload<GetAlbumResponse>>>> {
     let album = match album_id_cache.get(&query.id).await {
         Some(v) => v,
         None => Album::get_by_id(&data.db, &query.id)
@@ -41,10 +46,16 @@ pub async fn get(
             .ok_or(Error::NotFound)?,
     };
 
+    if !auth.may_access_album(&data.db, &album.id).await? {
+        return Err(Error::Forbidden);
+    }
+
     // If the user requests that photos are not returned, return an empty list.
+    let mut ratelimit: Option<RatelimitState> = None;
     let photos = match query.without_photos {
         Some(true) => vec![],
         Some(false) | None => {
+            ratelimit = Some(data.ratelimits.photo_convert.check(&auth, &req)?);
             let photos = Photo::list_in_album(&data.db, &album.id).await?;
 
             // Convert the DAL format to Proto format
@@ -85,11 +96,16 @@ pub async fn get(
         None
     };
 
-    Ok(Payload(GetAlbumResponse {
+    let response = Payload(GetAlbumResponse {
         photos,
         album: Some(AlbumWithCoverPhoto {
             album: Some(album.to_proto(&data.db).await?),
             cover_photo,
         }),
-    }))
+    });
+
+    Ok(match ratelimit {
+        Some(ratelimit) => Either::Right(Ratelimited(response, ratelimit)),
+        None => Either::Left(response),
+    })
 }
@@ -2,8 +2,9 @@ use actix_multiresponse::Payload;
 use actix_web::web;
 use futures::future::{join_all, try_join_all};
 use serde::Deserialize;
+use tracing::warn;
 
-use dal::database::{Album, Photo};
+use dal::database::{Album, AlbumCollection, Photo, StorageIncident};
 use dal::storage_engine::aws_error::GetObjectErrorKind;
 use dal::storage_engine::error::{SdkError, StorageError};
 use dal::DalError;
@@ -12,6 +13,7 @@ use proto::{AlbumWithCoverPhoto, ListAlbumsResponse};
 use crate::routes::appdata::{AlbumIdCache, WebData};
 use crate::routes::authorization::Authorization;
 use crate::routes::error::{Error, WebResult};
+use crate::routes::scope::Scope;
 use crate::routes::v1::PhotoQuality;
 
 #[derive(Debug, Deserialize)]
@@ -20,12 +22,15 @@ pub struct Query {
     include_cover_photo: bool,
     #[serde(default)]
     quality_preference: PhotoQuality,
+    /// Only list the albums in this collection
+    collection_id: Option<String>,
 }
 
-/// List all known albums
+/// List all known albums, or all albums in a collection
 ///
 /// # Errors
 ///
+/// - If the requested collection does not exist
 /// - If something went wrong
 pub async fn list(
     auth: Authorization,
@@ -34,7 +39,16 @@ pub async fn list(
     query: web::Query<Query>,
 ) -> WebResult<Payload<ListAlbumsResponse>> {
     // Fetch only IDs, so we can grab the rest from cache
-    let ids = Album::list_ids(&data.db).await?;
+    let ids = match &query.collection_id {
+        Some(collection_id) => {
+            AlbumCollection::get_by_id(&data.db, collection_id)
+                .await?
+                .ok_or(Error::NotFound)?
+                .list_album_ids(&data.db)
+                .await?
+        }
+        None => Album::list_ids(&data.db).await?,
+    };
 
     // Fetch cached albums
     let cached_albums = join_all(ids.into_iter().map(|f| {
@@ -76,13 +90,17 @@ pub async fn list(
     .concat();
 
     // Check if we should include draft albums
-    let include_draft = auth.is_admin
-        || auth
-            .has_scope(&data.db, "nl.svsticky.chroma.album.list.draft")
-            .await?;
+    let include_draft = auth.is_admin || auth.has_scope(Scope::AlbumListDraft);
 
     if !include_draft {
-        albums.retain(|f| !f.is_draft);
+        // Members of a draft album may see it regardless
+        let member_albums = auth.list_member_albums(&data.db).await?;
+        albums.retain(|f| !f.is_draft || member_albums.contains(&f.id));
+    }
+
+    // Users bound to specific albums only see those albums
+    if let Some(album_ids) = auth.list_bound_albums(&data.db).await? {
+        albums.retain(|album| album_ids.contains(&album.id));
     }
 
     // Transform them all to the proto response
@@ -113,9 +131,32 @@ pub async fn list(
                                             SdkError::ServiceError(s),
                                         )) => match s.err().kind {
                                             GetObjectErrorKind::NoSuchKey(_) => {
-                                                album_id_cache.remove(&album.id).await;
-                                                album.delete(&database).await?;
-                                                Ok(None)
+                                                // Never modify anything but the cover here, the object
+                                                // may only be missing due to a storage misconfiguration.
+                                                // The incident is reported by the consistency checker.
+                                                warn!(
+                                                    "Cover photo '{}' of album '{}' is missing from storage",
+                                                    photo.id, album.id
+                                                );
+                                                StorageIncident::record(
+                                                    &database,
+                                                    &album.id,
+                                                    &photo.id,
+                                                    &quality,
+                                                    &photo.object_key(&quality).await?,
+                                                )
+                                                .await?;
+
+                                                let mut album = album;
+                                                album.unset_cover_photo(&database).await?;
+                                                album_id_cache
+                                                    .insert(album.id.clone(), album.clone())
+                                                    .await;
+
+                                                Ok(Some(AlbumWithCoverPhoto {
+                                                    album: Some(album.to_proto(&database).await?),
+                                                    cover_photo: None,
+                                                }))
                                             }
                                             _ => Err(e),
                                         },
@@ -0,0 +1,54 @@
+use actix_multiresponse::Payload;
+use actix_web::web;
+use serde::Deserialize;
+
+use dal::database::{Album, AlbumMember};
+use proto::ListAlbumMembersResponse;
+
+use crate::routes::appdata::WebData;
+use crate::routes::authorization::Authorization;
+use crate::routes::error::{Error, WebResult};
+use crate::routes::scope::Scope;
+
+#[derive(Debug, Deserialize)]
+pub struct Query {
+    /// The ID of the album to list the members of
+    id: String,
+}
+
+/// List the members of an album and their roles.
+/// Members may see who else is a member, as may users who may list draft albums.
+///
+/// # Errors
+///
+/// - If the album does not exist
+/// - If something went wrong
+pub async fn list_members(
+    auth: Authorization,
+    data: WebData,
+    query: web::Query<Query>,
+) -> WebResult<Payload<ListAlbumMembersResponse>> {
+    let album = Album::get_by_id(&data.db, &query.id)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    if !auth.may_access_album(&data.db, &album.id).await? {
+        return Err(Error::Forbidden);
+    }
+
+    if !auth.is_admin
+        && !auth
+            .has_album_scope(&data.db, &album.id, Scope::AlbumListDraft)
+            .await?
+    {
+        return Err(Error::Forbidden);
+    }
+
+    let members = AlbumMember::list_for_album(&data.db, &album.id)
+        .await?
+        .into_iter()
+        .map(AlbumMember::to_proto)
+        .collect();
+
+    Ok(Payload(ListAlbumMembersResponse { members }))
+}
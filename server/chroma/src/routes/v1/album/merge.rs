@@ -0,0 +1,66 @@
+use actix_multiresponse::Payload;
+use actix_web::web;
+
+use dal::database::{Album, AuditAction, AuditLogEntry, AuditTarget};
+use proto::MergeAlbumsRequest;
+
+use crate::routes::appdata::{AlbumIdCache, WebData};
+use crate::routes::authorization::Authorization;
+use crate::routes::empty::Empty;
+use crate::routes::error::{Error, WebResult};
+
+/// Merge the source album into the target album.
+/// All photos of the source album are moved to the target album,
+/// after which the source album is deleted.
+/// The cover photo of the target album is kept, unless it has none.
+/// In that case, the cover photo of the source album is used.
+///
+/// Only admins may merge albums.
+///
+/// # Errors
+///
+/// - If either album does not exist
+/// - If the source and target album are the same album
+/// - If something went wrong
+pub async fn merge(
+    auth: Authorization,
+    data: WebData,
+    album_id_cache: web::Data<AlbumIdCache>,
+    payload: Payload<MergeAlbumsRequest>,
+) -> WebResult<Empty> {
+    if !auth.is_admin {
+        return Err(Error::Forbidden);
+    }
+
+    if payload.source_album_id.eq(&payload.target_album_id) {
+        return Err(Error::BadRequest(
+            "An album cannot be merged into itself".into(),
+        ));
+    }
+
+    let mut target = Album::get_by_id(&data.db, &payload.target_album_id)
+        .await?
+        .ok_or(Error::NotFound)?;
+    let source = Album::get_by_id(&data.db, &payload.source_album_id)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    let mut tx = data.db.begin().await?;
+    source.merge_into(&mut target, &mut tx).await?;
+    AuditLogEntry::record(
+        &mut tx,
+        Some(&auth.to_dal_user_type()),
+        AuditAction::AlbumMerged,
+        AuditTarget::Album(payload.source_album_id.clone()),
+        None,
+        Some(target.id.clone()),
+    )
+    .await?;
+    tx.commit().await?;
+
+    album_id_cache.remove(&payload.source_album_id).await;
+
+    album_id_cache.insert(target.id.clone(), target).await;
+
+    Ok(Empty)
+}
@@ -5,9 +5,17 @@ use crate::routes::routable::Routable;
 
 mod create;
 mod delete;
+mod export;
 mod get;
 mod list;
+mod list_members;
+mod merge;
+mod order;
+mod remove_member;
+mod split;
 mod update;
+mod update_member;
+mod zip;
 
 pub struct Router;
 
@@ -19,7 +27,349 @@ impl Routable for Router {
                 .route("", web::delete().to(delete::delete))
                 .route("", web::get().to(get::get))
                 .route("/list", web::get().to(list::list))
+                .route("/export", web::get().to(export::export))
+                .route("/members", web::get().to(list_members::list_members))
+                .route("/members", web::patch().to(update_member::update_member))
+                .route("/members", web::delete().to(remove_member::remove_member))
+                .route("/order", web::patch().to(order::order))
+                .route("/merge", web::post().to(merge::merge))
+                .route("/split", web::post().to(split::split))
                 .route("", web::patch().to(update::update)),
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::header::{ACCEPT, AUTHORIZATION};
+    use actix_web::http::StatusCode;
+    use actix_web::{test, App};
+    use mock_koala::MockUser;
+
+    use dal::database::{AlbumMember, AlbumRole};
+    use proto::{
+        CreateAlbumRequest, CreateAlbumResponse, DeleteAlbumRequest, RemoveAlbumMemberRequest,
+        ReorderAlbumRequest, UpdateAlbumMemberRequest, UpdateAlbumRequest,
+    };
+
+    use crate::routes::appdata::Caches;
+    use crate::testing;
+
+    /// Whether the role allows the request, judging by its response.
+    fn is_allowed(status: StatusCode) -> bool {
+        status != StatusCode::FORBIDDEN
+    }
+
+    #[actix_web::test]
+    #[ignore = "requires the database and S3 from docker-compose.yml"]
+    async fn roles_allow_album_changes() {
+        let koala_id = testing::random_koala_id();
+        let koala = testing::start_koala(vec![MockUser::new(koala_id, "Jan", "Jansen")]).await;
+        let data = testing::app_data(&koala).await;
+        let app = test::init_service(App::new().configure(testing::configure(data.clone()))).await;
+        let session_id = testing::session_id(
+            &test::call_service(&app, testing::login_request(&koala, koala_id).to_request()).await,
+        );
+
+        for role in testing::ALBUM_ROLES {
+            let album = testing::draft_album_with_role(&data.db, koala_id, role).await;
+            let req = test::TestRequest::patch()
+                .uri("/api/v1/album")
+                .insert_header((AUTHORIZATION, session_id.as_str()))
+                .set_json(UpdateAlbumRequest {
+                    id: album.id.clone(),
+                    name: Some("Renamed".into()),
+                    ..Default::default()
+                })
+                .to_request();
+            let status = test::call_service(&app, req).await.status();
+            assert_eq!(
+                is_allowed(status),
+                role >= Some(AlbumRole::Owner),
+                "update as {role:?}"
+            );
+
+            let req = test::TestRequest::patch()
+                .uri("/api/v1/album/order")
+                .insert_header((AUTHORIZATION, session_id.as_str()))
+                .set_json(ReorderAlbumRequest {
+                    album_id: album.id.clone(),
+                    photo_ids: Vec::new(),
+                })
+                .to_request();
+            let status = test::call_service(&app, req).await.status();
+            assert_eq!(
+                is_allowed(status),
+                role >= Some(AlbumRole::Owner),
+                "order as {role:?}"
+            );
+
+            let req = test::TestRequest::delete()
+                .uri("/api/v1/album")
+                .insert_header((AUTHORIZATION, session_id.as_str()))
+                .set_json(DeleteAlbumRequest { id: album.id })
+                .to_request();
+            let status = test::call_service(&app, req).await.status();
+            assert_eq!(
+                is_allowed(status),
+                role >= Some(AlbumRole::Owner),
+                "delete as {role:?}"
+            );
+        }
+    }
+
+    #[actix_web::test]
+    #[ignore = "requires the database and S3 from docker-compose.yml"]
+    async fn roles_allow_managing_members() {
+        let koala_id = testing::random_koala_id();
+        let koala = testing::start_koala(vec![MockUser::new(koala_id, "Jan", "Jansen")]).await;
+        let data = testing::app_data(&koala).await;
+        let app = test::init_service(App::new().configure(testing::configure(data.clone()))).await;
+        let session_id = testing::session_id(
+            &test::call_service(&app, testing::login_request(&koala, koala_id).to_request()).await,
+        );
+
+        for role in testing::ALBUM_ROLES {
+            let album = testing::draft_album_with_role(&data.db, koala_id, role).await;
+            let req = test::TestRequest::get()
+                .uri(&format!("/api/v1/album/members?id={}", album.id))
+                .insert_header((AUTHORIZATION, session_id.as_str()))
+                .to_request();
+            let status = test::call_service(&app, req).await.status();
+            assert_eq!(
+                is_allowed(status),
+                role >= Some(AlbumRole::Viewer),
+                "list members as {role:?}"
+            );
+
+            let req = test::TestRequest::patch()
+                .uri("/api/v1/album/members")
+                .insert_header((AUTHORIZATION, session_id.as_str()))
+                .set_json(UpdateAlbumMemberRequest {
+                    album_id: album.id.clone(),
+                    user_id: koala_id,
+                    role: proto::AlbumRole::Owner as i32,
+                })
+                .to_request();
+            let status = test::call_service(&app, req).await.status();
+            assert_eq!(
+                is_allowed(status),
+                role >= Some(AlbumRole::Owner),
+                "update member as {role:?}"
+            );
+
+            let req = test::TestRequest::delete()
+                .uri("/api/v1/album/members")
+                .insert_header((AUTHORIZATION, session_id.as_str()))
+                .set_json(RemoveAlbumMemberRequest {
+                    album_id: album.id.clone(),
+                    user_id: koala_id,
+                })
+                .to_request();
+            let status = test::call_service(&app, req).await.status();
+            assert_eq!(
+                is_allowed(status),
+                role >= Some(AlbumRole::Owner),
+                "remove member as {role:?}"
+            );
+        }
+    }
+
+    #[actix_web::test]
+    #[ignore = "requires the database and S3 from docker-compose.yml"]
+    async fn draft_creator_without_scope_becomes_viewer() {
+        let koala_id = testing::random_koala_id();
+        let koala = testing::start_koala(vec![MockUser::new(koala_id, "Jan", "Jansen")]).await;
+        let data = testing::app_data(&koala).await;
+        let app = test::init_service(App::new().configure(testing::configure(data.clone()))).await;
+        let session_id = testing::session_id(
+            &test::call_service(&app, testing::login_request(&koala, koala_id).to_request()).await,
+        );
+
+        let req = test::TestRequest::post()
+            .uri("/api/v1/album")
+            .insert_header((AUTHORIZATION, session_id.as_str()))
+            .set_json(CreateAlbumRequest {
+                name: "Published".into(),
+                is_draft: Some(false),
+            })
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            StatusCode::FORBIDDEN
+        );
+
+        let req = test::TestRequest::post()
+            .uri("/api/v1/album")
+            .insert_header((AUTHORIZATION, session_id.as_str()))
+            .insert_header((ACCEPT, "application/json"))
+            .set_json(CreateAlbumRequest {
+                name: "Draft".into(),
+                is_draft: Some(true),
+            })
+            .to_request();
+        let resp: CreateAlbumResponse = test::call_and_read_body_json(&app, req).await;
+
+        let role = AlbumMember::get_role(&data.db, &resp.id, koala_id)
+            .await
+            .unwrap();
+        assert_eq!(role, Some(AlbumRole::Viewer));
+    }
+
+    #[actix_web::test]
+    #[ignore = "requires the database and S3 from docker-compose.yml"]
+    async fn admin_creator_becomes_owner() {
+        let koala_id = testing::random_koala_id();
+        let koala =
+            testing::start_koala(vec![MockUser::new(koala_id, "Jan", "Jansen").admin()]).await;
+        let data = testing::app_data(&koala).await;
+        let app = test::init_service(App::new().configure(testing::configure(data.clone()))).await;
+        let session_id = testing::session_id(
+            &test::call_service(&app, testing::login_request(&koala, koala_id).to_request()).await,
+        );
+
+        let req = test::TestRequest::post()
+            .uri("/api/v1/album")
+            .insert_header((AUTHORIZATION, session_id.as_str()))
+            .insert_header((ACCEPT, "application/json"))
+            .set_json(CreateAlbumRequest {
+                name: "Draft".into(),
+                is_draft: Some(true),
+            })
+            .to_request();
+        let resp: CreateAlbumResponse = test::call_and_read_body_json(&app, req).await;
+
+        let role = AlbumMember::get_role(&data.db, &resp.id, koala_id)
+            .await
+            .unwrap();
+        assert_eq!(role, Some(AlbumRole::Owner));
+    }
+
+    #[actix_web::test]
+    #[ignore = "requires the database and S3 from docker-compose.yml"]
+    async fn deleted_album_is_evicted_on_all_workers() {
+        let koala_id = testing::random_koala_id();
+        let koala = testing::start_koala(vec![MockUser::new(koala_id, "Jan", "Jansen")]).await;
+        let data = testing::app_data(&koala).await;
+        let caches = Caches::new();
+        let worker_a = test::init_service(
+            App::new().configure(testing::configure_with_caches(data.clone(), caches.clone())),
+        )
+        .await;
+        let worker_b = test::init_service(
+            App::new().configure(testing::configure_with_caches(data.clone(), caches)),
+        )
+        .await;
+        let session_id = testing::session_id(
+            &test::call_service(
+                &worker_a,
+                testing::login_request(&koala, koala_id).to_request(),
+            )
+            .await,
+        );
+
+        // Updating the album caches it
+        let album =
+            testing::draft_album_with_role(&data.db, koala_id, Some(AlbumRole::Owner)).await;
+        let req = test::TestRequest::patch()
+            .uri("/api/v1/album")
+            .insert_header((AUTHORIZATION, session_id.as_str()))
+            .set_json(UpdateAlbumRequest {
+                id: album.id.clone(),
+                name: Some("Renamed".into()),
+                ..Default::default()
+            })
+            .to_request();
+        assert!(test::call_service(&worker_b, req)
+            .await
+            .status()
+            .is_success());
+
+        let req = test::TestRequest::delete()
+            .uri("/api/v1/album")
+            .insert_header((AUTHORIZATION, session_id.as_str()))
+            .set_json(DeleteAlbumRequest {
+                id: album.id.clone(),
+            })
+            .to_request();
+        assert!(test::call_service(&worker_a, req)
+            .await
+            .status()
+            .is_success());
+
+        let req = test::TestRequest::get()
+            .uri(&format!(
+                "/api/v1/album?id={}&without_photos=true",
+                album.id
+            ))
+            .insert_header((AUTHORIZATION, session_id.as_str()))
+            .to_request();
+        assert_eq!(
+            test::call_service(&worker_b, req).await.status(),
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[actix_web::test]
+    #[ignore = "requires the database and S3 from docker-compose.yml"]
+    async fn last_owner_cannot_leave() {
+        let koala_id = testing::random_koala_id();
+        let other_id = testing::random_koala_id();
+        let koala = testing::start_koala(vec![
+            MockUser::new(koala_id, "Jan", "Jansen"),
+            MockUser::new(other_id, "Piet", "Pietersen"),
+        ])
+        .await;
+        let data = testing::app_data(&koala).await;
+        let app = test::init_service(App::new().configure(testing::configure(data.clone()))).await;
+        let session_id = testing::session_id(
+            &test::call_service(&app, testing::login_request(&koala, koala_id).to_request()).await,
+        );
+        // Creates the other user
+        test::call_service(&app, testing::login_request(&koala, other_id).to_request()).await;
+
+        let album =
+            testing::draft_album_with_role(&data.db, koala_id, Some(AlbumRole::Owner)).await;
+        let update_member = |user_id, role: proto::AlbumRole| {
+            test::TestRequest::patch()
+                .uri("/api/v1/album/members")
+                .insert_header((AUTHORIZATION, session_id.as_str()))
+                .set_json(UpdateAlbumMemberRequest {
+                    album_id: album.id.clone(),
+                    user_id,
+                    role: role as i32,
+                })
+                .to_request()
+        };
+
+        let req = update_member(koala_id, proto::AlbumRole::Viewer);
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            StatusCode::BAD_REQUEST
+        );
+
+        let req = test::TestRequest::delete()
+            .uri("/api/v1/album/members")
+            .insert_header((AUTHORIZATION, session_id.as_str()))
+            .set_json(RemoveAlbumMemberRequest {
+                album_id: album.id.clone(),
+                user_id: koala_id,
+            })
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            StatusCode::BAD_REQUEST
+        );
+
+        // Once there is another owner, the user may step down
+        let req = update_member(other_id, proto::AlbumRole::Owner);
+        assert!(test::call_service(&app, req).await.status().is_success());
+        let req = update_member(koala_id, proto::AlbumRole::Viewer);
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        let role = AlbumMember::get_role(&data.db, &album.id, koala_id)
+            .await
+            .unwrap();
+        assert_eq!(role, Some(AlbumRole::Viewer));
+    }
+}
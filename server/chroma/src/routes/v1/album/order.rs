@@ -0,0 +1,76 @@
+use std::collections::HashSet;
+
+use actix_multiresponse::Payload;
+
+use dal::database::{Album, Photo};
+use proto::ReorderAlbumRequest;
+
+use crate::routes::appdata::WebData;
+use crate::routes::authorization::Authorization;
+use crate::routes::empty::Empty;
+use crate::routes::error::{Error, WebResult};
+use crate::routes::scope::Scope;
+
+/// Set the order of the photos in an album.
+/// `photo_ids` must contain every photo in the album exactly once.
+/// If `photo_ids` is empty, the explicit order is removed and the photos
+/// are ordered by the time they were taken again.
+///
+/// # Errors
+///
+/// - If the album does not exist
+/// - If `photo_ids` is not empty and does not contain exactly all photos in the album
+/// - If something went wrong
+pub async fn order(
+    auth: Authorization,
+    data: WebData,
+    payload: Payload<ReorderAlbumRequest>,
+) -> WebResult<Empty> {
+    let album = Album::get_by_id(&data.db, &payload.album_id)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    if !auth.may_access_album(&data.db, &album.id).await? {
+        return Err(Error::Forbidden);
+    }
+
+    if !auth.is_admin
+        && !auth
+            .has_album_scope(&data.db, &album.id, Scope::AlbumUpdate)
+            .await?
+    {
+        return Err(Error::Forbidden);
+    }
+
+    // Only admins may modify published albums.
+    if !album.is_draft && !auth.is_admin {
+        return Err(Error::Forbidden);
+    }
+
+    if !payload.photo_ids.is_empty() {
+        let album_photo_ids = Photo::list_in_album(&data.db, &album.id)
+            .await?
+            .into_iter()
+            .map(|photo| photo.id)
+            .collect::<HashSet<_>>();
+
+        let new_order = payload.photo_ids.iter().cloned().collect::<HashSet<_>>();
+
+        if new_order.len() != payload.photo_ids.len() {
+            return Err(Error::BadRequest(
+                "Provided order contains duplicate photos".into(),
+            ));
+        }
+
+        if new_order.ne(&album_photo_ids) {
+            return Err(Error::BadRequest(format!(
+                "Provided order does not contain exactly all photos in album with ID '{}'",
+                album.id
+            )));
+        }
+    }
+
+    Photo::set_order_in_album(&data.db, &album.id, &payload.photo_ids).await?;
+
+    Ok(Empty)
+}
@@ -0,0 +1,68 @@
+use actix_multiresponse::Payload;
+
+use dal::database::{Album, AlbumMember, AuditAction, AuditLogEntry, AuditTarget};
+use proto::RemoveAlbumMemberRequest;
+
+use crate::routes::appdata::WebData;
+use crate::routes::authorization::Authorization;
+use crate::routes::empty::Empty;
+use crate::routes::error::{Error, WebResult};
+use crate::routes::scope::Scope;
+
+/// Remove a user from an album.
+/// They keep the scopes they hold for all albums.
+/// Removing a user who is not a member has no effect.
+///
+/// # Errors
+///
+/// - If the album does not exist
+/// - If the user is the last owner of the album
+/// - If something went wrong
+pub async fn remove_member(
+    auth: Authorization,
+    data: WebData,
+    payload: Payload<RemoveAlbumMemberRequest>,
+) -> WebResult<Empty> {
+    let album = Album::get_by_id(&data.db, &payload.album_id)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    if !auth.may_access_album(&data.db, &album.id).await? {
+        return Err(Error::Forbidden);
+    }
+
+    if !auth.is_admin
+        && !auth
+            .has_album_scope(&data.db, &album.id, Scope::AlbumUpdate)
+            .await?
+    {
+        return Err(Error::Forbidden);
+    }
+
+    let previous_role = match AlbumMember::get_role(&data.db, &album.id, payload.user_id).await? {
+        Some(v) => v,
+        None => return Ok(Empty),
+    };
+
+    let mut tx = data.db.begin().await?;
+    let owners = AlbumMember::lock_owners(&mut tx, &album.id).await?;
+    if owners == [payload.user_id] {
+        return Err(Error::BadRequest(
+            "The album must keep at least one owner".into(),
+        ));
+    }
+
+    AlbumMember::remove(&mut tx, &album.id, payload.user_id).await?;
+    AuditLogEntry::record(
+        &mut tx,
+        Some(&auth.to_dal_user_type()),
+        AuditAction::AlbumMemberRemoved,
+        AuditTarget::Album(album.id.clone()),
+        Some(format!("{}:{previous_role}", payload.user_id)),
+        None,
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(Empty)
+}
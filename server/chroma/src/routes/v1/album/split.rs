@@ -0,0 +1,109 @@
+use std::collections::HashSet;
+
+use actix_multiresponse::Payload;
+use actix_web::web;
+
+use dal::database::{Album, AlbumSplit, AuditAction, AuditLogEntry, AuditTarget, Photo};
+use proto::split_album_request::SplitBy;
+use proto::{SplitAlbumRequest, SplitAlbumResponse};
+
+use crate::routes::appdata::{AlbumIdCache, WebData};
+use crate::routes::authorization::Authorization;
+use crate::routes::error::{Error, WebResult};
+
+/// Split an album into a new album.
+/// Either a set of photos, or all photos taken at or after a timestamp, are moved to the new album.
+/// The new album is published if the existing album is published.
+///
+/// Only admins may split albums.
+///
+/// # Errors
+///
+/// - If the album does not exist
+/// - If the new name's length is longer than [Album::MAX_NAME_LENGTH]
+/// - If no way to split the album is provided
+/// - If any of the provided photos is not part of the album
+/// - If something went wrong
+pub async fn split(
+    auth: Authorization,
+    data: WebData,
+    album_id_cache: web::Data<AlbumIdCache>,
+    payload: Payload<SplitAlbumRequest>,
+) -> WebResult<Payload<SplitAlbumResponse>> {
+    if !auth.is_admin {
+        return Err(Error::Forbidden);
+    }
+
+    if payload.new_album_name.len() > Album::MAX_NAME_LENGTH {
+        return Err(Error::BadRequest(format!(
+            "Provided value 'newAlbumName' with length '{}' exceeds the maximum length of '{}'",
+            payload.new_album_name.len(),
+            Album::MAX_NAME_LENGTH
+        )));
+    }
+
+    let mut album = Album::get_by_id(&data.db, &payload.album_id)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    let split = match &payload.split_by {
+        Some(SplitBy::Photos(photos)) => {
+            if photos.photo_ids.is_empty() {
+                return Err(Error::BadRequest("No photos provided".into()));
+            }
+
+            let album_photo_ids = Photo::list_in_album(&data.db, &album.id)
+                .await?
+                .into_iter()
+                .map(|photo| photo.id)
+                .collect::<HashSet<_>>();
+
+            if let Some(photo_id) = photos
+                .photo_ids
+                .iter()
+                .find(|photo_id| !album_photo_ids.contains(*photo_id))
+            {
+                return Err(Error::BadRequest(format!(
+                    "Photo with ID '{photo_id}' is not in album with ID '{}'",
+                    album.id
+                )));
+            }
+
+            AlbumSplit::Photos(photos.photo_ids.clone())
+        }
+        Some(SplitBy::TakenFrom(timestamp)) => AlbumSplit::TakenFrom(*timestamp),
+        None => {
+            return Err(Error::BadRequest(
+                "One of 'photos' or 'takenFrom' must be provided".into(),
+            ))
+        }
+    };
+
+    let mut tx = data.db.begin().await?;
+    let new_album = album
+        .split(
+            &payload.new_album_name,
+            split,
+            auth.to_dal_user_type(),
+            &mut tx,
+        )
+        .await?;
+    AuditLogEntry::record(
+        &mut tx,
+        Some(&auth.to_dal_user_type()),
+        AuditAction::AlbumSplit,
+        AuditTarget::Album(album.id.clone()),
+        None,
+        Some(new_album.id.clone()),
+    )
+    .await?;
+    tx.commit().await?;
+
+    let new_album_id = new_album.id.clone();
+    album_id_cache.insert(album.id.clone(), album).await;
+    album_id_cache.insert(new_album_id.clone(), new_album).await;
+
+    Ok(Payload(SplitAlbumResponse {
+        album_id: new_album_id,
+    }))
+}
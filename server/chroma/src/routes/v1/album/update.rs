@@ -1,13 +1,14 @@
 use actix_multiresponse::Payload;
 use actix_web::web;
 
-use dal::database::{Album, Photo};
+use dal::database::{Album, AuditAction, AuditLogEntry, AuditTarget, Photo};
 use proto::UpdateAlbumRequest;
 
 use crate::routes::appdata::{AlbumIdCache, WebData};
 use crate::routes::authorization::Authorization;
 use crate::routes::empty::Empty;
 use crate::routes::error::{Error, WebResult};
+use crate::routes::scope::Scope;
 
 /// Update the metadata of an existing album.
 /// Currently, only the following properties can be updated:
@@ -27,18 +28,22 @@ pub async fn update(
     album_id_cache: web::Data<AlbumIdCache>,
     payload: Payload<UpdateAlbumRequest>,
 ) -> WebResult<Empty> {
+    let mut album = Album::get_by_id(&data.db, &payload.id)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    if !auth.may_access_album(&data.db, &album.id).await? {
+        return Err(Error::Forbidden);
+    }
+
     if !auth.is_admin
         && !auth
-            .has_scope(&data.db, "nl.svsticky.chroma.album.update")
+            .has_album_scope(&data.db, &album.id, Scope::AlbumUpdate)
             .await?
     {
         return Err(Error::Forbidden);
     }
 
-    let mut album = Album::get_by_id(&data.db, &payload.id)
-        .await?
-        .ok_or(Error::NotFound)?;
-
     if !album.is_draft && !auth.is_admin {
         return Err(Error::Forbidden);
     }
@@ -78,20 +83,46 @@ pub async fn update(
             return Err(Error::Forbidden);
         }
 
-        match draft_settings {
+        let was_draft = album.is_draft;
+        let mut tx = data.db.begin().await?;
+        let action = match draft_settings {
             proto::update_album_request::DraftSettings::SetDraft(v) if *v => {
-                album.set_draft(&data.db).await?;
+                album.set_draft(&mut tx).await?;
+                Some(AuditAction::AlbumUnpublished)
             }
             proto::update_album_request::DraftSettings::SetPublished(v) if *v => {
                 album
-                    .set_published(auth.to_dal_user_type(&data.db).await?, &data.db)
+                    .set_published(auth.to_dal_user_type(), &mut tx)
                     .await?;
+                Some(AuditAction::AlbumPublished)
             }
-            _ => {}
+            _ => None,
+        };
+
+        if let Some(action) = action.filter(|_| was_draft != album.is_draft) {
+            AuditLogEntry::record(
+                &mut tx,
+                Some(&auth.to_dal_user_type()),
+                action,
+                AuditTarget::Album(album.id.clone()),
+                Some(draft_state(was_draft).into()),
+                Some(draft_state(album.is_draft).into()),
+            )
+            .await?;
         }
+
+        tx.commit().await?;
     }
 
     album_id_cache.insert(album.id.clone(), album).await;
 
     Ok(Empty)
 }
+
+fn draft_state(is_draft: bool) -> &'static str {
+    if is_draft {
+        "draft"
+    } else {
+        "published"
+    }
+}
@@ -0,0 +1,86 @@
+use actix_multiresponse::Payload;
+
+use dal::database::{Album, AlbumMember, AlbumRole, AuditAction, AuditLogEntry, AuditTarget, User};
+use proto::UpdateAlbumMemberRequest;
+
+use crate::routes::appdata::WebData;
+use crate::routes::authorization::{Authorization, AuthorizedUser};
+use crate::routes::empty::Empty;
+use crate::routes::error::{Error, WebResult};
+use crate::routes::scope::Scope;
+
+/// Give a user a role in an album, replacing their current role if they are a member already.
+/// Admins, users who may update all albums and owners of the album may manage its members.
+///
+/// # Errors
+///
+/// - If the request was made with an API key
+/// - If the album or the user does not exist
+/// - If the role is not known
+/// - If the user is the last owner of the album and would no longer be an owner
+/// - If something went wrong
+pub async fn update_member(
+    auth: Authorization,
+    data: WebData,
+    payload: Payload<UpdateAlbumMemberRequest>,
+) -> WebResult<Empty> {
+    let granted_by = match auth.user {
+        AuthorizedUser::Koala { koala_id, .. } => koala_id,
+        AuthorizedUser::ApiKey { .. } => {
+            return Err(Error::BadRequest(
+                "This endpoint does not support service accounts".into(),
+            ))
+        }
+    };
+
+    let album = Album::get_by_id(&data.db, &payload.album_id)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    if !auth.may_access_album(&data.db, &album.id).await? {
+        return Err(Error::Forbidden);
+    }
+
+    if !auth.is_admin
+        && !auth
+            .has_album_scope(&data.db, &album.id, Scope::AlbumUpdate)
+            .await?
+    {
+        return Err(Error::Forbidden);
+    }
+
+    let role = proto::AlbumRole::from_i32(payload.role)
+        .map(AlbumRole::from_proto)
+        .ok_or_else(|| Error::BadRequest(format!("Unknown album role '{}'", payload.role)))?;
+
+    let member = User::get_by_id(&data.db, payload.user_id)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    let previous_role = AlbumMember::get_role(&data.db, &album.id, member.koala_id).await?;
+    if previous_role == Some(role) {
+        return Ok(Empty);
+    }
+
+    let mut tx = data.db.begin().await?;
+    let owners = AlbumMember::lock_owners(&mut tx, &album.id).await?;
+    if role != AlbumRole::Owner && owners == [member.koala_id] {
+        return Err(Error::BadRequest(
+            "The album must keep at least one owner".into(),
+        ));
+    }
+
+    AlbumMember::set(&mut tx, &album.id, member.koala_id, role, granted_by).await?;
+    AuditLogEntry::record(
+        &mut tx,
+        Some(&auth.to_dal_user_type()),
+        AuditAction::AlbumMemberUpdated,
+        AuditTarget::Album(album.id.clone()),
+        previous_role.map(|role| format!("{}:{role}", member.koala_id)),
+        Some(format!("{}:{role}", member.koala_id)),
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(Empty)
+}
@@ -0,0 +1,355 @@
+use actix_web::web::Bytes;
+use crc32fast::Hasher;
+use thiserror::Error;
+use time::OffsetDateTime;
+
+const LOCAL_FILE_HEADER_SIGNATURE: u32 = 0x04034b50;
+const DATA_DESCRIPTOR_SIGNATURE: u32 = 0x08074b50;
+const CENTRAL_DIRECTORY_HEADER_SIGNATURE: u32 = 0x02014b50;
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x06054b50;
+
+/// ZIP 2.0, the minimum version supporting data descriptors
+const VERSION: u16 = 20;
+/// Bit 3: sizes and checksum are in the data descriptor, bit 11: names are UTF-8
+const FLAGS: u16 = (1 << 3) | (1 << 11);
+/// Entries are stored as-is, photos are already compressed
+const COMPRESSION_STORED: u16 = 0;
+
+/// The sizes of the records, excluding names
+const LOCAL_FILE_HEADER_LEN: u64 = 30;
+const DATA_DESCRIPTOR_LEN: u64 = 16;
+const CENTRAL_DIRECTORY_HEADER_LEN: u64 = 46;
+const END_OF_CENTRAL_DIRECTORY_LEN: u64 = 22;
+
+/// The maximum number of entries in an archive
+pub const MAX_ENTRIES: usize = u16::MAX as usize;
+/// The maximum size of an archive in bytes
+pub const MAX_SIZE: u64 = u32::MAX as u64;
+
+/// The archive does not fit in a ZIP archive without ZIP64, see [ZipWriter].
+#[derive(Debug, Error)]
+#[error("Archive exceeds the limits of ZIP without ZIP64")]
+pub struct ZipLimitExceeded;
+
+/// The size in bytes of an archive holding entries with these names and sizes of contents.
+/// The archive can only be written if it is at most [MAX_SIZE] bytes.
+pub fn archive_size<'a>(entries: impl IntoIterator<Item = (&'a str, u64)>) -> u64 {
+    let entries_size: u64 = entries
+        .into_iter()
+        .map(|(name, size)| {
+            LOCAL_FILE_HEADER_LEN
+                + DATA_DESCRIPTOR_LEN
+                + CENTRAL_DIRECTORY_HEADER_LEN
+                + 2 * name.len() as u64
+                + size
+        })
+        .sum();
+
+    entries_size + END_OF_CENTRAL_DIRECTORY_LEN
+}
+
+/// Writes a ZIP archive piece by piece, so it can be streamed while it is being written.
+/// The size and checksum of an entry are only known after its contents have been written,
+/// they are written in a data descriptor following the contents.
+///
+/// ZIP64 is not supported, so an archive may hold at most [MAX_ENTRIES] entries
+/// and may be at most [MAX_SIZE] bytes. Writing a larger archive fails with [ZipLimitExceeded],
+/// use [archive_size] to check beforehand.
+#[derive(Default)]
+pub struct ZipWriter {
+    offset: u64,
+    current: Option<CurrentEntry>,
+    entries: Vec<FinishedEntry>,
+}
+
+struct CurrentEntry {
+    header: EntryHeader,
+    hasher: Hasher,
+    size: u64,
+}
+
+struct FinishedEntry {
+    header: EntryHeader,
+    crc32: u32,
+    size: u32,
+}
+
+struct EntryHeader {
+    name: String,
+    offset: u32,
+    dos_time: u16,
+    dos_date: u16,
+}
+
+impl ZipWriter {
+    /// Start a new entry.
+    /// Returns the bytes to be written before the contents of the entry.
+    ///
+    /// # Errors
+    ///
+    /// If the entry would start beyond [MAX_SIZE], or its name is too long
+    ///
+    /// # Panics
+    ///
+    /// If the previous entry has not been finished
+    pub fn start_entry(&mut self, name: &str, modified_at: i64) -> Result<Bytes, ZipLimitExceeded> {
+        assert!(self.current.is_none(), "Previous entry was not finished");
+
+        let (dos_time, dos_date) = dos_date_time(modified_at);
+        let header = EntryHeader {
+            name: name.to_string(),
+            offset: to_u32(self.offset)?,
+            dos_time,
+            dos_date,
+        };
+
+        let mut buf = Vec::with_capacity(LOCAL_FILE_HEADER_LEN as usize + name.len());
+        put_u32(&mut buf, LOCAL_FILE_HEADER_SIGNATURE);
+        put_u16(&mut buf, VERSION);
+        put_u16(&mut buf, FLAGS);
+        put_u16(&mut buf, COMPRESSION_STORED);
+        put_u16(&mut buf, header.dos_time);
+        put_u16(&mut buf, header.dos_date);
+        // Checksum and sizes, these are provided in the data descriptor
+        put_u32(&mut buf, 0);
+        put_u32(&mut buf, 0);
+        put_u32(&mut buf, 0);
+        put_u16(&mut buf, to_u16(name.len())?);
+        // Extra field length
+        put_u16(&mut buf, 0);
+        buf.extend_from_slice(name.as_bytes());
+
+        self.current = Some(CurrentEntry {
+            header,
+            hasher: Hasher::new(),
+            size: 0,
+        });
+
+        Ok(self.advance(buf))
+    }
+
+    /// Register a chunk of the contents of the current entry.
+    /// The chunk itself should be written by the caller.
+    ///
+    /// # Panics
+    ///
+    /// If no entry was started
+    pub fn write(&mut self, chunk: &[u8]) {
+        let current = self.current.as_mut().expect("No entry was started");
+        current.hasher.update(chunk);
+        current.size += chunk.len() as u64;
+        self.offset += chunk.len() as u64;
+    }
+
+    /// Finish the current entry.
+    /// Returns the bytes to be written after the contents of the entry.
+    ///
+    /// # Errors
+    ///
+    /// If the contents of the entry are larger than [MAX_SIZE]
+    ///
+    /// # Panics
+    ///
+    /// If no entry was started
+    pub fn finish_entry(&mut self) -> Result<Bytes, ZipLimitExceeded> {
+        let current = self.current.take().expect("No entry was started");
+        let entry = FinishedEntry {
+            header: current.header,
+            crc32: current.hasher.finalize(),
+            size: to_u32(current.size)?,
+        };
+
+        let mut buf = Vec::with_capacity(DATA_DESCRIPTOR_LEN as usize);
+        put_u32(&mut buf, DATA_DESCRIPTOR_SIGNATURE);
+        put_u32(&mut buf, entry.crc32);
+        put_u32(&mut buf, entry.size);
+        put_u32(&mut buf, entry.size);
+
+        self.entries.push(entry);
+        Ok(self.advance(buf))
+    }
+
+    /// Finish the archive.
+    /// Returns the bytes to be written after all entries.
+    ///
+    /// # Errors
+    ///
+    /// If the archive would be larger than [MAX_SIZE], or has more than [MAX_ENTRIES] entries
+    pub fn finish(mut self) -> Result<Bytes, ZipLimitExceeded> {
+        let central_directory_offset = to_u32(self.offset)?;
+        let entry_count = to_u16(self.entries.len())?;
+
+        let mut buf = Vec::new();
+        for entry in &self.entries {
+            put_u32(&mut buf, CENTRAL_DIRECTORY_HEADER_SIGNATURE);
+            // Version made by and version needed
+            put_u16(&mut buf, VERSION);
+            put_u16(&mut buf, VERSION);
+            put_u16(&mut buf, FLAGS);
+            put_u16(&mut buf, COMPRESSION_STORED);
+            put_u16(&mut buf, entry.header.dos_time);
+            put_u16(&mut buf, entry.header.dos_date);
+            put_u32(&mut buf, entry.crc32);
+            put_u32(&mut buf, entry.size);
+            put_u32(&mut buf, entry.size);
+            put_u16(&mut buf, to_u16(entry.header.name.len())?);
+            // Extra field length, comment length, disk number, internal and external attributes
+            put_u16(&mut buf, 0);
+            put_u16(&mut buf, 0);
+            put_u16(&mut buf, 0);
+            put_u16(&mut buf, 0);
+            put_u32(&mut buf, 0);
+            put_u32(&mut buf, entry.header.offset);
+            buf.extend_from_slice(entry.header.name.as_bytes());
+        }
+
+        let central_directory_size = to_u32(buf.len() as u64)?;
+
+        // The archive must end within the limit as well
+        to_u32(self.offset + buf.len() as u64 + END_OF_CENTRAL_DIRECTORY_LEN)?;
+
+        put_u32(&mut buf, END_OF_CENTRAL_DIRECTORY_SIGNATURE);
+        // Number of this disk and the disk with the central directory
+        put_u16(&mut buf, 0);
+        put_u16(&mut buf, 0);
+        put_u16(&mut buf, entry_count);
+        put_u16(&mut buf, entry_count);
+        put_u32(&mut buf, central_directory_size);
+        put_u32(&mut buf, central_directory_offset);
+        // Comment length
+        put_u16(&mut buf, 0);
+
+        Ok(self.advance(buf))
+    }
+
+    fn advance(&mut self, buf: Vec<u8>) -> Bytes {
+        self.offset += buf.len() as u64;
+        Bytes::from(buf)
+    }
+}
+
+fn to_u16(value: usize) -> Result<u16, ZipLimitExceeded> {
+    u16::try_from(value).map_err(|_| ZipLimitExceeded)
+}
+
+fn to_u32(value: u64) -> Result<u32, ZipLimitExceeded> {
+    u32::try_from(value).map_err(|_| ZipLimitExceeded)
+}
+
+fn put_u16(buf: &mut Vec<u8>, value: u16) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(buf: &mut Vec<u8>, value: u32) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+/// Convert a UNIX timestamp to an MS-DOS time and date.
+/// MS-DOS dates cannot represent anything before 1980, such timestamps are clamped.
+fn dos_date_time(timestamp: i64) -> (u16, u16) {
+    let datetime =
+        OffsetDateTime::from_unix_timestamp(timestamp).unwrap_or(OffsetDateTime::UNIX_EPOCH);
+
+    if datetime.year() < 1980 {
+        // 1980-01-01 00:00:00
+        return (0, (1 << 5) | 1);
+    }
+
+    let time = (datetime.hour() as u16) << 11
+        | (datetime.minute() as u16) << 5
+        | (datetime.second() / 2) as u16;
+    let date = ((datetime.year() - 1980) as u16) << 9
+        | (u8::from(datetime.month()) as u16) << 5
+        | datetime.day() as u16;
+
+    (time, date)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Read};
+
+    use super::{archive_size, ZipWriter, MAX_SIZE};
+
+    /// Write an archive holding the entries, like an album export does
+    fn write_archive(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut zip = ZipWriter::default();
+        let mut archive = Vec::new();
+
+        for (name, contents) in entries {
+            archive.extend_from_slice(&zip.start_entry(name, 1_700_000_000).unwrap());
+            // Contents arrive in chunks
+            for chunk in contents.chunks(3) {
+                zip.write(chunk);
+                archive.extend_from_slice(chunk);
+            }
+            archive.extend_from_slice(&zip.finish_entry().unwrap());
+        }
+
+        archive.extend_from_slice(&zip.finish().unwrap());
+        archive
+    }
+
+    #[test]
+    fn archive_can_be_read() {
+        let entries: [(&str, &[u8]); 3] = [
+            ("0001_first.webp", b"first photo"),
+            ("0002_empty.webp", b""),
+            ("0003_naïve.webp", b"third photo, with a longer body"),
+        ];
+        let archive = write_archive(&entries);
+
+        let mut reader = ::zip::ZipArchive::new(Cursor::new(archive)).unwrap();
+        assert_eq!(reader.len(), entries.len());
+
+        for (idx, (name, contents)) in entries.iter().enumerate() {
+            let mut file = reader.by_index(idx).unwrap();
+            assert_eq!(file.name(), *name);
+            assert_eq!(file.size(), contents.len() as u64);
+
+            // Reading verifies the checksum
+            let mut read = Vec::new();
+            file.read_to_end(&mut read).unwrap();
+            assert_eq!(read, *contents);
+        }
+    }
+
+    #[test]
+    fn empty_archive_can_be_read() {
+        let archive = write_archive(&[]);
+        let reader = ::zip::ZipArchive::new(Cursor::new(archive)).unwrap();
+        assert_eq!(reader.len(), 0);
+    }
+
+    #[test]
+    fn archive_size_is_exact() {
+        let entries: [(&str, &[u8]); 2] = [("0001_a.webp", b"abc"), ("0002_ü.webp", b"defgh")];
+        let archive = write_archive(&entries);
+
+        let size = archive_size(
+            entries
+                .iter()
+                .map(|(name, contents)| (*name, contents.len() as u64)),
+        );
+        assert_eq!(size, archive.len() as u64);
+        assert_eq!(archive_size([]), 22);
+    }
+
+    #[test]
+    fn entries_beyond_limit_are_refused() {
+        let mut zip = ZipWriter::default();
+        zip.offset = MAX_SIZE + 1;
+        assert!(zip.start_entry("0001_a.webp", 0).is_err());
+
+        let mut zip = ZipWriter::default();
+        zip.start_entry("0001_a.webp", 0).unwrap();
+        zip.current.as_mut().unwrap().size = MAX_SIZE + 1;
+        assert!(zip.finish_entry().is_err());
+
+        let mut zip = ZipWriter::default();
+        zip.start_entry("0001_a.webp", 0).unwrap();
+        zip.finish_entry().unwrap();
+        zip.offset = MAX_SIZE - 10;
+        assert!(zip.finish().is_err());
+    }
+}
@@ -0,0 +1,93 @@
+use actix_multiresponse::Payload;
+use time::OffsetDateTime;
+
+use dal::database::{ApiKey, AuditAction, AuditLogEntry, AuditTarget};
+use proto::{CreateApiKeyRequest, CreateApiKeyResponse};
+
+use crate::routes::appdata::WebData;
+use crate::routes::authorization::{Authorization, AuthorizedUser};
+use crate::routes::error::{Error, WebResult};
+use crate::routes::v1::api_key::{validate_bindings, MAX_DESCRIPTION_LEN};
+
+/// Create a new API key.
+/// Non-admin keys may only use their scopes, and may be restricted further to specific albums.
+/// The key itself is only returned in this response, it cannot be retrieved afterwards.
+///
+/// # Errors
+///
+/// - If the user is not an admin
+/// - If the request was made with an API key
+/// - If the description is empty or too long
+/// - If the expiry date is in the past
+/// - If a scope is unknown or an album does not exist
+/// - If an admin key is bound to albums
+/// - If something went wrong
+pub async fn create(
+    auth: Authorization,
+    data: WebData,
+    payload: Payload<CreateApiKeyRequest>,
+) -> WebResult<Payload<CreateApiKeyResponse>> {
+    if !auth.is_admin {
+        return Err(Error::Forbidden);
+    }
+
+    let created_by = match auth.user {
+        AuthorizedUser::Koala { koala_id, .. } => koala_id,
+        AuthorizedUser::ApiKey { .. } => {
+            return Err(Error::BadRequest(
+                "This endpoint does not support service accounts".into(),
+            ))
+        }
+    };
+
+    if payload.description.trim().is_empty() {
+        return Err(Error::BadRequest("Description may not be empty".into()));
+    }
+
+    if payload.description.len() > MAX_DESCRIPTION_LEN {
+        return Err(Error::BadRequest(format!(
+            "Description may not be longer than {MAX_DESCRIPTION_LEN} characters"
+        )));
+    }
+
+    if let Some(expires_at) = payload.expires_at {
+        if expires_at <= OffsetDateTime::now_utc().unix_timestamp() {
+            return Err(Error::BadRequest("Expiry date is in the past".into()));
+        }
+    }
+
+    if payload.is_admin && !payload.album_ids.is_empty() {
+        return Err(Error::BadRequest(
+            "Admin keys cannot be bound to albums".into(),
+        ));
+    }
+
+    validate_bindings(&data, &payload.scopes, &payload.album_ids).await?;
+
+    let mut tx = data.db.begin().await?;
+    let (api_key, key) = ApiKey::create(
+        &mut tx,
+        &payload.description,
+        payload.is_admin,
+        created_by,
+        payload.expires_at,
+        &payload.scopes,
+        &payload.album_ids,
+    )
+    .await?;
+    AuditLogEntry::record(
+        &mut tx,
+        Some(&auth.to_dal_user_type()),
+        AuditAction::ApiKeyCreated,
+        AuditTarget::ApiKey(api_key.id),
+        None,
+        Some(payload.scopes.join(",")),
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(Payload(CreateApiKeyResponse {
+        api_key: Some(api_key.to_proto(&data.db).await?),
+        key,
+    }))
+}
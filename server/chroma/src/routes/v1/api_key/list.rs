@@ -0,0 +1,27 @@
+use actix_multiresponse::Payload;
+
+use dal::database::ApiKey;
+use proto::ListApiKeysResponse;
+
+use crate::routes::appdata::WebData;
+use crate::routes::authorization::Authorization;
+use crate::routes::error::{Error, WebResult};
+
+/// List all API keys, including those which have expired or have been revoked.
+///
+/// # Errors
+///
+/// - If the user is not an admin
+/// - If something went wrong
+pub async fn list(auth: Authorization, data: WebData) -> WebResult<Payload<ListApiKeysResponse>> {
+    if !auth.is_admin {
+        return Err(Error::Forbidden);
+    }
+
+    let mut api_keys = Vec::new();
+    for api_key in ApiKey::list(&data.db).await? {
+        api_keys.push(api_key.to_proto(&data.db).await?);
+    }
+
+    Ok(Payload(ListApiKeysResponse { api_keys }))
+}
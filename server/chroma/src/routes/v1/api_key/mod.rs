@@ -0,0 +1,154 @@
+use actix_web::web;
+use actix_web::web::ServiceConfig;
+
+use dal::database::Album;
+
+use crate::routes::appdata::WebData;
+use crate::routes::error::{Error, WebResult};
+use crate::routes::routable::Routable;
+use crate::routes::scope::Scope;
+
+mod create;
+mod list;
+mod revoke;
+mod update;
+
+/// The maximum length of the description of an API key
+const MAX_DESCRIPTION_LEN: usize = 128;
+
+pub struct Router;
+
+impl Routable for Router {
+    fn configure(config: &mut ServiceConfig) {
+        config.service(
+            web::scope("/api-key")
+                .route("", web::post().to(create::create))
+                .route("", web::patch().to(update::update))
+                .route("", web::delete().to(revoke::revoke))
+                .route("/list", web::get().to(list::list)),
+        );
+    }
+}
+
+/// Check that all scopes are valid grants and all albums exist.
+async fn validate_bindings(
+    data: &WebData,
+    scopes: &[String],
+    album_ids: &[String],
+) -> WebResult<()> {
+    Scope::check_grants(scopes)?;
+
+    for album_id in album_ids {
+        if Album::get_by_id(&data.db, album_id).await?.is_none() {
+            return Err(Error::BadRequest(format!(
+                "Album with ID '{album_id}' does not exist"
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::header::AUTHORIZATION;
+    use actix_web::http::StatusCode;
+    use actix_web::{test, App};
+    use mock_koala::MockUser;
+
+    use dal::database::{AlbumRole, ApiKey};
+    use proto::{RevokeApiKeyRequest, UpdateApiKeyRequest};
+
+    use crate::testing;
+
+    #[actix_web::test]
+    #[ignore = "requires the database and S3 from docker-compose.yml"]
+    async fn api_keys_cannot_manage_keys() {
+        let koala_id = testing::random_koala_id();
+        let koala = testing::start_koala(vec![MockUser::new(koala_id, "Jan", "Jansen")]).await;
+        let data = testing::app_data(&koala).await;
+        let app = test::init_service(App::new().configure(testing::configure(data.clone()))).await;
+
+        let mut tx = data.db.begin().await.unwrap();
+        let (_, admin_key) = ApiKey::create(&mut tx, "Admin", true, koala_id, None, &[], &[])
+            .await
+            .unwrap();
+        let (target, _) = ApiKey::create(&mut tx, "Target", false, koala_id, None, &[], &[])
+            .await
+            .unwrap();
+        tx.commit().await.unwrap();
+        let authorization = format!("Service {admin_key}");
+
+        let req = test::TestRequest::patch()
+            .uri("/api/v1/api-key")
+            .insert_header((AUTHORIZATION, authorization.as_str()))
+            .set_json(UpdateApiKeyRequest {
+                id: target.id,
+                ..Default::default()
+            })
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            StatusCode::BAD_REQUEST
+        );
+
+        let req = test::TestRequest::delete()
+            .uri("/api/v1/api-key")
+            .insert_header((AUTHORIZATION, authorization.as_str()))
+            .set_json(RevokeApiKeyRequest { id: target.id })
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            StatusCode::BAD_REQUEST
+        );
+
+        let target = ApiKey::get_by_id(&data.db, target.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(target.is_active());
+    }
+
+    #[actix_web::test]
+    #[ignore = "requires the database and S3 from docker-compose.yml"]
+    async fn update_without_albums_keeps_bindings() {
+        let koala_id = testing::random_koala_id();
+        let koala =
+            testing::start_koala(vec![MockUser::new(koala_id, "Jan", "Jansen").admin()]).await;
+        let data = testing::app_data(&koala).await;
+        let app = test::init_service(App::new().configure(testing::configure(data.clone()))).await;
+        let session_id = testing::session_id(
+            &test::call_service(&app, testing::login_request(&koala, koala_id).to_request()).await,
+        );
+
+        let album =
+            testing::draft_album_with_role(&data.db, koala_id, Some(AlbumRole::Owner)).await;
+        let mut tx = data.db.begin().await.unwrap();
+        let (api_key, _) = ApiKey::create(
+            &mut tx,
+            "Bound",
+            false,
+            koala_id,
+            None,
+            &[],
+            &[album.id.clone()],
+        )
+        .await
+        .unwrap();
+        tx.commit().await.unwrap();
+
+        let req = test::TestRequest::patch()
+            .uri("/api/v1/api-key")
+            .insert_header((AUTHORIZATION, session_id.as_str()))
+            .set_json(UpdateApiKeyRequest {
+                id: api_key.id,
+                scopes: Vec::new(),
+                albums: None,
+            })
+            .to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        let albums = api_key.list_albums(&data.db).await.unwrap();
+        assert_eq!(albums, vec![album.id]);
+    }
+}
@@ -0,0 +1,57 @@
+use actix_multiresponse::Payload;
+use actix_web::web;
+
+use dal::database::{ApiKey, AuditAction, AuditLogEntry, AuditTarget};
+use proto::RevokeApiKeyRequest;
+
+use crate::routes::appdata::{SessionIdCache, WebData};
+use crate::routes::authorization::{invalidate_cached_api_key, Authorization, AuthorizedUser};
+use crate::routes::empty::Empty;
+use crate::routes::error::{Error, WebResult};
+
+/// Revoke an API key, it can no longer be used afterwards.
+/// Revoked keys are kept, so albums created with them can still be attributed.
+///
+/// # Errors
+///
+/// - If the user is not an admin
+/// - If the request was made with an API key
+/// - If the key does not exist
+/// - If something went wrong
+pub async fn revoke(
+    auth: Authorization,
+    data: WebData,
+    session_cache: web::Data<SessionIdCache>,
+    payload: Payload<RevokeApiKeyRequest>,
+) -> WebResult<Empty> {
+    if !auth.is_admin {
+        return Err(Error::Forbidden);
+    }
+
+    if let AuthorizedUser::ApiKey { .. } = auth.user {
+        return Err(Error::BadRequest(
+            "This endpoint does not support service accounts".into(),
+        ));
+    }
+
+    let mut api_key = ApiKey::get_by_id(&data.db, payload.id)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    let mut tx = data.db.begin().await?;
+    api_key.revoke(&mut tx).await?;
+    AuditLogEntry::record(
+        &mut tx,
+        Some(&auth.to_dal_user_type()),
+        AuditAction::ApiKeyRevoked,
+        AuditTarget::ApiKey(api_key.id),
+        None,
+        None,
+    )
+    .await?;
+    tx.commit().await?;
+
+    invalidate_cached_api_key(&session_cache, api_key.id);
+
+    Ok(Empty)
+}
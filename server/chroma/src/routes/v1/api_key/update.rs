@@ -0,0 +1,79 @@
+use actix_multiresponse::Payload;
+use actix_web::web;
+
+use dal::database::{ApiKey, AuditAction, AuditLogEntry, AuditTarget};
+use proto::UpdateApiKeyRequest;
+
+use crate::routes::appdata::{SessionIdCache, WebData};
+use crate::routes::authorization::{invalidate_cached_api_key, Authorization, AuthorizedUser};
+use crate::routes::empty::Empty;
+use crate::routes::error::{Error, WebResult};
+use crate::routes::v1::api_key::validate_bindings;
+
+/// Replace the scopes of an API key, and the albums it is bound to if they are set.
+/// The changes apply to the next request made with the key.
+///
+/// # Errors
+///
+/// - If the user is not an admin
+/// - If the request was made with an API key
+/// - If the key does not exist
+/// - If a scope is unknown or an album does not exist
+/// - If an admin key is bound to albums
+/// - If something went wrong
+pub async fn update(
+    auth: Authorization,
+    data: WebData,
+    session_cache: web::Data<SessionIdCache>,
+    payload: Payload<UpdateApiKeyRequest>,
+) -> WebResult<Empty> {
+    if !auth.is_admin {
+        return Err(Error::Forbidden);
+    }
+
+    if let AuthorizedUser::ApiKey { .. } = auth.user {
+        return Err(Error::BadRequest(
+            "This endpoint does not support service accounts".into(),
+        ));
+    }
+
+    let mut api_key = ApiKey::get_by_id(&data.db, payload.id)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    let album_ids = payload.albums.as_ref().map(|albums| &albums.album_ids);
+    if api_key.is_admin && album_ids.map(|ids| !ids.is_empty()).unwrap_or(false) {
+        return Err(Error::BadRequest(
+            "Admin keys cannot be bound to albums".into(),
+        ));
+    }
+
+    validate_bindings(
+        &data,
+        &payload.scopes,
+        album_ids.map(Vec::as_slice).unwrap_or(&[]),
+    )
+    .await?;
+
+    let previous_scopes = api_key.list_scopes(&data.db).await?;
+
+    let mut tx = data.db.begin().await?;
+    api_key.set_scopes(&mut tx, &payload.scopes).await?;
+    if let Some(album_ids) = album_ids {
+        api_key.set_albums(&mut tx, album_ids).await?;
+    }
+    AuditLogEntry::record(
+        &mut tx,
+        Some(&auth.to_dal_user_type()),
+        AuditAction::ApiKeyUpdated,
+        AuditTarget::ApiKey(api_key.id),
+        Some(previous_scopes.join(",")),
+        Some(payload.scopes.join(",")),
+    )
+    .await?;
+    tx.commit().await?;
+
+    invalidate_cached_api_key(&session_cache, api_key.id);
+
+    Ok(Empty)
+}
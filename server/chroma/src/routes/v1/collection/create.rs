@@ -0,0 +1,49 @@
+use actix_multiresponse::Payload;
+
+use dal::database::AlbumCollection;
+use proto::{CreateCollectionRequest, CreateCollectionResponse};
+
+use crate::routes::appdata::WebData;
+use crate::routes::authorization::Authorization;
+use crate::routes::error::{Error, WebResult};
+use crate::routes::scope::Scope;
+
+/// Create a new, empty, album collection.
+/// If a `parent_id` is provided, the collection is created inside that collection.
+///
+/// # Errors
+///
+/// - If the provided `name`'s length is longer than [AlbumCollection::MAX_NAME_LENGTH]
+/// - If the provided parent collection does not exist
+/// - If something went wrong
+pub async fn create(
+    auth: Authorization,
+    data: WebData,
+    payload: Payload<CreateCollectionRequest>,
+) -> WebResult<Payload<CreateCollectionResponse>> {
+    if !auth.is_admin && !auth.has_scope(Scope::CollectionCreate) {
+        return Err(Error::Forbidden);
+    }
+
+    if payload.name.len() > AlbumCollection::MAX_NAME_LENGTH {
+        return Err(Error::BadRequest(format!(
+            "Provided value 'name' with length '{}' exceeds the maximum length of '{}'",
+            payload.name.len(),
+            AlbumCollection::MAX_NAME_LENGTH
+        )));
+    }
+
+    let parent = match &payload.parent_id {
+        Some(parent_id) => Some(
+            AlbumCollection::get_by_id(&data.db, parent_id)
+                .await?
+                .ok_or(Error::BadRequest(format!(
+                    "Parent collection with ID '{parent_id}' does not exist"
+                )))?,
+        ),
+        None => None,
+    };
+
+    let collection = AlbumCollection::create(&data.db, &payload.name, parent.as_ref()).await?;
+    Ok(Payload(CreateCollectionResponse { id: collection.id }))
+}
@@ -0,0 +1,49 @@
+use actix_multiresponse::Payload;
+
+use dal::database::{AlbumCollection, AuditAction, AuditLogEntry, AuditTarget};
+use proto::DeleteCollectionRequest;
+
+use crate::routes::appdata::WebData;
+use crate::routes::authorization::Authorization;
+use crate::routes::empty::Empty;
+use crate::routes::error::{Error, WebResult};
+use crate::routes::scope::Scope;
+
+/// Delete an album collection.
+/// The albums in the collection are *not* deleted.
+/// Collections inside the deleted collection are moved up one level.
+///
+/// # Errors
+///
+/// - If the provided `id` does not correspond to any known collection
+/// - If something went wrong
+pub async fn delete(
+    auth: Authorization,
+    data: WebData,
+    payload: Payload<DeleteCollectionRequest>,
+) -> WebResult<Empty> {
+    if !auth.is_admin && !auth.has_scope(Scope::CollectionDelete) {
+        return Err(Error::Forbidden);
+    }
+
+    let collection = AlbumCollection::get_by_id(&data.db, &payload.id)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    let mut tx = data.db.begin().await?;
+    let id = collection.id.clone();
+    let name = collection.name.clone();
+    collection.delete(&mut tx).await?;
+    AuditLogEntry::record(
+        &mut tx,
+        Some(&auth.to_dal_user_type()),
+        AuditAction::CollectionDeleted,
+        AuditTarget::Collection(id),
+        Some(name),
+        None,
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(Empty)
+}
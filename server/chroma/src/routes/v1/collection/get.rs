@@ -0,0 +1,45 @@
+use actix_multiresponse::Payload;
+use actix_web::web;
+use serde::Deserialize;
+
+use dal::database::AlbumCollection;
+use proto::GetCollectionResponse;
+
+use crate::routes::appdata::WebData;
+use crate::routes::authorization::Authorization;
+use crate::routes::error::{Error, WebResult};
+
+#[derive(Debug, Deserialize)]
+pub struct Query {
+    /// The ID of the collection to retrieve
+    id: String,
+}
+
+/// Retrieve an album collection, the collections directly inside it
+/// and the IDs of the albums in it.
+///
+/// # Errors
+///
+/// - If the requested collection does not exist
+/// - If something went wrong
+pub async fn get(
+    _: Authorization,
+    data: WebData,
+    query: web::Query<Query>,
+) -> WebResult<Payload<GetCollectionResponse>> {
+    let collection = AlbumCollection::get_by_id(&data.db, &query.id)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    let children = AlbumCollection::list(&data.db, Some(collection.id.as_str())).await?;
+    let album_ids = collection.list_album_ids(&data.db).await?;
+
+    Ok(Payload(GetCollectionResponse {
+        collection: Some(collection.to_proto()),
+        children: children
+            .into_iter()
+            .map(AlbumCollection::to_proto)
+            .collect::<Vec<_>>(),
+        album_ids,
+    }))
+}
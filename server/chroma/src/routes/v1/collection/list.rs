@@ -0,0 +1,39 @@
+use actix_multiresponse::Payload;
+use actix_web::web;
+use serde::Deserialize;
+
+use dal::database::AlbumCollection;
+use proto::ListCollectionsResponse;
+
+use crate::routes::appdata::WebData;
+use crate::routes::authorization::Authorization;
+use crate::routes::error::WebResult;
+
+#[derive(Debug, Deserialize)]
+pub struct Query {
+    /// List the collections inside this collection.
+    /// If not provided, the top-level collections are listed.
+    parent_id: Option<String>,
+}
+
+/// List album collections.
+/// If the `parent_id` provided does not correspond to any known collection,
+/// an empty set will be returned.
+///
+/// # Errors
+///
+/// - If something went wrong
+pub async fn list(
+    _: Authorization,
+    data: WebData,
+    query: web::Query<Query>,
+) -> WebResult<Payload<ListCollectionsResponse>> {
+    let collections = AlbumCollection::list(&data.db, query.parent_id.as_deref()).await?;
+
+    Ok(Payload(ListCollectionsResponse {
+        collections: collections
+            .into_iter()
+            .map(AlbumCollection::to_proto)
+            .collect::<Vec<_>>(),
+    }))
+}
@@ -0,0 +1,75 @@
+use actix_web::web;
+use actix_web::web::ServiceConfig;
+
+use crate::routes::routable::Routable;
+
+mod create;
+mod delete;
+mod get;
+mod list;
+mod update;
+
+pub struct Router;
+
+impl Routable for Router {
+    fn configure(config: &mut ServiceConfig) {
+        config.service(
+            web::scope("/collection")
+                .route("", web::post().to(create::create))
+                .route("", web::delete().to(delete::delete))
+                .route("", web::get().to(get::get))
+                .route("/list", web::get().to(list::list))
+                .route("", web::patch().to(update::update)),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::header::AUTHORIZATION;
+    use actix_web::http::StatusCode;
+    use actix_web::{test, App};
+    use mock_koala::MockUser;
+
+    use dal::database::AlbumCollection;
+    use proto::UpdateCollectionRequest;
+
+    use crate::testing;
+
+    #[actix_web::test]
+    #[ignore = "requires the database and S3 from docker-compose.yml"]
+    async fn rejected_update_changes_nothing() {
+        let koala_id = testing::random_koala_id();
+        let koala =
+            testing::start_koala(vec![MockUser::new(koala_id, "Jan", "Jansen").admin()]).await;
+        let data = testing::app_data(&koala).await;
+        let app = test::init_service(App::new().configure(testing::configure(data.clone()))).await;
+        let session_id = testing::session_id(
+            &test::call_service(&app, testing::login_request(&koala, koala_id).to_request()).await,
+        );
+
+        let collection = AlbumCollection::create(&data.db, "Original", None)
+            .await
+            .unwrap();
+
+        // The name is valid, the album is not
+        let req = test::TestRequest::patch()
+            .uri("/api/v1/collection")
+            .insert_header((AUTHORIZATION, session_id.as_str()))
+            .set_json(UpdateCollectionRequest {
+                id: collection.id.clone(),
+                name: Some("Renamed".into()),
+                add_album_ids: vec!["AL_does_not_exist".into()],
+                ..Default::default()
+            })
+            .to_request();
+        let status = test::call_service(&app, req).await.status();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let collection = AlbumCollection::get_by_id(&data.db, &collection.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(collection.name, "Original");
+    }
+}
@@ -0,0 +1,116 @@
+use actix_multiresponse::Payload;
+
+use dal::database::{Album, AlbumCollection};
+use proto::update_collection_request::ParentSettings;
+use proto::UpdateCollectionRequest;
+
+use crate::routes::appdata::WebData;
+use crate::routes::authorization::Authorization;
+use crate::routes::empty::Empty;
+use crate::routes::error::{Error, WebResult};
+use crate::routes::scope::Scope;
+
+/// Update an existing album collection.
+/// The following can be updated:
+/// - The name
+/// - The parent collection
+/// - The albums in the collection
+///
+/// # Errors
+///
+/// - If the new name's length is longer than [AlbumCollection::MAX_NAME_LENGTH]
+/// - If the collection to be updated could not be found
+/// - If the new parent collection does not exist
+/// - If the new parent collection is the collection itself, or a collection inside it
+/// - If any of the albums to be added does not exist
+/// - If something went wrong
+pub async fn update(
+    auth: Authorization,
+    data: WebData,
+    payload: Payload<UpdateCollectionRequest>,
+) -> WebResult<Empty> {
+    if !auth.is_admin && !auth.has_scope(Scope::CollectionUpdate) {
+        return Err(Error::Forbidden);
+    }
+
+    let mut collection = AlbumCollection::get_by_id(&data.db, &payload.id)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    // Check everything before changing anything, so the update is not applied partially
+    for album_id in payload
+        .add_album_ids
+        .iter()
+        .chain(&payload.remove_album_ids)
+    {
+        if !auth.may_access_album(&data.db, album_id).await? {
+            return Err(Error::Forbidden);
+        }
+    }
+
+    if let Some(name) = &payload.name {
+        if name.len() > AlbumCollection::MAX_NAME_LENGTH {
+            return Err(Error::BadRequest(format!(
+                "Provided value 'name' with length '{}' exceeds the maximum length of '{}'",
+                name.len(),
+                AlbumCollection::MAX_NAME_LENGTH
+            )));
+        }
+    }
+
+    let parent = match &payload.parent_settings {
+        Some(ParentSettings::SetTopLevel(v)) if *v => Some(None),
+        Some(ParentSettings::SetParentId(parent_id)) => {
+            let parent = AlbumCollection::get_by_id(&data.db, parent_id)
+                .await?
+                .ok_or(Error::BadRequest(format!(
+                    "Parent collection with ID '{parent_id}' does not exist"
+                )))?;
+
+            // A collection may not end up inside itself
+            if parent
+                .list_ancestor_ids(&data.db)
+                .await?
+                .contains(&collection.id)
+            {
+                return Err(Error::BadRequest(format!(
+                    "Collection with ID '{parent_id}' is inside collection with ID '{}'",
+                    collection.id
+                )));
+            }
+
+            Some(Some(parent))
+        }
+        _ => None,
+    };
+
+    for album_id in &payload.add_album_ids {
+        if Album::get_by_id(&data.db, album_id).await?.is_none() {
+            return Err(Error::BadRequest(format!(
+                "Album with ID '{album_id}' does not exist"
+            )));
+        }
+    }
+
+    let mut tx = data.db.begin().await?;
+
+    if let Some(name) = &payload.name {
+        collection.update_name(&mut tx, name).await?;
+    }
+
+    if let Some(parent) = parent {
+        collection.set_parent(&mut tx, parent.as_ref()).await?;
+    }
+
+    for album_id in &payload.add_album_ids {
+        collection.add_album(&mut tx, album_id).await?;
+    }
+
+    for album_id in &payload.remove_album_ids {
+        collection.remove_album(&mut tx, album_id).await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(Empty)
+}
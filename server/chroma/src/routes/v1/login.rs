@@ -1,87 +1,341 @@
-use actix_web::web;
+use actix_web::http::header::USER_AGENT;
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpRequest};
 use serde::Deserialize;
 use tracing::trace;
 
-use dal::database::{OAuthAccess, User};
+use dal::database::{ExternalIdentity, LoginAttempt, OAuthAccess, User};
 
-use crate::routes::appdata::WebData;
+use crate::identity::{PendingLogin, UserInfo};
+use crate::routes::appdata::{SessionIdCache, WebData};
+use crate::routes::authorization::invalidate_cached_user;
 use crate::routes::error::{Error, WebResult};
+use crate::routes::group_sync;
 use crate::routes::redirect::Redirect;
 
+/// The maximum number of characters of the user agent stored with a session
+const MAX_USER_AGENT_LEN: usize = 256;
+
 #[derive(Debug, Deserialize)]
 pub struct Query {
-    /// The OAuth code generated by Koala
-    code: String,
+    /// The OAuth code generated by the identity provider.
+    /// If absent, a new login is started.
+    code: Option<String>,
+    /// The state the login was started with
+    state: Option<String>,
 }
 
-/// Complete the Koala login flow.
-/// The provided code must be generated by Koala.
+/// Start or complete the login flow with the identity provider.
+/// Without a code, the user is redirected to the identity provider to log in,
+/// which redirects back here with a code.
 ///
-/// Creates a session for the requesting user.
+/// Creates a session for the requesting user,
+/// and syncs the grants they receive through their groups.
 ///
 /// # Errors
 ///
 /// - If the provided `code` isn't valid
+/// - If the login was not started by chroma, or was started too long ago
+/// - If the ID token returned by the identity provider isn't valid
 /// - If something went wrong
-pub async fn login(data: WebData, query: web::Query<Query>) -> WebResult<Redirect> {
+pub async fn login(
+    data: WebData,
+    session_cache: web::Data<SessionIdCache>,
+    req: HttpRequest,
+    query: web::Query<Query>,
+) -> WebResult<Redirect> {
+    let code = match &query.code {
+        Some(code) => code,
+        None => {
+            trace!("No code provided, starting a new login");
+            let start = data.identity.start_login();
+            if let Some(pending) = &start.pending {
+                LoginAttempt::create(
+                    &data.db,
+                    &pending.state,
+                    &pending.pkce_verifier,
+                    &pending.nonce,
+                )
+                .await?;
+            }
+
+            return Ok(Redirect::new(start.url));
+        }
+    };
+
+    let pending = match &query.state {
+        Some(state) => LoginAttempt::take(&data.db, state)
+            .await?
+            .map(|attempt| PendingLogin {
+                state: attempt.state,
+                pkce_verifier: attempt.pkce_verifier,
+                nonce: attempt.nonce,
+            }),
+        None => None,
+    };
+
     // Complete the OAuth2 flow by exchanging the code for a token pair
-    trace!("Exchanging received code for Oauth2 tokens with Koala");
+    trace!("Exchanging received code for OAuth2 tokens with the identity provider");
+    let tokens = data.identity.exchange_code(code, pending.as_ref()).await?;
+    let userinfo = data.identity.userinfo(&tokens.access.access_token).await?;
 
-    let oauth_api = data.koala.oauth_api(data.config.oauth_client_config());
+    // The userinfo must describe the user the ID token was issued to
+    if let Some(subject) = &tokens.subject {
+        if subject.ne(&userinfo.subject) {
+            return Err(Error::BadRequest(
+                "Userinfo does not match the ID token".into(),
+            ));
+        }
+    }
 
-    let oauth_tokens = oauth_api
-        .exchange_login_code(&query.code)
-        .await
-        .map_err(Error::Koala)?;
+    let issuer = data.identity.issuer();
+    let user_id = match userinfo.user_id {
+        Some(id) => Some(id),
+        None => ExternalIdentity::get_user_id(&data.db, issuer, &userinfo.subject).await?,
+    };
 
-    let userinfo = oauth_api
-        .get_userinfo(&oauth_tokens.access_token)
-        .await
-        .map_err(Error::Koala)?;
+    trace!("Is signed-in user admin?: {}", userinfo.is_admin);
+    let existing = match user_id {
+        Some(id) => User::get_by_id(&data.db, id).await?,
+        None => None,
+    };
 
-    let user = match User::get_by_id(&data.db, userinfo.koala_id).await? {
-        Some(mut u) => {
+    let user = match existing {
+        Some(u) => {
             trace!("User already exists in database, setting new OAuth2 tokens");
-
-            // Update the tokens for this user
-            u.set_tokens(
-                oauth_tokens.access_token,
-                oauth_tokens.refresh_token,
-                oauth_tokens.expires_at,
-            )
-            .await?;
-            u
+            update_user(&data, u, tokens.access, userinfo.is_admin).await?
         }
         // No user exists yet, create one
         None => {
             trace!("User does not yet exist in database, creating new user.");
+            match create_user(&data, user_id, &userinfo, tokens.access.clone()).await? {
+                Some(user) => user,
+                None => {
+                    trace!("User was created by a concurrent login");
+                    let id = match user_id {
+                        Some(id) => Some(id),
+                        None => {
+                            ExternalIdentity::get_user_id(&data.db, issuer, &userinfo.subject)
+                                .await?
+                        }
+                    };
 
-            let user_info = oauth_api
-                .get_userinfo(&oauth_tokens.access_token)
-                .await
-                .map_err(Error::Koala)?;
-
-            trace!("Is signed-in user admin?: {}", user_info.is_admin);
-            User::create(
-                &data.db,
-                user_info.koala_id,
-                OAuthAccess {
-                    access_token: oauth_tokens.access_token,
-                    refresh_token: oauth_tokens.refresh_token,
-                    expires_at: oauth_tokens.expires_at,
-                },
-                user_info.is_admin,
-                user_info.full_name,
-            )
-            .await?
+                    let u = match id {
+                        Some(id) => User::get_by_id(&data.db, id).await?,
+                        None => None,
+                    }
+                    .ok_or(Error::Other(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+                    update_user(&data, u, tokens.access, userinfo.is_admin).await?
+                }
+            }
         }
     };
 
+    group_sync::sync(&data, user.koala_id, &user.access_token).await?;
+
+    // The admin status and synced grants of the user may have changed,
+    // which other sessions of the user may have cached
+    invalidate_cached_user(&session_cache, user.koala_id);
+
     trace!("Creating new session for user.");
-    let session_id = user.create_session().await?;
+    let user_agent = req
+        .headers()
+        .get(USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.chars().take(MAX_USER_AGENT_LEN).collect::<String>());
+    let session_id = user.create_session(user_agent.as_deref()).await?;
     let redirect_to = format!(
         "{}?session_id={}&is_admin={}",
         data.config.login_complete_redirect_uri, session_id, user.is_admin
     );
     Ok(Redirect::new(redirect_to))
 }
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::StatusCode;
+    use actix_web::{test, App};
+    use mock_koala::MockUser;
+
+    use dal::database::User;
+
+    use crate::routes::redirect::Redirect;
+    use crate::testing;
+
+    #[actix_web::test]
+    #[ignore = "requires the database and S3 from docker-compose.yml"]
+    async fn login_creates_user_and_session() {
+        let koala_id = testing::random_koala_id();
+        let koala =
+            testing::start_koala(vec![MockUser::new(koala_id, "Jan", "Jansen").admin()]).await;
+        let data = testing::app_data(&koala).await;
+        let app = test::init_service(App::new().configure(testing::configure(data.clone()))).await;
+
+        let req = test::TestRequest::get()
+            .uri(&format!(
+                "/api/v1/login?code={}",
+                koala.issue_code(koala_id)
+            ))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), Redirect::STATUS_CODE);
+        assert!(testing::location(&resp).starts_with(testing::LOGIN_COMPLETE_REDIRECT_URI));
+        assert!(testing::location(&resp).ends_with("is_admin=true"));
+        assert!(!testing::session_id(&resp).is_empty());
+
+        let user = User::get_by_id(&data.db, koala_id).await.unwrap().unwrap();
+        assert!(user.is_admin);
+    }
+
+    #[actix_web::test]
+    #[ignore = "requires the database and S3 from docker-compose.yml"]
+    async fn login_updates_existing_user() {
+        let koala_id = testing::random_koala_id();
+        let koala =
+            testing::start_koala(vec![MockUser::new(koala_id, "Jan", "Jansen").admin()]).await;
+        let data = testing::app_data(&koala).await;
+        let app = test::init_service(App::new().configure(testing::configure(data.clone()))).await;
+
+        let req = test::TestRequest::get()
+            .uri(&format!(
+                "/api/v1/login?code={}",
+                koala.issue_code(koala_id)
+            ))
+            .to_request();
+        let first_session = testing::session_id(&test::call_service(&app, req).await);
+
+        koala.update_user(koala_id, |user| user.is_admin = false);
+        let req = test::TestRequest::get()
+            .uri(&format!(
+                "/api/v1/login?code={}",
+                koala.issue_code(koala_id)
+            ))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert!(testing::location(&resp).ends_with("is_admin=false"));
+        assert_ne!(testing::session_id(&resp), first_session);
+
+        let user = User::get_by_id(&data.db, koala_id).await.unwrap().unwrap();
+        assert!(!user.is_admin);
+    }
+
+    #[actix_web::test]
+    #[ignore = "requires the database and S3 from docker-compose.yml"]
+    async fn login_without_code_redirects_to_koala() {
+        let koala = testing::start_koala(Vec::new()).await;
+        let data = testing::app_data(&koala).await;
+        let app = test::init_service(App::new().configure(testing::configure(data))).await;
+
+        let req = test::TestRequest::get().uri("/api/v1/login").to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), Redirect::STATUS_CODE);
+        assert!(testing::location(&resp).starts_with(koala.base_uri()));
+    }
+
+    #[actix_web::test]
+    #[ignore = "requires the database and S3 from docker-compose.yml"]
+    async fn login_rejects_invalid_and_reused_codes() {
+        let koala_id = testing::random_koala_id();
+        let koala = testing::start_koala(vec![MockUser::new(koala_id, "Jan", "Jansen")]).await;
+        let data = testing::app_data(&koala).await;
+        let app = test::init_service(App::new().configure(testing::configure(data))).await;
+
+        let req = test::TestRequest::get()
+            .uri("/api/v1/login?code=invalid")
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            StatusCode::BAD_REQUEST
+        );
+
+        let code = koala.issue_code(koala_id);
+        let req = test::TestRequest::get()
+            .uri(&format!("/api/v1/login?code={code}"))
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            Redirect::STATUS_CODE
+        );
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/api/v1/login?code={code}"))
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            StatusCode::BAD_REQUEST
+        );
+    }
+}
+
+/// Store the new tokens and admin status of a user who logged in before.
+///
+/// # Errors
+///
+/// If a database error occurs
+async fn update_user<'a>(
+    data: &WebData,
+    mut user: User<'a>,
+    access: OAuthAccess,
+    is_admin: bool,
+) -> WebResult<User<'a>> {
+    let mut tx = data.db.begin().await?;
+    user.set_tokens(
+        &mut tx,
+        access.access_token,
+        access.refresh_token,
+        access.expires_at,
+    )
+    .await?;
+    tx.commit().await?;
+    user.set_is_admin(is_admin).await?;
+
+    Ok(user)
+}
+
+/// Create a user who logs in for the first time.
+/// Users of providers without chroma user IDs are linked by their subject,
+/// the user is only created if they can be linked.
+/// Returns `None` if a concurrent login of the same user created them first.
+///
+/// # Errors
+///
+/// If a database error occurs
+async fn create_user<'a>(
+    data: &'a WebData,
+    user_id: Option<i32>,
+    userinfo: &UserInfo,
+    access: OAuthAccess,
+) -> WebResult<Option<User<'a>>> {
+    let mut tx = data.db.begin().await?;
+    let id = match user_id {
+        Some(id) => id,
+        None => ExternalIdentity::next_user_id(&mut tx).await?,
+    };
+
+    let user = match User::create(
+        &data.db,
+        &mut tx,
+        id,
+        access,
+        userinfo.is_admin,
+        &userinfo.name,
+    )
+    .await?
+    {
+        Some(v) => v,
+        None => return Ok(None),
+    };
+
+    if user_id.is_none()
+        && !ExternalIdentity::link(&mut tx, data.identity.issuer(), &userinfo.subject, id).await?
+    {
+        return Ok(None);
+    }
+
+    tx.commit().await?;
+    Ok(Some(user))
+}
@@ -9,9 +9,14 @@ use serde::Deserialize;
 use crate::routes::routable::Routable;
 
 mod access;
+mod admin;
 mod album;
+mod api_key;
+mod collection;
 mod login;
 mod photo;
+mod role;
+mod trash;
 mod user;
 
 #[derive(Debug, Default, Clone, Deserialize)]
@@ -37,8 +42,13 @@ impl Routable for Router {
     fn configure(config: &mut ServiceConfig) {
         config.service(
             web::scope("/v1")
+                .configure(admin::Router::configure)
                 .configure(album::Router::configure)
+                .configure(api_key::Router::configure)
+                .configure(collection::Router::configure)
                 .configure(photo::Router::configure)
+                .configure(role::Router::configure)
+                .configure(trash::Router::configure)
                 .configure(user::Router::configure)
                 .route("/login", web::get().to(login::login))
                 // This route requires strict ratelimits
@@ -1,8 +1,8 @@
 use std::io::Cursor;
 
 use actix_multiresponse::Payload;
+use actix_web::HttpRequest;
 use exif::{In, Tag};
-use governor::clock::Clock;
 use image::imageops::FilterType;
 use image::io::Reader;
 use image::{DynamicImage, GenericImageView};
@@ -13,7 +13,7 @@ use tokio::time::Instant;
 use tracing::{debug, instrument, trace, warn};
 use webp::Encoder;
 
-use dal::database::{Album, Database, Photo, PhotoQuality};
+use dal::database::{Album, Database, Photo, PhotoObject, PhotoQuality};
 use dal::storage_engine::Storage;
 use proto::photo_respone::Response;
 use proto::{CreatePhotoRequest, CreatePhotoResponse};
@@ -21,39 +21,52 @@ use proto::{CreatePhotoRequest, CreatePhotoResponse};
 use crate::routes::appdata::WebData;
 use crate::routes::authorization::Authorization;
 use crate::routes::error::{Error, ImagePipelineError, WebResult};
+use crate::routes::ratelimit::Ratelimited;
+use crate::routes::scope::Scope;
 
 /// Create a new photo in an existing album.
 ///
 /// # Errors
 ///
 /// - If the album does not exist
+/// - If the user has uploaded too many photos recently
 /// - If something went wrong
 pub async fn create(
     auth: Authorization,
     data: WebData,
+    req: HttpRequest,
     payload: Payload<CreatePhotoRequest>,
-) -> WebResult<Payload<CreatePhotoResponse>> {
+) -> WebResult<Ratelimited<Payload<CreatePhotoResponse>>> {
+    let album = Album::get_by_id(&data.db, &payload.album_id)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    if !auth.may_access_album(&data.db, &album.id).await? {
+        return Err(Error::Forbidden);
+    }
+
     if !auth.is_admin
         && !auth
-            .has_scope(&data.db, "nl.svsticky.chroma.photo.create")
+            .has_album_scope(&data.db, &album.id, Scope::PhotoCreate)
             .await?
     {
         return Err(Error::Forbidden);
     }
 
-    let album = Album::get_by_id(&data.db, &payload.album_id)
-        .await?
-        .ok_or(Error::NotFound)?;
-
     // Album must be un-published for non-admins to modify them.
     if !album.is_draft && !auth.is_admin {
         return Err(Error::Forbidden);
     }
 
+    let ratelimit = data.ratelimits.photo_create.check(&auth, &req)?;
+
     // TODO Update actix-multiresponse to support moving out the payload, avoids another clone
     let photo_id = image_pipeline(&data, payload.photo_data.clone(), &album).await?;
 
-    Ok(Payload(CreatePhotoResponse { photo_id }))
+    Ok(Ratelimited(
+        Payload(CreatePhotoResponse { photo_id }),
+        ratelimit,
+    ))
 }
 
 /// Process the image and return the resulting photo ID
@@ -63,15 +76,6 @@ pub async fn create(
 /// If any step in the pipeline fails
 #[instrument(skip(data, image))]
 async fn image_pipeline(data: &WebData, image: Vec<u8>, album: &Album) -> WebResult<String> {
-    // Make sure we don't run into AWS ratelimits here
-    if let Err(e) = data.ratelimits.photo_create.check() {
-        return Err(Error::Ratelimit {
-            retry_after: e
-                .wait_time_from(governor::clock::DefaultClock::default().now())
-                .as_secs(),
-        });
-    }
-
     // This pipeline modifies the image. The idea is that each 'step' outputs
     // a variable 'image', which the next step can then use.
 
@@ -110,7 +114,9 @@ async fn image_pipeline(data: &WebData, image: Vec<u8>, album: &Album) -> WebRes
     trace!("Uploading original image on another Task");
     let original_image = image.clone();
     let photo_id = photo_metadata.id.clone();
+    let album_id = photo_metadata.album_id.clone();
     let engine = data.storage.clone();
+    let db = data.db.clone();
     tokio::spawn(async move {
         // Encode to WebP
         let encoder = match Encoder::from_image(&original_image) {
@@ -128,9 +134,15 @@ async fn image_pipeline(data: &WebData, image: Vec<u8>, album: &Album) -> WebRes
             "Saving image '{photo_id}' in quality '{:?}'",
             PhotoQuality::Original
         );
-        match engine
-            .create_photo(&photo_id, &PhotoQuality::Original, image)
-            .await
+        match PhotoObject::upload(
+            &db,
+            &engine,
+            &album_id,
+            &photo_id,
+            &PhotoQuality::Original,
+            image,
+        )
+        .await
         {
             Ok(_) => {}
             Err(e) => {
@@ -145,6 +157,7 @@ async fn image_pipeline(data: &WebData, image: Vec<u8>, album: &Album) -> WebRes
         PhotoQuality::W400,
         data.storage.clone(),
         data.db.clone(),
+        photo_metadata.album_id.clone(),
         photo_metadata.id.clone(),
     );
 
@@ -154,6 +167,7 @@ async fn image_pipeline(data: &WebData, image: Vec<u8>, album: &Album) -> WebRes
         PhotoQuality::W1600,
         data.storage.clone(),
         data.db.clone(),
+        photo_metadata.album_id.clone(),
         photo_metadata.id.clone(),
     );
 
@@ -168,6 +182,7 @@ fn resize_and_save(
     quality: PhotoQuality,
     engine: Storage,
     db: Database,
+    album_id: String,
     photo_id: String,
 ) {
     let target_width = match quality.width() {
@@ -189,9 +204,15 @@ fn resize_and_save(
         };
 
         trace!("Saving image '{photo_id}' in quality '{quality:?}'");
-        match engine
-            .create_photo(&photo_id, &quality, converted_image_data)
-            .await
+        match PhotoObject::upload(
+            &db,
+            &engine,
+            &album_id,
+            &photo_id,
+            &quality,
+            converted_image_data,
+        )
+        .await
         {
             Ok(_) => {}
             Err(e) => {
@@ -1,18 +1,21 @@
 use actix_multiresponse::Payload;
+use actix_web::web;
 use reqwest::StatusCode;
 
-use dal::database::PhotoQuality;
-use dal::database::{Album, Photo};
+use dal::database::{Album, AuditAction, AuditLogEntry, AuditTarget, Photo};
 use proto::DeletePhotoRequest;
 
-use crate::routes::appdata::WebData;
+use crate::routes::appdata::{AlbumIdCache, WebData};
 use crate::routes::authorization::Authorization;
 use crate::routes::empty::Empty;
 use crate::routes::error::{Error, WebResult};
+use crate::routes::scope::Scope;
 
-/// Delete a photo.
+/// Move a photo to the trash.
 /// If this photo is the cover of it's album, the album will no longer have a defined cover image.
 /// If this was the last photo in an album, the album will *not* be automatically deleted.
+/// The photo is permanently deleted once it has been in the trash for longer than the
+/// configured retention period. Until then, it can be restored by an admin.
 ///
 /// # Errors
 ///
@@ -21,20 +24,25 @@ use crate::routes::error::{Error, WebResult};
 pub async fn delete(
     auth: Authorization,
     data: WebData,
+    album_id_cache: web::Data<AlbumIdCache>,
     payload: Payload<DeletePhotoRequest>,
 ) -> WebResult<Empty> {
+    let mut photo = Photo::get_by_id(&data.db, &payload.photo_id)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    if !auth.may_access_album(&data.db, &photo.album_id).await? {
+        return Err(Error::Forbidden);
+    }
+
     if !auth.is_admin
         && !auth
-            .has_scope(&data.db, "nl.svsticky.chroma.photo.delete")
+            .has_album_scope(&data.db, &photo.album_id, Scope::PhotoDelete)
             .await?
     {
         return Err(Error::Forbidden);
     }
 
-    let photo = Photo::get_by_id(&data.db, &payload.photo_id)
-        .await?
-        .ok_or(Error::NotFound)?;
-
     if !auth.is_admin {
         let album = Album::get_by_id(&data.db, &photo.album_id)
             .await?
@@ -46,14 +54,21 @@ pub async fn delete(
         }
     }
 
-    let id = photo.id.clone();
-    photo.delete().await?;
+    let mut tx = data.db.begin().await?;
+    photo.trash(&mut tx).await?;
+    AuditLogEntry::record(
+        &mut tx,
+        Some(&auth.to_dal_user_type()),
+        AuditAction::PhotoTrashed,
+        AuditTarget::Photo(photo.id.clone()),
+        None,
+        None,
+    )
+    .await?;
+    tx.commit().await?;
 
-    data.storage
-        .delete_photo(&id, &PhotoQuality::Original)
-        .await?;
-    data.storage.delete_photo(&id, &PhotoQuality::W1600).await?;
-    data.storage.delete_photo(&id, &PhotoQuality::W400).await?;
+    // The photo might have been the album's cover
+    album_id_cache.remove(&photo.album_id).await;
 
     Ok(Empty)
 }
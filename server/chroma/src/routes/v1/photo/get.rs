@@ -1,7 +1,7 @@
 use std::io::Cursor;
 
 use actix_multiresponse::Payload;
-use actix_web::web;
+use actix_web::{web, Either, HttpRequest};
 use image::{DynamicImage, ImageOutputFormat};
 use serde::Deserialize;
 use tap::TapFallible;
@@ -15,6 +15,7 @@ use proto::{GetPhotoResponse, PhotoRespone};
 use crate::routes::appdata::WebData;
 use crate::routes::authorization::Authorization;
 use crate::routes::error::{Error, WebResult};
+use crate::routes::ratelimit::Ratelimited;
 use crate::routes::v1::PhotoQuality;
 
 #[derive(Debug, Deserialize)]
@@ -45,27 +46,35 @@ pub enum ImageFormat {
 }
 
 /// Retrieve a photo by its ID.
+/// Retrieving the photo's bytes, or converting it to another format, is ratelimited.
 ///
 /// # Errors
 ///
 /// - If the photo does not exist
+/// - If the user is bound to other albums
+/// - If the user has retrieved too many photos as bytes recently
 /// - If something went wrong
 pub async fn get(
-    _: Authorization,
+    auth: Authorization,
     data: WebData,
+    req: HttpRequest,
     query: web::Query<Query>,
-) -> WebResult<Payload<GetPhotoResponse>> {
+) -> WebResult<Either<Payload<GetPhotoResponse>, Ratelimited<Payload<GetPhotoResponse>>>> {
     let photo = Photo::get_by_id(&data.db, &query.id)
         .await?
         .ok_or(Error::NotFound)?;
 
+    if !auth.may_access_album(&data.db, &photo.album_id).await? {
+        return Err(Error::Forbidden);
+    }
+
     if query.format.eq(&ImageFormat::WebP) && !query.force_bytes {
         return match photo
             .clone()
             .photo_to_proto_url(&data.storage, &query.quality_preference.clone().into())
             .await
         {
-            Ok(p) => Ok(Payload(GetPhotoResponse { photo: Some(p) })),
+            Ok(p) => Ok(Either::Left(Payload(GetPhotoResponse { photo: Some(p) }))),
             Err(e) => match e {
                 DalError::Storage(e) => Err(e.into()),
                 DalError::Db(e) => Err(e.into()),
@@ -73,6 +82,8 @@ pub async fn get(
         };
     }
 
+    let ratelimit = data.ratelimits.photo_convert.check(&auth, &req)?;
+
     let mut proto = photo
         .photo_to_proto_bytes(&data.storage, query.quality_preference.clone().into())
         .await
@@ -90,7 +101,10 @@ pub async fn get(
         response: Some(Response::Bytes(convert_format(bytes, &query.format)?)),
     });
 
-    Ok(Payload(GetPhotoResponse { photo: Some(proto) }))
+    Ok(Either::Right(Ratelimited(
+        Payload(GetPhotoResponse { photo: Some(proto) }),
+        ratelimit,
+    )))
 }
 
 fn convert_format(bytes: Vec<u8>, format: &ImageFormat) -> WebResult<Vec<u8>> {
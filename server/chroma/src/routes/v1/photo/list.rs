@@ -25,19 +25,30 @@ pub struct Query {
 /// List all photos, either all known or all from one album.
 /// If the `album_id` provided does not correspond to any known album,
 /// an empty set will be returned.
+/// Users bound to specific albums only see the photos in those albums.
 ///
 /// # Errors
 ///
+/// - If the user is bound to other albums than the provided album
 /// - If something went wrong
 pub async fn list(
-    _: Authorization,
+    auth: Authorization,
     data: WebData,
     query: web::Query<Query>,
 ) -> WebResult<Payload<ListPhotoResponse>> {
     let photos = if let Some(album_id) = &query.album_id {
+        if !auth.may_access_album(&data.db, album_id).await? {
+            return Err(Error::Forbidden);
+        }
+
         Photo::list_in_album(&data.db, album_id).await?
     } else {
-        Photo::list(&data.db).await?
+        let mut photos = Photo::list(&data.db).await?;
+        if let Some(album_ids) = auth.list_bound_albums(&data.db).await? {
+            photos.retain(|photo| album_ids.contains(&photo.album_id));
+        }
+
+        photos
     };
 
     let photos = join_all(photos.into_iter().map(|p| {
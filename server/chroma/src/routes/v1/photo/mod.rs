@@ -7,6 +7,7 @@ mod create;
 mod delete;
 mod get;
 mod list;
+mod move_photos;
 
 pub struct Router;
 
@@ -17,7 +18,255 @@ impl Routable for Router {
                 .route("", web::post().to(create::create))
                 .route("", web::delete().to(delete::delete))
                 .route("", web::get().to(get::get))
-                .route("/list", web::get().to(list::list)),
+                .route("/list", web::get().to(list::list))
+                .route("/move", web::post().to(move_photos::move_photos)),
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::header::{ACCEPT, AUTHORIZATION};
+    use actix_web::http::StatusCode;
+    use actix_web::{test, App};
+    use mock_koala::MockUser;
+    use time::OffsetDateTime;
+
+    use dal::consistency::ConsistencyReport;
+    use dal::database::{AlbumRole, Photo};
+    use proto::{CreatePhotoRequest, DeletePhotoRequest, MovePhotosRequest, MovePhotosResponse};
+
+    use crate::testing;
+
+    /// Whether the role allows the request, judging by its response.
+    fn is_allowed(status: StatusCode) -> bool {
+        status != StatusCode::FORBIDDEN
+    }
+
+    #[actix_web::test]
+    #[ignore = "requires the database and S3 from docker-compose.yml"]
+    async fn roles_allow_photo_changes() {
+        let koala_id = testing::random_koala_id();
+        let koala = testing::start_koala(vec![MockUser::new(koala_id, "Jan", "Jansen")]).await;
+        let data = testing::app_data(&koala).await;
+        let app = test::init_service(App::new().configure(testing::configure(data.clone()))).await;
+        let session_id = testing::session_id(
+            &test::call_service(&app, testing::login_request(&koala, koala_id).to_request()).await,
+        );
+
+        for role in testing::ALBUM_ROLES {
+            let album = testing::draft_album_with_role(&data.db, koala_id, role).await;
+
+            // The photo data is not an image, only allowed uploads get to decoding it
+            let req = test::TestRequest::post()
+                .uri("/api/v1/photo")
+                .insert_header((AUTHORIZATION, session_id.as_str()))
+                .set_json(CreatePhotoRequest {
+                    album_id: album.id.clone(),
+                    photo_data: vec![0; 16],
+                })
+                .to_request();
+            let status = test::call_service(&app, req).await.status();
+            assert_eq!(
+                is_allowed(status),
+                role >= Some(AlbumRole::Contributor),
+                "create as {role:?}"
+            );
+
+            let photo = Photo::create(&data.db, &album, OffsetDateTime::now_utc().unix_timestamp())
+                .await
+                .unwrap();
+            let req = test::TestRequest::delete()
+                .uri("/api/v1/photo")
+                .insert_header((AUTHORIZATION, session_id.as_str()))
+                .set_json(DeletePhotoRequest { photo_id: photo.id })
+                .to_request();
+            let status = test::call_service(&app, req).await.status();
+            assert_eq!(
+                is_allowed(status),
+                role >= Some(AlbumRole::Contributor),
+                "delete as {role:?}"
+            );
+        }
+    }
+
+    #[actix_web::test]
+    #[ignore = "requires the database and S3 from docker-compose.yml"]
+    async fn roles_allow_moving_photos() {
+        let koala_id = testing::random_koala_id();
+        let koala = testing::start_koala(vec![MockUser::new(koala_id, "Jan", "Jansen")]).await;
+        let data = testing::app_data(&koala).await;
+        let app = test::init_service(App::new().configure(testing::configure(data.clone()))).await;
+        let session_id = testing::session_id(
+            &test::call_service(&app, testing::login_request(&koala, koala_id).to_request()).await,
+        );
+
+        for role in testing::ALBUM_ROLES {
+            // The role in the album the photo is moved out of
+            let source = testing::draft_album_with_role(&data.db, koala_id, role).await;
+            let target =
+                testing::draft_album_with_role(&data.db, koala_id, Some(AlbumRole::Owner)).await;
+            let photo = Photo::create(
+                &data.db,
+                &source,
+                OffsetDateTime::now_utc().unix_timestamp(),
+            )
+            .await
+            .unwrap();
+
+            let req = test::TestRequest::post()
+                .uri("/api/v1/photo/move")
+                .insert_header((AUTHORIZATION, session_id.as_str()))
+                .set_json(MovePhotosRequest {
+                    photo_ids: vec![photo.id],
+                    target_album_id: target.id,
+                    copy: None,
+                })
+                .to_request();
+            let status = test::call_service(&app, req).await.status();
+            assert_eq!(
+                is_allowed(status),
+                role >= Some(AlbumRole::Contributor),
+                "move out of album as {role:?}"
+            );
+
+            // The role in the album the photo is moved into
+            let source =
+                testing::draft_album_with_role(&data.db, koala_id, Some(AlbumRole::Owner)).await;
+            let target = testing::draft_album_with_role(&data.db, koala_id, role).await;
+            let photo = Photo::create(
+                &data.db,
+                &source,
+                OffsetDateTime::now_utc().unix_timestamp(),
+            )
+            .await
+            .unwrap();
+
+            let req = test::TestRequest::post()
+                .uri("/api/v1/photo/move")
+                .insert_header((AUTHORIZATION, session_id.as_str()))
+                .set_json(MovePhotosRequest {
+                    photo_ids: vec![photo.id],
+                    target_album_id: target.id,
+                    copy: None,
+                })
+                .to_request();
+            let status = test::call_service(&app, req).await.status();
+            assert_eq!(
+                is_allowed(status),
+                role >= Some(AlbumRole::Contributor),
+                "move into album as {role:?}"
+            );
+        }
+    }
+
+    #[actix_web::test]
+    #[ignore = "requires the database and S3 from docker-compose.yml"]
+    async fn roles_allow_copying_photos() {
+        let koala_id = testing::random_koala_id();
+        let koala = testing::start_koala(vec![MockUser::new(koala_id, "Jan", "Jansen")]).await;
+        let data = testing::app_data(&koala).await;
+        let app = test::init_service(App::new().configure(testing::configure(data.clone()))).await;
+        let session_id = testing::session_id(
+            &test::call_service(&app, testing::login_request(&koala, koala_id).to_request()).await,
+        );
+
+        for role in testing::ALBUM_ROLES {
+            // The role in the draft album the photo is copied out of
+            let source = testing::draft_album_with_role(&data.db, koala_id, role).await;
+            let target =
+                testing::draft_album_with_role(&data.db, koala_id, Some(AlbumRole::Owner)).await;
+            let photo = Photo::create(
+                &data.db,
+                &source,
+                OffsetDateTime::now_utc().unix_timestamp(),
+            )
+            .await
+            .unwrap();
+
+            let req = test::TestRequest::post()
+                .uri("/api/v1/photo/move")
+                .insert_header((AUTHORIZATION, session_id.as_str()))
+                .set_json(MovePhotosRequest {
+                    photo_ids: vec![photo.id],
+                    target_album_id: target.id,
+                    copy: Some(true),
+                })
+                .to_request();
+            let status = test::call_service(&app, req).await.status();
+            assert_eq!(
+                is_allowed(status),
+                role >= Some(AlbumRole::Viewer),
+                "copy out of album as {role:?}"
+            );
+        }
+    }
+
+    #[actix_web::test]
+    #[ignore = "requires the database and S3 from docker-compose.yml"]
+    async fn duplicate_photos_are_moved_once() {
+        let koala_id = testing::random_koala_id();
+        let koala = testing::start_koala(vec![MockUser::new(koala_id, "Jan", "Jansen")]).await;
+        let data = testing::app_data(&koala).await;
+        let app = test::init_service(App::new().configure(testing::configure(data.clone()))).await;
+        let session_id = testing::session_id(
+            &test::call_service(&app, testing::login_request(&koala, koala_id).to_request()).await,
+        );
+
+        let source =
+            testing::draft_album_with_role(&data.db, koala_id, Some(AlbumRole::Owner)).await;
+        let target =
+            testing::draft_album_with_role(&data.db, koala_id, Some(AlbumRole::Owner)).await;
+        let first = Photo::create(
+            &data.db,
+            &source,
+            OffsetDateTime::now_utc().unix_timestamp(),
+        )
+        .await
+        .unwrap();
+        let second = Photo::create(
+            &data.db,
+            &source,
+            OffsetDateTime::now_utc().unix_timestamp(),
+        )
+        .await
+        .unwrap();
+
+        let req = test::TestRequest::post()
+            .uri("/api/v1/photo/move")
+            .insert_header((AUTHORIZATION, session_id.as_str()))
+            .insert_header((ACCEPT, "application/json"))
+            .set_json(MovePhotosRequest {
+                photo_ids: vec![second.id.clone(), first.id.clone(), second.id.clone()],
+                target_album_id: target.id.clone(),
+                copy: None,
+            })
+            .to_request();
+        let response: MovePhotosResponse = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(response.photo_ids, vec![second.id, first.id]);
+
+        let moved = Photo::list_in_album(&data.db, &target.id).await.unwrap();
+        assert_eq!(moved.len(), 2);
+    }
+
+    #[actix_web::test]
+    #[ignore = "requires the database and S3 from docker-compose.yml"]
+    async fn uploading_photo_is_not_missing_its_original() {
+        let koala_id = testing::random_koala_id();
+        let koala = testing::start_koala(vec![MockUser::new(koala_id, "Jan", "Jansen")]).await;
+        let data = testing::app_data(&koala).await;
+        let album =
+            testing::draft_album_with_role(&data.db, koala_id, Some(AlbumRole::Owner)).await;
+
+        // Taken long ago, but its original has not been stored yet
+        let photo = Photo::create(&data.db, &album, 0).await.unwrap();
+
+        let report = ConsistencyReport::check(&data.db, &data.storage)
+            .await
+            .unwrap();
+        assert!(!report
+            .photos_missing_original
+            .iter()
+            .any(|missing| missing.id == photo.id));
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::HashSet;
+
+use actix_multiresponse::Payload;
+use actix_web::web;
+use reqwest::StatusCode;
+
+use dal::database::{
+    Album, AuditAction, AuditLogEntry, AuditTarget, Database, DbResult, Photo, PhotoCopy,
+};
+use dal::DalError;
+use proto::{MovePhotosRequest, MovePhotosResponse};
+
+use crate::routes::appdata::{AlbumIdCache, WebData};
+use crate::routes::authorization::Authorization;
+use crate::routes::error::{Error, WebResult};
+use crate::routes::scope::Scope;
+
+/// Move one or more photos to another album.
+/// If `copy` is set, the photos are copied instead and remain in their current album.
+/// Photos that are moved away from an album of which they are the cover are
+/// removed as cover of that album.
+///
+/// The IDs of the photos in the target album are returned in the same order as requested,
+/// photos which are requested more than once are only moved or copied once.
+/// When copying, these are the IDs of the newly created photos.
+/// Either all photos are copied or none are.
+///
+/// # Errors
+///
+/// - If no photos are provided
+/// - If the target album or any of the photos does not exist
+/// - If the user may not modify the target album
+/// - If the user may not view the photos in the source albums or, when moving, modify the source albums
+/// - If something went wrong
+pub async fn move_photos(
+    auth: Authorization,
+    data: WebData,
+    album_id_cache: web::Data<AlbumIdCache>,
+    payload: Payload<MovePhotosRequest>,
+) -> WebResult<Payload<MovePhotosResponse>> {
+    let copy = payload.copy.unwrap_or(false);
+
+    if payload.photo_ids.is_empty() {
+        return Err(Error::BadRequest("No photos provided".into()));
+    }
+
+    let target = Album::get_by_id(&data.db, &payload.target_album_id)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    if !auth.may_access_album(&data.db, &target.id).await? {
+        return Err(Error::Forbidden);
+    }
+
+    if !auth.is_admin
+        && !auth
+            .has_album_scope(&data.db, &target.id, Scope::PhotoCreate)
+            .await?
+    {
+        return Err(Error::Forbidden);
+    }
+
+    // Only admins may modify published albums.
+    if !target.is_draft && !auth.is_admin {
+        return Err(Error::Forbidden);
+    }
+
+    let mut seen = HashSet::new();
+    let photo_ids = payload
+        .photo_ids
+        .iter()
+        .filter(|photo_id| seen.insert(photo_id.as_str()))
+        .cloned()
+        .collect::<Vec<_>>();
+
+    // Make sure all photos exist before changing anything
+    let mut photos = Vec::with_capacity(photo_ids.len());
+    for photo_id in &photo_ids {
+        let photo = Photo::get_by_id(&data.db, photo_id)
+            .await?
+            .ok_or(Error::BadRequest(format!(
+                "Photo with ID '{photo_id}' does not exist"
+            )))?;
+        photos.push(photo);
+    }
+
+    let source_album_ids = photos
+        .iter()
+        .map(|photo| photo.album_id.clone())
+        .collect::<HashSet<_>>();
+
+    for album_id in &source_album_ids {
+        if !auth.may_access_album(&data.db, album_id).await? {
+            return Err(Error::Forbidden);
+        }
+
+        if auth.is_admin {
+            continue;
+        }
+
+        let album = Album::get_by_id(&data.db, album_id)
+            .await?
+            .ok_or(Error::Other(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+        // Photos in draft albums may only be seen by members and users who may list draft albums
+        if album.is_draft
+            && !auth
+                .has_album_scope(&data.db, &album.id, Scope::AlbumListDraft)
+                .await?
+        {
+            return Err(Error::Forbidden);
+        }
+
+        // Moving a photo removes it from its current album.
+        // Only admins may remove photos from published albums
+        if !copy
+            && (!album.is_draft
+                || !auth
+                    .has_album_scope(&data.db, &album.id, Scope::PhotoDelete)
+                    .await?)
+        {
+            return Err(Error::Forbidden);
+        }
+    }
+
+    if copy {
+        let mut copies = Vec::with_capacity(photos.len());
+        for photo in &photos {
+            match photo.copy_objects(&data.storage, &target).await {
+                Ok(copied) => copies.push(copied),
+                Err(e) => {
+                    discard_copies(&data.db, copies).await?;
+                    return Err(match e {
+                        DalError::Storage(e) => Error::from(e),
+                        DalError::Db(e) => Error::from(e),
+                    });
+                }
+            }
+        }
+
+        let photo_ids = copies.iter().map(|copied| copied.id.clone()).collect();
+        if let Err(e) = insert_copies(&auth, &data.db, &target, &copies).await {
+            discard_copies(&data.db, copies).await?;
+            return Err(e.into());
+        }
+
+        return Ok(Payload(MovePhotosResponse { photo_ids }));
+    }
+
+    let mut tx = data.db.begin().await?;
+    Photo::move_to_album(&mut tx, &photo_ids, &target).await?;
+    for photo in &photos {
+        AuditLogEntry::record(
+            &mut tx,
+            Some(&auth.to_dal_user_type()),
+            AuditAction::PhotoMoved,
+            AuditTarget::Photo(photo.id.clone()),
+            Some(photo.album_id.clone()),
+            Some(target.id.clone()),
+        )
+        .await?;
+    }
+    tx.commit().await?;
+
+    // The cover photo of the source albums might have been unset
+    for album_id in &source_album_ids {
+        album_id_cache.remove(album_id).await;
+    }
+
+    Ok(Payload(MovePhotosResponse { photo_ids }))
+}
+
+/// Record the copies of photos in a single transaction.
+async fn insert_copies(
+    auth: &Authorization,
+    db: &Database,
+    target: &Album,
+    copies: &[PhotoCopy],
+) -> DbResult<()> {
+    let mut tx = db.begin().await?;
+    for copied in copies {
+        copied.insert(&mut tx).await?;
+        AuditLogEntry::record(
+            &mut tx,
+            Some(&auth.to_dal_user_type()),
+            AuditAction::PhotoCopied,
+            AuditTarget::Photo(copied.id.clone()),
+            Some(copied.source_id.clone()),
+            Some(target.id.clone()),
+        )
+        .await?;
+    }
+    tx.commit().await
+}
+
+/// Delete the objects of copies which could not be recorded.
+async fn discard_copies(db: &Database, copies: Vec<PhotoCopy>) -> DbResult<()> {
+    let mut tx = db.begin().await?;
+    for copied in copies {
+        copied.discard(&mut tx).await?;
+    }
+    tx.commit().await
+}
@@ -0,0 +1,56 @@
+use actix_multiresponse::Payload;
+
+use dal::database::{AuditAction, AuditLogEntry, AuditTarget, Role};
+use proto::{CreateRoleRequest, CreateRoleResponse};
+
+use crate::routes::appdata::WebData;
+use crate::routes::authorization::Authorization;
+use crate::routes::error::{Error, WebResult};
+use crate::routes::scope::Scope;
+use crate::routes::v1::role::{validate_description, validate_name};
+
+/// Create a new role with the provided scopes.
+///
+/// # Errors
+///
+/// - If the user is not an admin
+/// - If the name is empty, too long or already in use
+/// - If the description is too long
+/// - If a scope is unknown
+/// - If something went wrong
+pub async fn create(
+    auth: Authorization,
+    data: WebData,
+    payload: Payload<CreateRoleRequest>,
+) -> WebResult<Payload<CreateRoleResponse>> {
+    if !auth.is_admin {
+        return Err(Error::Forbidden);
+    }
+
+    validate_name(&data, &payload.name, None).await?;
+    validate_description(&payload.description)?;
+    Scope::check_grants(&payload.scopes)?;
+
+    let mut tx = data.db.begin().await?;
+    let role = Role::create(
+        &mut tx,
+        &payload.name,
+        &payload.description,
+        &payload.scopes,
+    )
+    .await?;
+    AuditLogEntry::record(
+        &mut tx,
+        Some(&auth.to_dal_user_type()),
+        AuditAction::RoleCreated,
+        AuditTarget::Role(role.id),
+        None,
+        Some(payload.scopes.join(",")),
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(Payload(CreateRoleResponse {
+        role: Some(role.to_proto(&data.db).await?),
+    }))
+}
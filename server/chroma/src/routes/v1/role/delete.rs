@@ -0,0 +1,53 @@
+use actix_multiresponse::Payload;
+use actix_web::web;
+
+use dal::database::{AuditAction, AuditLogEntry, AuditTarget, Role};
+use proto::DeleteRoleRequest;
+
+use crate::routes::appdata::{SessionIdCache, WebData};
+use crate::routes::authorization::Authorization;
+use crate::routes::empty::Empty;
+use crate::routes::error::{Error, WebResult};
+
+/// Delete a role, removing it from all users it was assigned to.
+/// Scopes which were granted to those users directly are kept.
+///
+/// # Errors
+///
+/// - If the user is not an admin
+/// - If the role does not exist
+/// - If something went wrong
+pub async fn delete(
+    auth: Authorization,
+    data: WebData,
+    session_cache: web::Data<SessionIdCache>,
+    payload: Payload<DeleteRoleRequest>,
+) -> WebResult<Empty> {
+    if !auth.is_admin {
+        return Err(Error::Forbidden);
+    }
+
+    let role = Role::get_by_id(&data.db, payload.id)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    let id = role.id;
+    let name = role.name.clone();
+    let mut tx = data.db.begin().await?;
+    role.delete(&mut tx).await?;
+    AuditLogEntry::record(
+        &mut tx,
+        Some(&auth.to_dal_user_type()),
+        AuditAction::RoleDeleted,
+        AuditTarget::Role(id),
+        Some(name),
+        None,
+    )
+    .await?;
+    tx.commit().await?;
+
+    // The role may be held by any number of users, directly or through their groups
+    session_cache.invalidate_all();
+
+    Ok(Empty)
+}
@@ -0,0 +1,27 @@
+use actix_multiresponse::Payload;
+
+use dal::database::Role;
+use proto::ListRolesResponse;
+
+use crate::routes::appdata::WebData;
+use crate::routes::authorization::Authorization;
+use crate::routes::error::{Error, WebResult};
+
+/// List all roles with their scopes.
+///
+/// # Errors
+///
+/// - If the user is not an admin
+/// - If something went wrong
+pub async fn list(auth: Authorization, data: WebData) -> WebResult<Payload<ListRolesResponse>> {
+    if !auth.is_admin {
+        return Err(Error::Forbidden);
+    }
+
+    let mut roles = Vec::new();
+    for role in Role::list(&data.db).await? {
+        roles.push(role.to_proto(&data.db).await?);
+    }
+
+    Ok(Payload(ListRolesResponse { roles }))
+}
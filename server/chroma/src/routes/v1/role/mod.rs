@@ -0,0 +1,62 @@
+use actix_web::web;
+use actix_web::web::ServiceConfig;
+
+use dal::database::Role;
+
+use crate::routes::appdata::WebData;
+use crate::routes::error::{Error, WebResult};
+use crate::routes::routable::Routable;
+
+mod create;
+mod delete;
+mod list;
+mod update;
+
+/// The maximum length of the name of a role
+const MAX_NAME_LEN: usize = 64;
+/// The maximum length of the description of a role
+const MAX_DESCRIPTION_LEN: usize = 256;
+
+pub struct Router;
+
+impl Routable for Router {
+    fn configure(config: &mut ServiceConfig) {
+        config.service(
+            web::scope("/role")
+                .route("", web::post().to(create::create))
+                .route("", web::patch().to(update::update))
+                .route("", web::delete().to(delete::delete))
+                .route("/list", web::get().to(list::list)),
+        );
+    }
+}
+
+/// Check that the name is valid and not used by another role.
+async fn validate_name(data: &WebData, name: &str, role_id: Option<i32>) -> WebResult<()> {
+    if name.trim().is_empty() {
+        return Err(Error::BadRequest("Name may not be empty".into()));
+    }
+
+    if name.len() > MAX_NAME_LEN {
+        return Err(Error::BadRequest(format!(
+            "Name may not be longer than {MAX_NAME_LEN} characters"
+        )));
+    }
+
+    match Role::get_by_name(&data.db, name).await? {
+        Some(existing) if Some(existing.id) != role_id => Err(Error::BadRequest(format!(
+            "A role named '{name}' already exists"
+        ))),
+        _ => Ok(()),
+    }
+}
+
+fn validate_description(description: &str) -> WebResult<()> {
+    if description.len() > MAX_DESCRIPTION_LEN {
+        return Err(Error::BadRequest(format!(
+            "Description may not be longer than {MAX_DESCRIPTION_LEN} characters"
+        )));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,83 @@
+use actix_multiresponse::Payload;
+use actix_web::web;
+
+use dal::database::{AuditAction, AuditLogEntry, AuditTarget, Role};
+use proto::UpdateRoleRequest;
+
+use crate::routes::appdata::{SessionIdCache, WebData};
+use crate::routes::authorization::Authorization;
+use crate::routes::empty::Empty;
+use crate::routes::error::{Error, WebResult};
+use crate::routes::scope::Scope;
+use crate::routes::v1::role::{validate_description, validate_name};
+
+/// Update a role, and replace its scopes if new scopes are provided.
+/// The changes apply to all users the role is assigned to.
+///
+/// # Errors
+///
+/// - If the user is not an admin
+/// - If the role does not exist
+/// - If the name is empty, too long or already in use
+/// - If the description is too long
+/// - If a scope is unknown
+/// - If something went wrong
+pub async fn update(
+    auth: Authorization,
+    data: WebData,
+    session_cache: web::Data<SessionIdCache>,
+    payload: Payload<UpdateRoleRequest>,
+) -> WebResult<Empty> {
+    if !auth.is_admin {
+        return Err(Error::Forbidden);
+    }
+
+    let mut role = Role::get_by_id(&data.db, payload.id)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    if let Some(name) = &payload.name {
+        validate_name(&data, name, Some(role.id)).await?;
+    }
+
+    if let Some(description) = &payload.description {
+        validate_description(description)?;
+    }
+
+    let scopes = payload.scopes.as_ref().map(|scopes| &scopes.scopes);
+    if let Some(scopes) = scopes {
+        Scope::check_grants(scopes)?;
+    }
+
+    let previous_scopes = role.list_scopes(&data.db).await?;
+
+    let mut tx = data.db.begin().await?;
+
+    if let Some(name) = &payload.name {
+        role.update_name(&mut tx, name).await?;
+    }
+
+    if let Some(description) = &payload.description {
+        role.update_description(&mut tx, description).await?;
+    }
+
+    if let Some(scopes) = scopes {
+        role.set_scopes(&mut tx, scopes).await?;
+    }
+
+    AuditLogEntry::record(
+        &mut tx,
+        Some(&auth.to_dal_user_type()),
+        AuditAction::RoleUpdated,
+        AuditTarget::Role(role.id),
+        Some(previous_scopes.join(",")),
+        Some(scopes.unwrap_or(&previous_scopes).join(",")),
+    )
+    .await?;
+    tx.commit().await?;
+
+    // The role may be held by any number of users, directly or through their groups
+    session_cache.invalidate_all();
+
+    Ok(Empty)
+}
@@ -0,0 +1,55 @@
+use actix_multiresponse::Payload;
+use futures::future::{join_all, try_join_all};
+
+use dal::database::{Album, Photo, PhotoQuality};
+use dal::DalError;
+use proto::ListTrashResponse;
+
+use crate::routes::appdata::WebData;
+use crate::routes::authorization::Authorization;
+use crate::routes::error::{Error, WebResult};
+
+/// List all albums and photos in the trash.
+/// Photos which are in the trash because their album is in the trash are not listed separately.
+///
+/// Only admins may view the trash.
+///
+/// # Errors
+///
+/// - If something went wrong
+pub async fn list(auth: Authorization, data: WebData) -> WebResult<Payload<ListTrashResponse>> {
+    if !auth.is_admin {
+        return Err(Error::Forbidden);
+    }
+
+    let albums = try_join_all(
+        Album::list_trashed(&data.db)
+            .await?
+            .into_iter()
+            .map(|album| album.to_proto(&data.db)),
+    )
+    .await?;
+
+    let photos = join_all(
+        Photo::list_trashed(&data.db)
+            .await?
+            .into_iter()
+            .map(|photo| {
+                let storage = data.storage.clone();
+                async move {
+                    photo
+                        .photo_to_proto_url(&storage, &PhotoQuality::W400)
+                        .await
+                }
+            }),
+    )
+    .await
+    .into_iter()
+    .collect::<Result<Vec<_>, DalError>>()
+    .map_err(|e| match e {
+        DalError::Storage(e) => Error::from(e),
+        DalError::Db(e) => Error::from(e),
+    })?;
+
+    Ok(Payload(ListTrashResponse { albums, photos }))
+}
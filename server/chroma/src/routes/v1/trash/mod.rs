@@ -0,0 +1,19 @@
+use actix_web::web;
+use actix_web::web::ServiceConfig;
+
+use crate::routes::routable::Routable;
+
+mod list;
+mod restore;
+
+pub struct Router;
+
+impl Routable for Router {
+    fn configure(config: &mut ServiceConfig) {
+        config.service(
+            web::scope("/trash")
+                .route("/list", web::get().to(list::list))
+                .route("/restore", web::post().to(restore::restore)),
+        );
+    }
+}
@@ -0,0 +1,82 @@
+use actix_multiresponse::Payload;
+
+use dal::database::{Album, AuditAction, AuditLogEntry, AuditTarget, Photo};
+use proto::restore_from_trash_request::Item;
+use proto::RestoreFromTrashRequest;
+
+use crate::routes::appdata::WebData;
+use crate::routes::authorization::Authorization;
+use crate::routes::empty::Empty;
+use crate::routes::error::{Error, WebResult};
+
+/// Restore an album or a photo from the trash.
+///
+/// Only admins may restore items from the trash.
+///
+/// # Errors
+///
+/// - If the album or photo is not in the trash
+/// - If the photo's album is in the trash, the album must be restored first
+/// - If something went wrong
+pub async fn restore(
+    auth: Authorization,
+    data: WebData,
+    payload: Payload<RestoreFromTrashRequest>,
+) -> WebResult<Empty> {
+    if !auth.is_admin {
+        return Err(Error::Forbidden);
+    }
+
+    match &payload.item {
+        Some(Item::AlbumId(album_id)) => {
+            let mut album = Album::get_trashed_by_id(&data.db, album_id)
+                .await?
+                .ok_or(Error::NotFound)?;
+
+            let mut tx = data.db.begin().await?;
+            album.restore(&mut tx).await?;
+            AuditLogEntry::record(
+                &mut tx,
+                Some(&auth.to_dal_user_type()),
+                AuditAction::AlbumRestored,
+                AuditTarget::Album(album.id),
+                None,
+                None,
+            )
+            .await?;
+            tx.commit().await?;
+        }
+        Some(Item::PhotoId(photo_id)) => {
+            let mut photo = Photo::get_trashed_by_id(&data.db, photo_id)
+                .await?
+                .ok_or(Error::NotFound)?;
+
+            if Album::get_by_id(&data.db, &photo.album_id).await?.is_none() {
+                return Err(Error::BadRequest(format!(
+                    "The album of photo with ID '{photo_id}' is in the trash, restore album with ID '{}' first",
+                    photo.album_id
+                )));
+            }
+
+            let mut tx = data.db.begin().await?;
+            photo.restore(&mut tx).await?;
+            AuditLogEntry::record(
+                &mut tx,
+                Some(&auth.to_dal_user_type()),
+                AuditAction::PhotoRestored,
+                AuditTarget::Photo(photo.id),
+                None,
+                None,
+            )
+            .await?;
+            tx.commit().await?;
+        }
+        None => {
+            return Err(Error::BadRequest(
+                "One of 'albumId' or 'photoId' must be provided".into(),
+            ))
+        }
+    }
+
+    Ok(Empty)
+}
@@ -4,6 +4,7 @@ use proto::GetAvailableScopesResponse;
 
 use crate::routes::authorization::Authorization;
 use crate::routes::error::{Error, WebResult};
+use crate::routes::scope::Scope;
 
 pub async fn available_scopes(
     auth: Authorization,
@@ -13,15 +14,6 @@ pub async fn available_scopes(
     }
 
     Ok(Payload(GetAvailableScopesResponse {
-        scopes: vec![
-            // Albums
-            "nl.svsticky.chroma.album.create".into(),
-            "nl.svsticky.chroma.album.update".into(),
-            "nl.svsticky.chroma.album.delete".into(),
-            "nl.svsticky.chroma.album.list.draft".into(),
-            // Photos
-            "nl.svsticky.chroma.photo.create".into(),
-            "nl.svsticky.chroma.photo.delete".into(),
-        ],
+        scopes: Scope::ALL.iter().map(Scope::to_string).collect(),
     }))
 }
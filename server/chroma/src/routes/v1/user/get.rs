@@ -2,7 +2,7 @@ use actix_multiresponse::Payload;
 use actix_web::web;
 use serde::Deserialize;
 
-use dal::database::User;
+use dal::database::{Role, SyncedGrants, User};
 use proto::GetUserResponse;
 
 use crate::routes::appdata::WebData;
@@ -29,6 +29,19 @@ pub async fn get(
 
     let scopes = user.get_chroma_scopes().await?;
 
+    let mut roles = Vec::new();
+    for role in Role::list_for_user(&data.db, user.koala_id).await? {
+        roles.push(role.to_proto(&data.db).await?);
+    }
+
+    let synced_scopes = SyncedGrants::get_for_user(&data.db, user.koala_id)
+        .await?
+        .scopes;
+    let mut synced_roles = Vec::new();
+    for role in SyncedGrants::list_roles(&data.db, user.koala_id).await? {
+        synced_roles.push(role.to_proto(&data.db).await?);
+    }
+
     Ok(Payload(GetUserResponse {
         user: Some(proto::User {
             id: user.koala_id,
@@ -40,7 +53,11 @@ pub async fn get(
                 name: f.scope,
                 granted_by: f.granted_by,
                 granted_at: f.granted_at,
+                expires_at: f.expires_at,
             })
             .collect::<Vec<_>>(),
+        roles,
+        synced_scopes,
+        synced_roles,
     }))
 }
@@ -0,0 +1,46 @@
+use actix_multiresponse::Payload;
+use actix_web::web;
+
+use dal::database::Session;
+use proto::LogoutRequest;
+
+use crate::routes::appdata::{SessionIdCache, WebData};
+use crate::routes::authorization::{Authorization, AuthorizedUser};
+use crate::routes::empty::Empty;
+use crate::routes::error::{Error, WebResult};
+
+/// End the session the request was made with, or all sessions of the user.
+///
+/// # Errors
+///
+/// - If the request was made with a service token
+/// - If something went wrong
+pub async fn logout(
+    auth: Authorization,
+    data: WebData,
+    session_cache: web::Data<SessionIdCache>,
+    payload: Payload<LogoutRequest>,
+) -> WebResult<Empty> {
+    let (koala_id, session_id) = match auth.user {
+        AuthorizedUser::Koala {
+            koala_id,
+            session_id,
+        } => (koala_id, session_id),
+        AuthorizedUser::ApiKey { .. } => {
+            return Err(Error::BadRequest(
+                "This endpoint does not support service accounts".into(),
+            ))
+        }
+    };
+
+    if payload.all_sessions {
+        for session_id in Session::delete_all_for_user(&data.db, koala_id).await? {
+            session_cache.invalidate(&session_id).await;
+        }
+    } else {
+        Session::delete(&data.db, &session_id).await?;
+        session_cache.invalidate(&session_id).await;
+    }
+
+    Ok(Empty)
+}
@@ -6,6 +6,9 @@ use crate::routes::routable::Routable;
 mod available_scopes;
 mod get;
 mod list;
+mod logout;
+mod roles;
+mod sessions;
 mod update;
 
 pub struct Router;
@@ -17,6 +20,9 @@ impl Routable for Router {
                 .route("", web::get().to(get::get))
                 .route("", web::patch().to(update::update))
                 .route("/list", web::get().to(list::list))
+                .route("/logout", web::post().to(logout::logout))
+                .route("/roles", web::patch().to(roles::update_roles))
+                .route("/sessions", web::get().to(sessions::sessions))
                 .route(
                     "/available-scopes",
                     web::get().to(available_scopes::available_scopes),
@@ -0,0 +1,97 @@
+use std::collections::HashSet;
+
+use actix_multiresponse::Payload;
+use actix_web::web;
+
+use dal::database::{AuditAction, AuditLogEntry, AuditTarget, Role, User};
+use proto::UpdateUserRolesRequest;
+
+use crate::routes::appdata::{SessionIdCache, WebData};
+use crate::routes::authorization::{invalidate_cached_user, Authorization, AuthorizedUser};
+use crate::routes::empty::Empty;
+use crate::routes::error::{Error, WebResult};
+
+/// Replace the roles assigned to a user.
+/// Scopes granted to the user directly are not affected.
+///
+/// # Errors
+///
+/// - If the user is not an admin
+/// - If the request was made with an API key
+/// - If the user or any of the roles does not exist
+/// - If something went wrong
+pub async fn update_roles(
+    data: WebData,
+    auth: Authorization,
+    session_cache: web::Data<SessionIdCache>,
+    payload: Payload<UpdateUserRolesRequest>,
+) -> WebResult<Empty> {
+    if !auth.is_admin {
+        return Err(Error::Forbidden);
+    }
+
+    let granted_by = match auth.user {
+        AuthorizedUser::Koala { koala_id, .. } => koala_id,
+        AuthorizedUser::ApiKey { .. } => {
+            return Err(Error::BadRequest(
+                "This endpoint does not support service accounts".into(),
+            ))
+        }
+    };
+
+    let grantee = User::get_by_id(&data.db, payload.user_id)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    let existing_roles = Role::list_for_user(&data.db, grantee.koala_id).await?;
+    let new_role_ids = payload.role_ids.iter().copied().collect::<HashSet<_>>();
+
+    let mut to_add = Vec::new();
+    for id in &new_role_ids {
+        if existing_roles.iter().any(|role| role.id.eq(id)) {
+            continue;
+        }
+
+        let role = Role::get_by_id(&data.db, *id)
+            .await?
+            .ok_or_else(|| Error::BadRequest(format!("Role with ID '{id}' does not exist")))?;
+        to_add.push(role);
+    }
+
+    let actor = auth.to_dal_user_type();
+    let mut tx = data.db.begin().await?;
+
+    for role in &to_add {
+        role.assign(&mut tx, grantee.koala_id, granted_by).await?;
+        AuditLogEntry::record(
+            &mut tx,
+            Some(&actor),
+            AuditAction::RoleGranted,
+            AuditTarget::User(grantee.koala_id),
+            None,
+            Some(role.name.clone()),
+        )
+        .await?;
+    }
+
+    for role in existing_roles
+        .iter()
+        .filter(|role| !new_role_ids.contains(&role.id))
+    {
+        role.unassign(&mut tx, grantee.koala_id).await?;
+        AuditLogEntry::record(
+            &mut tx,
+            Some(&actor),
+            AuditAction::RoleRevoked,
+            AuditTarget::User(grantee.koala_id),
+            Some(role.name.clone()),
+            None,
+        )
+        .await?;
+    }
+
+    tx.commit().await?;
+    invalidate_cached_user(&session_cache, grantee.koala_id);
+
+    Ok(Empty)
+}
@@ -0,0 +1,47 @@
+use actix_multiresponse::Payload;
+
+use dal::database::Session;
+use proto::ListUserSessionsResponse;
+
+use crate::routes::appdata::WebData;
+use crate::routes::authorization::{Authorization, AuthorizedUser};
+use crate::routes::error::{Error, WebResult};
+
+/// List the active sessions of the requesting user.
+/// The last seen timestamp is updated at most once per session cache period.
+///
+/// # Errors
+///
+/// - If the request was made with a service token
+/// - If something went wrong
+pub async fn sessions(
+    auth: Authorization,
+    data: WebData,
+) -> WebResult<Payload<ListUserSessionsResponse>> {
+    let (koala_id, session_id) = match auth.user {
+        AuthorizedUser::Koala {
+            koala_id,
+            session_id,
+        } => (koala_id, session_id),
+        AuthorizedUser::ApiKey { .. } => {
+            return Err(Error::BadRequest(
+                "This endpoint does not support service accounts".into(),
+            ))
+        }
+    };
+
+    let sessions = Session::list_for_user(&data.db, koala_id).await?;
+
+    Ok(Payload(ListUserSessionsResponse {
+        sessions: sessions
+            .into_iter()
+            .map(|f| proto::UserSession {
+                current: f.id.eq(&session_id),
+                created_at: f.created_at,
+                last_seen_at: f.last_seen_at,
+                expires_at: f.expires_at,
+                user_agent: f.user_agent,
+            })
+            .collect::<Vec<_>>(),
+    }))
+}
@@ -1,18 +1,22 @@
 use std::collections::HashSet;
 
 use actix_multiresponse::Payload;
+use actix_web::web;
+use time::OffsetDateTime;
 
-use dal::database::User;
+use dal::database::{AuditAction, AuditLogEntry, AuditTarget, User};
 use proto::UpdateUserRequest;
 
-use crate::routes::appdata::WebData;
-use crate::routes::authorization::{Authorization, AuthorizedUser};
+use crate::routes::appdata::{SessionIdCache, WebData};
+use crate::routes::authorization::{invalidate_cached_user, Authorization, AuthorizedUser};
 use crate::routes::empty::Empty;
 use crate::routes::error::{Error, WebResult};
+use crate::routes::scope::Scope;
 
 pub async fn update(
     data: WebData,
     auth: Authorization,
+    session_cache: web::Data<SessionIdCache>,
     payload: Payload<UpdateUserRequest>,
 ) -> WebResult<Empty> {
     if !auth.is_admin {
@@ -20,8 +24,8 @@ pub async fn update(
     }
 
     let granted_by_id = match auth.user {
-        AuthorizedUser::Koala { koala_id } => koala_id,
-        AuthorizedUser::Service { .. } => {
+        AuthorizedUser::Koala { koala_id, .. } => koala_id,
+        AuthorizedUser::ApiKey { .. } => {
             return Err(Error::BadRequest(
                 "This endpoint does not support service accounts".into(),
             ))?
@@ -34,11 +38,10 @@ pub async fn update(
         .ok_or(Error::NotFound)?;
 
     // Get the current list of scopes
-    let existing_scopes = grantee
-        .get_chroma_scopes()
-        .await?
-        .into_iter()
-        .map(|f| f.scope)
+    let mut current_scopes = grantee.get_chroma_scopes().await?;
+    let existing_scopes = current_scopes
+        .iter()
+        .map(|f| f.scope.clone())
         .collect::<HashSet<_>>();
 
     // Stash all new scopes in a HashSet
@@ -58,18 +61,108 @@ pub async fn update(
         .difference(&existing_scopes)
         .collect::<HashSet<_>>();
 
+    // Scopes which are no longer known may still be removed
+    if let Some(scope) = to_add.iter().find(|scope| !Scope::is_valid_grant(scope)) {
+        return Err(Error::BadRequest(format!("Unknown scope '{scope}'")));
+    }
+
+    let now = OffsetDateTime::now_utc().unix_timestamp();
+    for (scope, expires_at) in &payload.scope_expires_at {
+        if !new_scopes.contains(scope) {
+            return Err(Error::BadRequest(format!(
+                "Expiry provided for scope '{scope}', which is not granted"
+            )));
+        }
+
+        if *expires_at != NO_EXPIRY && *expires_at <= now {
+            return Err(Error::BadRequest(format!(
+                "Expiry date of scope '{scope}' is in the past"
+            )));
+        }
+    }
+
     // User who is granting the scopes
     let granted_by = User::get_by_id(&data.db, granted_by_id)
         .await?
         .ok_or(Error::NotFound)?;
 
+    let actor = auth.to_dal_user_type();
+    let mut tx = data.db.begin().await?;
+
     for scope in &to_add {
-        grantee.add_scope(scope, &granted_by).await?;
+        let expires_at = requested_expiry(&payload, scope).flatten();
+        grantee
+            .add_scope(&mut tx, scope, &granted_by, expires_at)
+            .await?;
+        AuditLogEntry::record(
+            &mut tx,
+            Some(&actor),
+            AuditAction::ScopeGranted,
+            AuditTarget::User(grantee.koala_id),
+            None,
+            Some(describe_grant(scope, expires_at)),
+        )
+        .await?;
+    }
+
+    // Scopes which are kept only get a new expiry if one was provided
+    for scope in current_scopes
+        .iter_mut()
+        .filter(|f| new_scopes.contains(&f.scope))
+    {
+        let expires_at = match requested_expiry(&payload, &scope.scope) {
+            Some(v) if v != scope.expires_at => v,
+            _ => continue,
+        };
+
+        let before = describe_grant(&scope.scope, scope.expires_at);
+        scope.set_expires_at(&mut tx, expires_at).await?;
+        AuditLogEntry::record(
+            &mut tx,
+            Some(&actor),
+            AuditAction::ScopeExpiryChanged,
+            AuditTarget::User(grantee.koala_id),
+            Some(before),
+            Some(describe_grant(&scope.scope, expires_at)),
+        )
+        .await?;
     }
 
     for scope in &to_remove {
-        grantee.remove_scope_by_name(scope).await?;
+        grantee.remove_scope_by_name(&mut tx, scope).await?;
+        AuditLogEntry::record(
+            &mut tx,
+            Some(&actor),
+            AuditAction::ScopeRevoked,
+            AuditTarget::User(grantee.koala_id),
+            Some(scope.to_string()),
+            None,
+        )
+        .await?;
     }
 
+    tx.commit().await?;
+    invalidate_cached_user(&session_cache, grantee.koala_id);
+
     Ok(Empty)
 }
+
+/// The value of `scope_expires_at` which removes the expiry of a scope
+const NO_EXPIRY: i64 = 0;
+
+/// The expiry requested for the scope.
+/// `None` if no expiry was provided, `Some(None)` if the scope should not expire.
+fn requested_expiry(payload: &UpdateUserRequest, scope: &str) -> Option<Option<i64>> {
+    payload
+        .scope_expires_at
+        .get(scope)
+        .map(|expires_at| Some(*expires_at).filter(|v| *v != NO_EXPIRY))
+}
+
+/// Describe a grant for the audit log
+fn describe_grant(scope: &str, expires_at: Option<i64>) -> String {
+    match expires_at {
+        Some(expires_at) => format!("{scope} until {expires_at}"),
+        None => scope.to_string(),
+    }
+}
@@ -0,0 +1,43 @@
+use std::time::Duration;
+
+use tracing::{info, warn};
+
+use dal::consistency::ConsistencyReport;
+use dal::DalError;
+
+use crate::routes::appdata::AppData;
+
+/// How often the consistency between the database and storage is checked
+const CHECK_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Periodically check the consistency between the database and storage.
+/// Discrepancies are only reported, repairing them is left to an admin.
+pub async fn run(data: AppData) {
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+
+        if let Err(e) = check(&data).await {
+            warn!("Failed to check storage consistency: {e}");
+        }
+    }
+}
+
+async fn check(data: &AppData) -> Result<(), DalError> {
+    let report = ConsistencyReport::check(&data.db, &data.storage).await?;
+
+    if report.is_consistent() {
+        info!("Storage is consistent with the database");
+    } else {
+        warn!(
+            "Storage is inconsistent with the database: {} orphaned objects, {} unknown objects, {} photos missing their original, {} stale URLs, {} missing cover photos",
+            report.orphaned_objects.len(),
+            report.unknown_objects.len(),
+            report.photos_missing_original.len(),
+            report.stale_urls.len(),
+            report.incidents.len(),
+        );
+    }
+
+    Ok(())
+}
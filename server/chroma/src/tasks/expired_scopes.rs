@@ -0,0 +1,49 @@
+use std::time::Duration;
+
+use tracing::{info, warn};
+
+use dal::database::{AuditAction, AuditLogEntry, AuditTarget, ChromaScope, DbResult};
+
+use crate::routes::appdata::AppData;
+
+/// How often expired scopes are removed
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Periodically remove scopes whose grant has expired.
+/// Expired scopes are already ignored when checking authorization,
+/// this only keeps them from showing up as granted once they are past their expiry.
+pub async fn run(data: AppData) {
+    let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+    loop {
+        interval.tick().await;
+
+        match sweep(&data).await {
+            Ok(0) => {}
+            Ok(count) => info!("Removed {count} expired scopes"),
+            Err(e) => warn!("Failed to remove expired scopes: {e}"),
+        }
+    }
+}
+
+/// Remove expired scopes, recording the removal of each scope in the audit log.
+/// Returns the number of removed scopes.
+async fn sweep(data: &AppData) -> DbResult<usize> {
+    let mut tx = data.db.begin().await?;
+
+    let expired = ChromaScope::delete_expired(&mut tx).await?;
+    for (koala_id, scope) in &expired {
+        AuditLogEntry::record(
+            &mut tx,
+            None,
+            AuditAction::ScopeExpired,
+            AuditTarget::User(*koala_id),
+            Some(scope.clone()),
+            None,
+        )
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(expired.len())
+}
@@ -0,0 +1,56 @@
+use std::time::Duration;
+
+use tracing::{info, warn};
+
+use dal::database::{AuditAction, AuditLogEntry, AuditTarget, DbResult, LoginAttempt, Session};
+
+use crate::routes::appdata::AppData;
+
+/// How often expired sessions are removed
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Periodically remove sessions and login attempts which have expired.
+/// Expired sessions are already rejected when they are used,
+/// this only prevents sessions which are never used again,
+/// and logins which are never completed, from piling up.
+pub async fn run(data: AppData) {
+    let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+    loop {
+        interval.tick().await;
+
+        match sweep_sessions(&data).await {
+            Ok(0) => {}
+            Ok(count) => info!("Removed {count} expired sessions"),
+            Err(e) => warn!("Failed to remove expired sessions: {e}"),
+        }
+
+        match LoginAttempt::delete_expired(&data.db).await {
+            Ok(0) => {}
+            Ok(count) => info!("Removed {count} expired login attempts"),
+            Err(e) => warn!("Failed to remove expired login attempts: {e}"),
+        }
+    }
+}
+
+/// Remove expired sessions, recording the removal of each session in the audit log.
+/// Returns the number of removed sessions.
+async fn sweep_sessions(data: &AppData) -> DbResult<usize> {
+    let mut tx = data.db.begin().await?;
+
+    let koala_ids = Session::delete_expired(&mut tx).await?;
+    for koala_id in &koala_ids {
+        AuditLogEntry::record(
+            &mut tx,
+            None,
+            AuditAction::SessionExpired,
+            AuditTarget::User(*koala_id),
+            None,
+            None,
+        )
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(koala_ids.len())
+}
@@ -0,0 +1,49 @@
+use std::time::Duration;
+
+use time::OffsetDateTime;
+use tracing::{info, warn};
+
+use dal::database::{DbResult, User};
+
+use crate::routes::appdata::AppData;
+use crate::routes::group_sync::{self, SYNC_INTERVAL_SECS};
+
+/// Periodically sync the grants of users from their groups,
+/// so users who leave a group lose its grants without having to make a request first.
+/// Users whose access token has expired are synced on their next request,
+/// once their tokens have been refreshed.
+pub async fn run(data: AppData) {
+    if data.group_mappings.is_empty() {
+        return;
+    }
+
+    let mut interval = tokio::time::interval(Duration::from_secs(SYNC_INTERVAL_SECS as u64));
+    loop {
+        interval.tick().await;
+
+        match sync_all(&data).await {
+            Ok(0) => {}
+            Ok(count) => info!("Synced changed grants of {count} users from their groups"),
+            Err(e) => warn!("Failed to sync grants from groups: {e}"),
+        }
+    }
+}
+
+/// Sync the grants of all users which are due to be synced.
+/// Returns the number of users whose grants changed.
+async fn sync_all(data: &AppData) -> DbResult<usize> {
+    let now = OffsetDateTime::now_utc().unix_timestamp();
+
+    let mut changed = 0;
+    for user in User::list(&data.db).await? {
+        if user.oauth_expires_at <= now {
+            continue;
+        }
+
+        if group_sync::sync_if_due(data, user.koala_id, &user.access_token).await? {
+            changed += 1;
+        }
+    }
+
+    Ok(changed)
+}
@@ -0,0 +1,21 @@
+use crate::routes::appdata::AppData;
+
+mod consistency_check;
+mod expired_scopes;
+mod expired_sessions;
+mod group_sync;
+mod prune_ratelimits;
+mod purge_trash;
+mod storage_tombstones;
+
+/// Spawn all background tasks.
+/// The tasks keep running for as long as the server is running.
+pub fn spawn_all(app_data: &AppData) {
+    tokio::spawn(consistency_check::run(app_data.clone()));
+    tokio::spawn(expired_scopes::run(app_data.clone()));
+    tokio::spawn(expired_sessions::run(app_data.clone()));
+    tokio::spawn(group_sync::run(app_data.clone()));
+    tokio::spawn(prune_ratelimits::run(app_data.clone()));
+    tokio::spawn(purge_trash::run(app_data.clone()));
+    tokio::spawn(storage_tombstones::run(app_data.clone()));
+}
@@ -0,0 +1,16 @@
+use std::time::Duration;
+
+use crate::routes::appdata::AppData;
+
+/// How often the ratelimits are pruned
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Periodically forget users and IP addresses which have not made ratelimited requests recently.
+/// Their state would be reset anyway, this only prevents it from piling up.
+pub async fn run(data: AppData) {
+    let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+    loop {
+        interval.tick().await;
+        data.ratelimits.prune();
+    }
+}
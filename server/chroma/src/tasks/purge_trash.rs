@@ -0,0 +1,67 @@
+use std::time::Duration;
+
+use time::OffsetDateTime;
+use tracing::{info, warn};
+
+use dal::database::{Album, AuditAction, AuditLogEntry, AuditTarget, Photo};
+use dal::DalError;
+
+use crate::routes::appdata::AppData;
+
+/// How often the trash is checked for items past their retention period
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Periodically delete albums and photos which have been in the trash
+/// for longer than the configured retention period.
+pub async fn run(data: AppData) {
+    let mut interval = tokio::time::interval(PURGE_INTERVAL);
+    loop {
+        interval.tick().await;
+
+        if let Err(e) = purge(&data).await {
+            warn!("Failed to purge trash: {e}");
+        }
+    }
+}
+
+async fn purge(data: &AppData) -> Result<(), DalError> {
+    let cutoff =
+        OffsetDateTime::now_utc().unix_timestamp() - data.config.trash_retention().as_secs() as i64;
+
+    for album in Album::list_trashed_before(&data.db, cutoff).await? {
+        info!("Permanently deleting album '{}' from the trash", album.id);
+        // The stored objects are deleted by the storage tombstone worker
+        let id = album.id.clone();
+        let mut tx = data.db.begin().await?;
+        album.delete(&mut tx).await?;
+        AuditLogEntry::record(
+            &mut tx,
+            None,
+            AuditAction::AlbumDeleted,
+            AuditTarget::Album(id),
+            None,
+            None,
+        )
+        .await?;
+        tx.commit().await?;
+    }
+
+    for photo in Photo::list_trashed_before(&data.db, cutoff).await? {
+        info!("Permanently deleting photo '{}' from the trash", photo.id);
+        let id = photo.id.clone();
+        let mut tx = data.db.begin().await?;
+        photo.delete(&mut tx).await?;
+        AuditLogEntry::record(
+            &mut tx,
+            None,
+            AuditAction::PhotoDeleted,
+            AuditTarget::Photo(id),
+            None,
+            None,
+        )
+        .await?;
+        tx.commit().await?;
+    }
+
+    Ok(())
+}
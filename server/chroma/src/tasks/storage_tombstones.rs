@@ -0,0 +1,47 @@
+use std::time::Duration;
+
+use tracing::{debug, warn};
+
+use dal::database::StorageTombstone;
+use dal::DalError;
+
+use crate::routes::appdata::AppData;
+
+/// How often pending tombstones are processed
+const PROCESS_INTERVAL: Duration = Duration::from_secs(60);
+/// The maximum number of tombstones processed per interval
+const BATCH_SIZE: i64 = 256;
+
+/// Periodically delete the stored objects of photos whose metadata has been removed.
+/// Failed deletions are recorded on the tombstone and retried later.
+pub async fn run(data: AppData) {
+    let mut interval = tokio::time::interval(PROCESS_INTERVAL);
+    loop {
+        interval.tick().await;
+
+        if let Err(e) = process(&data).await {
+            warn!("Failed to process storage tombstones: {e}");
+        }
+    }
+}
+
+async fn process(data: &AppData) -> Result<(), DalError> {
+    for mut tombstone in StorageTombstone::list_pending(&data.db, BATCH_SIZE).await? {
+        match data.storage.delete_photo(&tombstone.object_key).await {
+            Ok(_) => {
+                debug!("Deleted object '{}'", tombstone.object_key);
+                tombstone.resolve(&data.db).await?;
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to delete object '{}' (attempt {}): {e}",
+                    tombstone.object_key,
+                    tombstone.attempts + 1
+                );
+                tombstone.record_failure(&data.db, &e.to_string()).await?;
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,5 @@
+ALTER TABLE album_metadata
+    ADD COLUMN deleted_at BIGINT DEFAULT NULL;
+
+ALTER TABLE photo_metadata
+    ADD COLUMN deleted_at BIGINT DEFAULT NULL;
\ No newline at end of file
@@ -0,0 +1,9 @@
+CREATE TABLE storage_tombstones (
+    photo_id VARCHAR(32) NOT NULL,
+    quality photo_quality NOT NULL,
+    created_at BIGINT NOT NULL,
+    attempts INT NOT NULL DEFAULT 0,
+    last_attempt_at BIGINT DEFAULT NULL,
+    last_error TEXT DEFAULT NULL,
+    PRIMARY KEY (photo_id, quality)
+);
\ No newline at end of file
@@ -0,0 +1,7 @@
+CREATE TABLE storage_incidents (
+    id BIGSERIAL PRIMARY KEY,
+    album_id VARCHAR(32) NOT NULL,
+    photo_id VARCHAR(32) NOT NULL,
+    quality photo_quality NOT NULL,
+    created_at BIGINT NOT NULL
+);
\ No newline at end of file
@@ -0,0 +1,21 @@
+CREATE TABLE photo_objects (
+    photo_id VARCHAR(32) NOT NULL,
+    quality photo_quality NOT NULL,
+    object_key TEXT NOT NULL UNIQUE,
+    created_at BIGINT NOT NULL,
+    PRIMARY KEY (photo_id, quality)
+);
+
+-- Tombstones and incidents refer to objects by key,
+-- existing rows refer to objects stored in the legacy layout
+ALTER TABLE storage_tombstones ADD COLUMN object_key TEXT;
+UPDATE storage_tombstones SET object_key = photo_id || '_' || quality::TEXT;
+ALTER TABLE storage_tombstones DROP CONSTRAINT storage_tombstones_pkey;
+ALTER TABLE storage_tombstones DROP COLUMN photo_id;
+ALTER TABLE storage_tombstones DROP COLUMN quality;
+ALTER TABLE storage_tombstones ALTER COLUMN object_key SET NOT NULL;
+ALTER TABLE storage_tombstones ADD PRIMARY KEY (object_key);
+
+ALTER TABLE storage_incidents ADD COLUMN object_key TEXT;
+UPDATE storage_incidents SET object_key = photo_id || '_' || quality::TEXT;
+ALTER TABLE storage_incidents ALTER COLUMN object_key SET NOT NULL;
\ No newline at end of file
@@ -0,0 +1,11 @@
+ALTER TABLE user_sessions
+    ADD COLUMN created_at BIGINT DEFAULT NULL,
+    ADD COLUMN last_seen_at BIGINT DEFAULT NULL,
+    ADD COLUMN user_agent TEXT DEFAULT NULL;
+
+-- Sessions were valid for 15 days after their creation
+UPDATE user_sessions SET created_at = expires_at - 15 * 24 * 60 * 60;
+
+ALTER TABLE user_sessions ALTER COLUMN created_at SET NOT NULL;
+
+CREATE INDEX idx_user_sessions_koala_id ON user_sessions(koala_id);
\ No newline at end of file
@@ -0,0 +1,33 @@
+CREATE TABLE api_keys (
+    id SERIAL NOT NULL,
+    key_hash VARCHAR(64) NOT NULL,
+    description VARCHAR(128) NOT NULL,
+    is_admin BOOLEAN NOT NULL,
+    created_by INT DEFAULT NULL,
+    created_at BIGINT NOT NULL,
+    expires_at BIGINT DEFAULT NULL,
+    last_used_at BIGINT DEFAULT NULL,
+    revoked_at BIGINT DEFAULT NULL,
+    PRIMARY KEY (id),
+    UNIQUE (key_hash),
+    FOREIGN KEY (created_by) REFERENCES users(koala_id)
+);
+
+CREATE TABLE api_key_scopes (
+    api_key_id INT NOT NULL,
+    scope VARCHAR(128) NOT NULL,
+    PRIMARY KEY (api_key_id, scope),
+    FOREIGN KEY (api_key_id) REFERENCES api_keys(id)
+);
+
+-- Service tokens which have been used keep their ID, so albums keep referring to them.
+-- They had access to everything, so they become admin keys.
+-- Tokens are hashed as UTF-8, casting to BYTEA would interpret backslashes as escapes.
+-- Tokens which were never used are migrated on startup, as only the configuration knows them.
+INSERT INTO api_keys (id, key_hash, description, is_admin, created_at)
+    SELECT id, encode(sha256(convert_to(service_token, 'UTF8')), 'hex'), 'Service token', TRUE, EXTRACT(EPOCH FROM NOW())::BIGINT
+    FROM service_token_user;
+
+SELECT setval(pg_get_serial_sequence('api_keys', 'id'), GREATEST((SELECT MAX(id) FROM api_keys), 1));
+
+DROP TABLE service_token_user;
\ No newline at end of file
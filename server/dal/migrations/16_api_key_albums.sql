@@ -0,0 +1,13 @@
+-- Whether the key is restricted to the albums in `api_key_albums`.
+-- Kept apart from the bindings, so a key remains restricted when its albums are deleted.
+ALTER TABLE api_keys ADD COLUMN album_bound BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE api_key_albums (
+    api_key_id INT NOT NULL,
+    album_id VARCHAR(32) NOT NULL,
+    PRIMARY KEY (api_key_id, album_id),
+    FOREIGN KEY (api_key_id) REFERENCES api_keys(id),
+    FOREIGN KEY (album_id) REFERENCES album_metadata(id)
+);
+
+CREATE INDEX idx_api_key_albums_album_id ON api_key_albums(album_id);
\ No newline at end of file
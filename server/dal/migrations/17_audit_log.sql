@@ -0,0 +1,26 @@
+CREATE TABLE audit_log (
+    id BIGSERIAL NOT NULL,
+    created_at BIGINT NOT NULL,
+    actor_id INT DEFAULT NULL,
+    actor_type user_type DEFAULT NULL,
+    action VARCHAR(64) NOT NULL,
+    target_type VARCHAR(32) NOT NULL,
+    target_id VARCHAR(64) NOT NULL,
+    before TEXT DEFAULT NULL,
+    after TEXT DEFAULT NULL,
+    PRIMARY KEY (id)
+);
+
+CREATE INDEX idx_audit_log_created_at ON audit_log(created_at);
+CREATE INDEX idx_audit_log_target ON audit_log(target_type, target_id);
+CREATE INDEX idx_audit_log_actor ON audit_log(actor_type, actor_id);
+
+CREATE FUNCTION audit_log_append_only() RETURNS TRIGGER AS $$
+BEGIN
+    RAISE EXCEPTION 'audit_log is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_log_append_only
+    BEFORE UPDATE OR DELETE ON audit_log
+    FOR EACH ROW EXECUTE FUNCTION audit_log_append_only();
\ No newline at end of file
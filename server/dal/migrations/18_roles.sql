@@ -0,0 +1,28 @@
+CREATE TABLE roles (
+    id SERIAL NOT NULL,
+    name VARCHAR(64) NOT NULL,
+    description VARCHAR(256) NOT NULL,
+    created_at BIGINT NOT NULL,
+    PRIMARY KEY (id),
+    UNIQUE (name)
+);
+
+CREATE TABLE role_scopes (
+    role_id INT NOT NULL,
+    scope VARCHAR(128) NOT NULL,
+    PRIMARY KEY (role_id, scope),
+    FOREIGN KEY (role_id) REFERENCES roles(id)
+);
+
+CREATE TABLE user_roles (
+    koala_id INT NOT NULL,
+    role_id INT NOT NULL,
+    granted_by INT NOT NULL,
+    granted_at BIGINT NOT NULL,
+    PRIMARY KEY (koala_id, role_id),
+    FOREIGN KEY (koala_id) REFERENCES users(koala_id),
+    FOREIGN KEY (role_id) REFERENCES roles(id),
+    FOREIGN KEY (granted_by) REFERENCES users(koala_id)
+);
+
+CREATE INDEX idx_user_roles_role_id ON user_roles(role_id);
\ No newline at end of file
@@ -0,0 +1,3 @@
+ALTER TABLE chroma_scopes ADD COLUMN expires_at BIGINT DEFAULT NULL;
+
+CREATE INDEX idx_chroma_scopes_expires_at ON chroma_scopes(expires_at) WHERE expires_at IS NOT NULL;
\ No newline at end of file
@@ -0,0 +1,19 @@
+-- Grants made because of a user's Koala groups, kept apart from grants made by admins
+CREATE TABLE synced_scopes (
+    koala_id INT NOT NULL,
+    scope VARCHAR(128) NOT NULL,
+    PRIMARY KEY (koala_id, scope),
+    FOREIGN KEY (koala_id) REFERENCES users(koala_id)
+);
+
+CREATE TABLE synced_roles (
+    koala_id INT NOT NULL,
+    role_id INT NOT NULL,
+    PRIMARY KEY (koala_id, role_id),
+    FOREIGN KEY (koala_id) REFERENCES users(koala_id),
+    FOREIGN KEY (role_id) REFERENCES roles(id)
+);
+
+CREATE INDEX idx_synced_roles_role_id ON synced_roles(role_id);
+
+ALTER TABLE users ADD COLUMN groups_synced_at BIGINT DEFAULT NULL;
\ No newline at end of file
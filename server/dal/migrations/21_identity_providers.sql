@@ -0,0 +1,21 @@
+-- Logins with an OpenID Connect provider which have started but not completed yet, by their state
+CREATE TABLE login_attempts (
+    state VARCHAR(64) NOT NULL,
+    pkce_verifier VARCHAR(128) NOT NULL,
+    nonce VARCHAR(64) NOT NULL,
+    created_at BIGINT NOT NULL,
+    PRIMARY KEY (state)
+);
+
+-- Users of OpenID Connect providers, whose subjects are not user IDs like those of Koala.
+-- They are given negative IDs, so they never collide with Koala IDs.
+CREATE SEQUENCE external_user_ids AS INT INCREMENT BY -1 MAXVALUE -1 START WITH -1;
+
+CREATE TABLE external_identities (
+    issuer VARCHAR(256) NOT NULL,
+    subject VARCHAR(256) NOT NULL,
+    koala_id INT NOT NULL,
+    PRIMARY KEY (issuer, subject),
+    UNIQUE (koala_id),
+    FOREIGN KEY (koala_id) REFERENCES users(koala_id)
+);
\ No newline at end of file
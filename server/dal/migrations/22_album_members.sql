@@ -0,0 +1,15 @@
+-- Users with a role in a specific album, on top of the scopes they hold for all albums.
+-- The role is stored by its name, see `AlbumRole`.
+CREATE TABLE album_members (
+    album_id VARCHAR(32) NOT NULL,
+    koala_id INT NOT NULL,
+    role VARCHAR(16) NOT NULL,
+    granted_by INT NOT NULL,
+    granted_at BIGINT NOT NULL,
+    PRIMARY KEY (album_id, koala_id),
+    FOREIGN KEY (album_id) REFERENCES album_metadata(id),
+    FOREIGN KEY (koala_id) REFERENCES users(koala_id),
+    FOREIGN KEY (granted_by) REFERENCES users(koala_id)
+);
+
+CREATE INDEX idx_album_members_koala_id ON album_members(koala_id);
\ No newline at end of file
@@ -0,0 +1,4 @@
+-- The time a photo was uploaded, its `created_at` is the time it was taken.
+-- Photos uploaded before this column existed have none.
+ALTER TABLE photo_metadata
+    ADD COLUMN uploaded_at BIGINT DEFAULT NULL;
@@ -0,0 +1,2 @@
+ALTER TABLE photo_metadata
+    ADD COLUMN position INT DEFAULT NULL;
\ No newline at end of file
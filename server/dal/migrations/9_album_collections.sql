@@ -0,0 +1,20 @@
+CREATE TABLE album_collections (
+    id VARCHAR(32) NOT NULL,
+    name VARCHAR(64) NOT NULL,
+    parent_id VARCHAR(32) DEFAULT NULL,
+    created_at BIGINT NOT NULL,
+    PRIMARY KEY (id),
+    FOREIGN KEY (parent_id) REFERENCES album_collections(id)
+);
+
+CREATE INDEX idx_album_collections_parent_id ON album_collections(parent_id);
+
+CREATE TABLE album_collection_members (
+    collection_id VARCHAR(32) NOT NULL,
+    album_id VARCHAR(32) NOT NULL,
+    PRIMARY KEY (collection_id, album_id),
+    FOREIGN KEY (collection_id) REFERENCES album_collections(id),
+    FOREIGN KEY (album_id) REFERENCES album_metadata(id)
+);
+
+CREATE INDEX idx_album_collection_members_album_id ON album_collection_members(album_id);
\ No newline at end of file
@@ -0,0 +1,451 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use time::OffsetDateTime;
+
+use crate::database::{Database, DbResult, PhotoQuality};
+
+/// All metadata of an instance which is included in an archive.
+/// Records mirror the database rows they were exported from, so ids and timestamps are preserved.
+/// Enums are stored by their database name.
+///
+/// Sessions and Koala tokens are not included, users have to log in again after an import.
+/// Grants synced from Koala groups are not included either, they are synced again on login.
+/// API keys are not included either, albums created by them keep referring to their IDs.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ArchiveMetadata {
+    pub users: Vec<UserRecord>,
+    #[serde(default)]
+    pub external_identities: Vec<ExternalIdentityRecord>,
+    pub scopes: Vec<ScopeRecord>,
+    // Archives made before roles existed do not contain them
+    #[serde(default)]
+    pub roles: Vec<RoleRecord>,
+    #[serde(default)]
+    pub role_scopes: Vec<RoleScopeRecord>,
+    #[serde(default)]
+    pub user_roles: Vec<UserRoleRecord>,
+    pub albums: Vec<AlbumRecord>,
+    // Archives made before album members existed do not contain them
+    #[serde(default)]
+    pub album_members: Vec<AlbumMemberRecord>,
+    pub photos: Vec<PhotoRecord>,
+    pub collections: Vec<CollectionRecord>,
+    pub collection_members: Vec<CollectionMemberRecord>,
+}
+
+/// An object of an archived photo which has been stored in this instance.
+/// Its key and URL are recorded along with the metadata.
+#[derive(Debug)]
+pub struct ImportedObject {
+    pub photo_id: String,
+    pub quality: PhotoQuality,
+    pub object_key: String,
+    pub url: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct UserRecord {
+    pub koala_id: i32,
+    pub name: String,
+    pub is_admin: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct ExternalIdentityRecord {
+    pub issuer: String,
+    pub subject: String,
+    pub koala_id: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct ScopeRecord {
+    pub koala_id: i32,
+    pub scope: String,
+    pub granted_by: i32,
+    pub granted_at: i64,
+    #[serde(default)]
+    pub expires_at: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct RoleRecord {
+    pub id: i32,
+    pub name: String,
+    pub description: String,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct RoleScopeRecord {
+    pub role_id: i32,
+    pub scope: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct UserRoleRecord {
+    pub koala_id: i32,
+    pub role_id: i32,
+    pub granted_by: i32,
+    pub granted_at: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct AlbumRecord {
+    pub id: String,
+    pub name: String,
+    pub created_at: i64,
+    pub cover_photo_id: Option<String>,
+    pub is_draft: bool,
+    pub created_by: i32,
+    pub created_by_type: String,
+    pub published_by: Option<i32>,
+    pub published_by_type: Option<String>,
+    pub published_at: Option<i64>,
+    pub deleted_at: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct AlbumMemberRecord {
+    pub album_id: String,
+    pub koala_id: i32,
+    pub role: String,
+    pub granted_by: i32,
+    pub granted_at: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct PhotoRecord {
+    pub id: String,
+    pub album_id: String,
+    pub created_at: i64,
+    pub position: Option<i32>,
+    pub deleted_at: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct CollectionRecord {
+    pub id: String,
+    pub name: String,
+    pub parent_id: Option<String>,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct CollectionMemberRecord {
+    pub collection_id: String,
+    pub album_id: String,
+}
+
+impl ArchiveMetadata {
+    /// Read all metadata to be archived, including trashed albums and photos.
+    ///
+    /// # Errors
+    ///
+    /// If a database error occurs
+    pub async fn export(db: &Database) -> DbResult<Self> {
+        Ok(Self {
+            users: sqlx::query_as("SELECT koala_id, name, is_admin FROM users ORDER BY koala_id")
+                .fetch_all(&**db)
+                .await?,
+            external_identities: sqlx::query_as(
+                "SELECT issuer, subject, koala_id FROM external_identities ORDER BY koala_id",
+            )
+            .fetch_all(&**db)
+            .await?,
+            scopes: sqlx::query_as(
+                "SELECT koala_id, scope, granted_by, granted_at, expires_at FROM chroma_scopes \
+                ORDER BY koala_id, scope",
+            )
+            .fetch_all(&**db)
+            .await?,
+            roles: sqlx::query_as("SELECT id, name, description, created_at FROM roles ORDER BY id")
+                .fetch_all(&**db)
+                .await?,
+            role_scopes: sqlx::query_as(
+                "SELECT role_id, scope FROM role_scopes ORDER BY role_id, scope",
+            )
+            .fetch_all(&**db)
+            .await?,
+            user_roles: sqlx::query_as(
+                "SELECT koala_id, role_id, granted_by, granted_at FROM user_roles \
+                ORDER BY koala_id, role_id",
+            )
+            .fetch_all(&**db)
+            .await?,
+            albums: sqlx::query_as(
+                "SELECT id, name, created_at, cover_photo_id, is_draft, \
+                created_by, created_by_type::TEXT AS created_by_type, \
+                published_by, published_by_type::TEXT AS published_by_type, published_at, deleted_at \
+                FROM album_metadata ORDER BY created_at",
+            )
+            .fetch_all(&**db)
+            .await?,
+            album_members: sqlx::query_as(
+                "SELECT album_id, koala_id, role, granted_by, granted_at FROM album_members \
+                ORDER BY album_id, koala_id",
+            )
+            .fetch_all(&**db)
+            .await?,
+            photos: sqlx::query_as(
+                "SELECT id, album_id, created_at, position, deleted_at FROM photo_metadata \
+                ORDER BY album_id, created_at",
+            )
+            .fetch_all(&**db)
+            .await?,
+            collections: sqlx::query_as(
+                "SELECT id, name, parent_id, created_at FROM album_collections ORDER BY created_at",
+            )
+            .fetch_all(&**db)
+            .await?,
+            collection_members: sqlx::query_as(
+                "SELECT collection_id, album_id FROM album_collection_members \
+                ORDER BY collection_id, album_id",
+            )
+            .fetch_all(&**db)
+            .await?,
+        })
+    }
+
+    /// The IDs of the archived photos which already exist in this instance, including trashed photos.
+    pub async fn existing_photo_ids(&self, db: &Database) -> DbResult<Vec<String>> {
+        let ids = self
+            .photos
+            .iter()
+            .map(|photo| photo.id.clone())
+            .collect::<Vec<_>>();
+
+        sqlx::query_scalar("SELECT id FROM photo_metadata WHERE id = ANY($1)")
+            .bind(&ids)
+            .fetch_all(&**db)
+            .await
+    }
+
+    /// Insert the archived metadata in a single transaction.
+    /// Users and scopes which already exist are left untouched,
+    /// roles, albums, photos and collections which already exist cause the import to fail.
+    /// Imported users have no Koala tokens, these are set when they log in.
+    ///
+    /// Stored objects are not part of the metadata, they should be uploaded before
+    /// the metadata is imported. Their keys are recorded in the same transaction,
+    /// so photos never refer to objects which have not been stored.
+    ///
+    /// # Errors
+    ///
+    /// - If a role, album, photo or collection already exists
+    /// - If a database error occurs
+    pub async fn import(&self, db: &Database, objects: &[ImportedObject]) -> DbResult<()> {
+        let mut tx = db.begin().await?;
+
+        for user in &self.users {
+            sqlx::query(
+                "INSERT INTO users (koala_id, access_token, refresh_token, expires_at, is_admin, name) \
+                VALUES ($1, '', '', 0, $2, $3) ON CONFLICT DO NOTHING",
+            )
+            .bind(user.koala_id)
+            .bind(user.is_admin)
+            .bind(&user.name)
+            .execute(&mut tx)
+            .await?;
+        }
+
+        for identity in &self.external_identities {
+            sqlx::query(
+                "INSERT INTO external_identities (issuer, subject, koala_id) \
+                VALUES ($1, $2, $3) ON CONFLICT DO NOTHING",
+            )
+            .bind(&identity.issuer)
+            .bind(&identity.subject)
+            .bind(identity.koala_id)
+            .execute(&mut tx)
+            .await?;
+        }
+
+        // Users of OpenID Connect providers keep their IDs, new users get IDs below them
+        if let Some(min_id) = self.users.iter().map(|user| user.koala_id).min() {
+            if min_id < 0 {
+                sqlx::query("SELECT setval('external_user_ids', LEAST($1, (SELECT last_value FROM external_user_ids)))")
+                    .bind(min_id)
+                    .execute(&mut tx)
+                    .await?;
+            }
+        }
+
+        for scope in &self.scopes {
+            sqlx::query(
+                "INSERT INTO chroma_scopes (koala_id, scope, granted_by, granted_at, expires_at) \
+                VALUES ($1, $2, $3, $4, $5) ON CONFLICT DO NOTHING",
+            )
+            .bind(scope.koala_id)
+            .bind(&scope.scope)
+            .bind(scope.granted_by)
+            .bind(scope.granted_at)
+            .bind(scope.expires_at)
+            .execute(&mut tx)
+            .await?;
+        }
+
+        for role in &self.roles {
+            sqlx::query(
+                "INSERT INTO roles (id, name, description, created_at) VALUES ($1, $2, $3, $4)",
+            )
+            .bind(role.id)
+            .bind(&role.name)
+            .bind(&role.description)
+            .bind(role.created_at)
+            .execute(&mut tx)
+            .await?;
+        }
+
+        if !self.roles.is_empty() {
+            sqlx::query(
+                "SELECT setval(pg_get_serial_sequence('roles', 'id'), (SELECT MAX(id) FROM roles))",
+            )
+            .execute(&mut tx)
+            .await?;
+        }
+
+        for role_scope in &self.role_scopes {
+            sqlx::query("INSERT INTO role_scopes (role_id, scope) VALUES ($1, $2)")
+                .bind(role_scope.role_id)
+                .bind(&role_scope.scope)
+                .execute(&mut tx)
+                .await?;
+        }
+
+        for user_role in &self.user_roles {
+            sqlx::query(
+                "INSERT INTO user_roles (koala_id, role_id, granted_by, granted_at) \
+                VALUES ($1, $2, $3, $4)",
+            )
+            .bind(user_role.koala_id)
+            .bind(user_role.role_id)
+            .bind(user_role.granted_by)
+            .bind(user_role.granted_at)
+            .execute(&mut tx)
+            .await?;
+        }
+
+        // Covers refer to photos, which refer to albums. Covers are set once the photos exist.
+        for album in &self.albums {
+            sqlx::query(
+                "INSERT INTO album_metadata \
+                (id, name, created_at, is_draft, created_by, created_by_type, published_by, published_by_type, published_at, deleted_at) \
+                VALUES ($1, $2, $3, $4, $5, $6::user_type, $7, $8::user_type, $9, $10)",
+            )
+            .bind(&album.id)
+            .bind(&album.name)
+            .bind(album.created_at)
+            .bind(album.is_draft)
+            .bind(album.created_by)
+            .bind(&album.created_by_type)
+            .bind(album.published_by)
+            .bind(&album.published_by_type)
+            .bind(album.published_at)
+            .bind(album.deleted_at)
+            .execute(&mut tx)
+            .await?;
+        }
+
+        for member in &self.album_members {
+            sqlx::query(
+                "INSERT INTO album_members (album_id, koala_id, role, granted_by, granted_at) \
+                VALUES ($1, $2, $3, $4, $5)",
+            )
+            .bind(&member.album_id)
+            .bind(member.koala_id)
+            .bind(&member.role)
+            .bind(member.granted_by)
+            .bind(member.granted_at)
+            .execute(&mut tx)
+            .await?;
+        }
+
+        for photo in &self.photos {
+            sqlx::query(
+                "INSERT INTO photo_metadata (id, album_id, created_at, position, deleted_at) \
+                VALUES ($1, $2, $3, $4, $5)",
+            )
+            .bind(&photo.id)
+            .bind(&photo.album_id)
+            .bind(photo.created_at)
+            .bind(photo.position)
+            .bind(photo.deleted_at)
+            .execute(&mut tx)
+            .await?;
+        }
+
+        let imported_at = OffsetDateTime::now_utc().unix_timestamp();
+        for object in objects {
+            sqlx::query(
+                "INSERT INTO photo_objects (photo_id, quality, object_key, created_at) VALUES ($1, $2, $3, $4)",
+            )
+            .bind(&object.photo_id)
+            .bind(&object.quality)
+            .bind(&object.object_key)
+            .bind(imported_at)
+            .execute(&mut tx)
+            .await?;
+
+            sqlx::query(
+                "INSERT INTO photo_s3_urls (photo_id, s3_url, quality) VALUES ($1, $2, $3)",
+            )
+            .bind(&object.photo_id)
+            .bind(&object.url)
+            .bind(&object.quality)
+            .execute(&mut tx)
+            .await?;
+
+            // The key may have been used before, its object must not be deleted
+            sqlx::query("DELETE FROM storage_tombstones WHERE object_key = $1")
+                .bind(&object.object_key)
+                .execute(&mut tx)
+                .await?;
+        }
+
+        for album in &self.albums {
+            if let Some(cover_photo_id) = &album.cover_photo_id {
+                sqlx::query("UPDATE album_metadata SET cover_photo_id = $1 WHERE id = $2")
+                    .bind(cover_photo_id)
+                    .bind(&album.id)
+                    .execute(&mut tx)
+                    .await?;
+            }
+        }
+
+        // Parents may be listed after their children, they are linked once all collections exist.
+        for collection in &self.collections {
+            sqlx::query(
+                "INSERT INTO album_collections (id, name, parent_id, created_at) VALUES ($1, $2, NULL, $3)",
+            )
+            .bind(&collection.id)
+            .bind(&collection.name)
+            .bind(collection.created_at)
+            .execute(&mut tx)
+            .await?;
+        }
+
+        for collection in &self.collections {
+            if let Some(parent_id) = &collection.parent_id {
+                sqlx::query("UPDATE album_collections SET parent_id = $1 WHERE id = $2")
+                    .bind(parent_id)
+                    .bind(&collection.id)
+                    .execute(&mut tx)
+                    .await?;
+            }
+        }
+
+        for member in &self.collection_members {
+            sqlx::query(
+                "INSERT INTO album_collection_members (collection_id, album_id) VALUES ($1, $2)",
+            )
+            .bind(&member.collection_id)
+            .bind(&member.album_id)
+            .execute(&mut tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,202 @@
+use std::collections::{HashMap, HashSet};
+
+use time::OffsetDateTime;
+
+use crate::database::{
+    AuditAction, AuditLogEntry, AuditTarget, Database, DbResult, Photo, PhotoObject, PhotoQuality,
+    PhotoS3Url, StorageIncident, StorageTombstone, UserType,
+};
+use crate::storage_engine::{KeyLayout, Storage};
+use crate::DalError;
+
+/// Photos uploaded and objects stored less than this many seconds ago are not checked,
+/// as their upload or metadata may still be in progress.
+const SETTLE_PERIOD: i64 = 60 * 60;
+
+/// A stored URL of an object which does not exist.
+pub struct StaleUrl {
+    pub url: PhotoS3Url,
+    pub object_key: String,
+}
+
+/// The discrepancies found between the database and the storage bucket.
+#[derive(Default)]
+pub struct ConsistencyReport<'a> {
+    /// Keys of objects in a photo key layout which do not belong to any photo,
+    /// and for which no deletion is pending.
+    pub orphaned_objects: Vec<String>,
+    /// Keys of objects which are not in any photo key layout.
+    /// These are only reported, never removed.
+    pub unknown_objects: Vec<String>,
+    /// Photos whose original object does not exist.
+    pub photos_missing_original: Vec<Photo<'a>>,
+    /// Stored URLs of objects which do not exist.
+    pub stale_urls: Vec<StaleUrl>,
+    /// Cover photos which could not be retrieved while listing albums.
+    pub incidents: Vec<StorageIncident>,
+    /// The number of objects waiting to be deleted.
+    pub pending_tombstones: usize,
+}
+
+impl<'a> ConsistencyReport<'a> {
+    /// Compare the contents of the storage bucket with the photo metadata.
+    ///
+    /// # Errors
+    ///
+    /// If a database or storage error occurs
+    pub async fn check(
+        db: &'a Database,
+        storage: &Storage,
+    ) -> Result<ConsistencyReport<'a>, DalError> {
+        let settled_before = OffsetDateTime::now_utc().unix_timestamp() - SETTLE_PERIOD;
+
+        let objects = storage.list_objects().await?;
+        let photos = Photo::list_including_trashed(db).await?;
+        let recently_uploaded = Photo::list_ids_uploaded_since(db, settled_before)
+            .await?
+            .into_iter()
+            .collect::<HashSet<_>>();
+        let urls = PhotoS3Url::list(db).await?;
+        let tombstones = StorageTombstone::list(db).await?;
+        let incidents = StorageIncident::list(db).await?;
+
+        let photo_objects = PhotoObject::list(db).await?;
+
+        // The key under which every variant of every photo is stored
+        let mut keys = photo_objects
+            .into_iter()
+            .map(|object| ((object.photo_id, object.quality), object.object_key))
+            .collect::<HashMap<_, _>>();
+        for photo in &photos {
+            for quality in [
+                PhotoQuality::Original,
+                PhotoQuality::W400,
+                PhotoQuality::W1600,
+            ] {
+                keys.entry((photo.id.clone(), quality.clone()))
+                    .or_insert_with(|| PhotoObject::legacy_key(&photo.id, &quality));
+            }
+        }
+
+        let live = keys.values().map(String::as_str).collect::<HashSet<_>>();
+        let tombstoned = tombstones
+            .iter()
+            .map(|tombstone| tombstone.object_key.as_str())
+            .collect::<HashSet<_>>();
+        let legacy_layout = KeyLayout::legacy();
+
+        let mut report = ConsistencyReport {
+            pending_tombstones: tombstones.len(),
+            incidents,
+            ..Default::default()
+        };
+
+        let mut stored = HashSet::new();
+        for object in objects {
+            let is_settled = object
+                .last_modified
+                .map(|last_modified| last_modified < settled_before)
+                .unwrap_or(true);
+
+            if is_settled
+                && !live.contains(object.key.as_str())
+                && !tombstoned.contains(object.key.as_str())
+            {
+                if storage.key_layout().matches(&object.key) || legacy_layout.matches(&object.key) {
+                    report.orphaned_objects.push(object.key.clone());
+                } else {
+                    report.unknown_objects.push(object.key.clone());
+                }
+            }
+
+            stored.insert(object.key);
+        }
+
+        let key_of = |photo_id: &str, quality: &PhotoQuality| {
+            keys.get(&(photo_id.to_string(), quality.clone()))
+                .cloned()
+                .unwrap_or_else(|| PhotoObject::legacy_key(photo_id, quality))
+        };
+
+        report.stale_urls = urls
+            .into_iter()
+            .map(|url| StaleUrl {
+                object_key: key_of(&url.photo_id, &url.quality),
+                url,
+            })
+            .filter(|stale| !stored.contains(&stale.object_key))
+            .collect();
+
+        report.photos_missing_original = photos
+            .into_iter()
+            .filter(|photo| !recently_uploaded.contains(&photo.id))
+            .filter(|photo| !stored.contains(&key_of(&photo.id, &PhotoQuality::Original)))
+            .collect();
+
+        Ok(report)
+    }
+
+    /// Whether the database and storage bucket are consistent.
+    pub fn is_consistent(&self) -> bool {
+        self.orphaned_objects.is_empty()
+            && self.unknown_objects.is_empty()
+            && self.photos_missing_original.is_empty()
+            && self.stale_urls.is_empty()
+            && self.incidents.is_empty()
+    }
+
+    /// Repair the discrepancies in the report.
+    /// - Orphaned objects are scheduled for deletion, see [StorageTombstone].
+    /// - Photos missing their original can never be served, they are moved to the trash.
+    ///   With `delete_missing_originals` they are deleted permanently instead.
+    /// - Stale URLs are removed, so they are recreated once the object exists.
+    /// - Incidents are resolved, the affected photos are covered by the checks above.
+    ///
+    /// Unknown objects are left untouched.
+    /// Trashed and deleted photos are recorded in the audit log as changed by `actor`.
+    ///
+    /// # Errors
+    ///
+    /// If a database error occurs
+    pub async fn repair(
+        self,
+        db: &Database,
+        actor: Option<&UserType>,
+        delete_missing_originals: bool,
+    ) -> DbResult<()> {
+        for key in &self.orphaned_objects {
+            StorageTombstone::insert(db, key).await?;
+        }
+
+        for stale in self.stale_urls {
+            stale.url.delete(db).await?;
+        }
+
+        for mut photo in self.photos_missing_original {
+            // Already in the trash
+            if !delete_missing_originals && photo.deleted_at.is_some() {
+                continue;
+            }
+
+            let id = photo.id.clone();
+            let mut tx = db.begin().await?;
+            let action = if delete_missing_originals {
+                photo.delete(&mut tx).await?;
+                AuditAction::PhotoDeleted
+            } else {
+                photo.trash(&mut tx).await?;
+                AuditAction::PhotoTrashed
+            };
+
+            AuditLogEntry::record(&mut tx, actor, action, AuditTarget::Photo(id), None, None)
+                .await?;
+            tx.commit().await?;
+        }
+
+        for incident in self.incidents {
+            incident.resolve(db).await?;
+        }
+
+        Ok(())
+    }
+}
@@ -3,10 +3,10 @@ use std::fmt;
 use std::fmt::Formatter;
 
 use rand::Rng;
-use sqlx::{FromRow, Type};
+use sqlx::{Executor, FromRow, Postgres, Transaction, Type};
 use time::OffsetDateTime;
 
-use crate::database::{Database, DatabaseError, DbResult, Photo, User};
+use crate::database::{Database, DatabaseError, DbResult, Photo, StorageTombstone, User};
 
 #[derive(Clone)]
 pub struct Album {
@@ -18,6 +18,7 @@ pub struct Album {
     pub created_by: UserType,
     pub published_by: Option<UserType>,
     pub published_at: Option<i64>,
+    pub deleted_at: Option<i64>,
 }
 
 // Manually impl debug as to not print the `db` field
@@ -32,14 +33,24 @@ impl fmt::Debug for Album {
             .field("published_by", &self.published_by)
             .field("is_draft", &self.is_draft)
             .field("cover_photo_id", &self.cover_photo_id)
+            .field("deleted_at", &self.deleted_at)
             .finish()
     }
 }
 
+/// The set of photos to move to a new album when splitting an album
+#[derive(Clone, Debug)]
+pub enum AlbumSplit {
+    /// Split off the photos with the provided IDs
+    Photos(Vec<String>),
+    /// Split off all photos taken at or after the provided timestamp
+    TakenFrom(i64),
+}
+
 #[derive(Clone, Debug)]
 pub enum UserType {
     Koala(i32),
-    ServiceToken(i32),
+    ApiKey(i32),
 }
 
 #[derive(FromRow)]
@@ -54,11 +65,12 @@ struct _Album {
     published_by: Option<i32>,
     published_by_type: Option<_UserType>,
     published_at: Option<i64>,
+    deleted_at: Option<i64>,
 }
 
 #[derive(Clone, Type)]
 #[sqlx(type_name = "user_type")]
-enum _UserType {
+pub(crate) enum _UserType {
     Koala,
     Service,
 }
@@ -73,13 +85,14 @@ impl _Album {
             is_draft: self.is_draft,
             published_by: match (self.published_by_type, self.published_by) {
                 (Some(_UserType::Koala), Some(id)) => Some(UserType::Koala(id)),
-                (Some(_UserType::Service), Some(id)) => Some(UserType::ServiceToken(id)),
+                (Some(_UserType::Service), Some(id)) => Some(UserType::ApiKey(id)),
                 _ => None,
             },
             published_at: self.published_at,
+            deleted_at: self.deleted_at,
             created_by: match self.created_by_type {
                 _UserType::Koala => UserType::Koala(self.created_by),
-                _UserType::Service => UserType::ServiceToken(self.created_by),
+                _UserType::Service => UserType::ApiKey(self.created_by),
             },
         }
     }
@@ -111,9 +124,9 @@ impl Album {
                     r#type: proto::UserType::Koala as i32,
                 }
             }
-            UserType::ServiceToken(id) => proto::AlbumUser {
+            UserType::ApiKey(id) => proto::AlbumUser {
                 id,
-                name: None,
+                name: ApiKey::get_by_id(db, id).await?.map(|key| key.description),
                 r#type: proto::UserType::Service as i32,
             },
         })
@@ -132,11 +145,23 @@ impl Album {
                 None => None,
             },
             published_at: self.published_at,
+            deleted_at: self.deleted_at,
         })
     }
 
     pub async fn create(
-        db: &Database,
+        tx: &mut Transaction<'_, Postgres>,
+        name: impl Into<Cow<'_, str>>,
+        is_draft: bool,
+        created_by: UserType,
+    ) -> DbResult<Album> {
+        Self::insert(&mut *tx, name, is_draft, created_by).await
+    }
+
+    /// Insert a new album using the provided executor.
+    /// This allows albums to be created as part of a transaction.
+    async fn insert<'c, E: Executor<'c, Database = Postgres>>(
+        executor: E,
         name: impl Into<Cow<'_, str>>,
         is_draft: bool,
         created_by: UserType,
@@ -147,7 +172,7 @@ impl Album {
 
         let (created_by_type, created_by_id) = match &created_by {
             UserType::Koala(id) => (_UserType::Koala, *id),
-            UserType::ServiceToken(id) => (_UserType::Service, *id),
+            UserType::ApiKey(id) => (_UserType::Service, *id),
         };
 
         // If something is a draft, there can be no publisher.
@@ -156,7 +181,7 @@ impl Album {
         let published_by_type = (!is_draft).then_some(created_by_type.clone());
         let published_by_id = (!is_draft).then_some(match &created_by {
             UserType::Koala(id) => *id,
-            UserType::ServiceToken(id) => *id,
+            UserType::ApiKey(id) => *id,
         });
 
         sqlx::query(
@@ -173,7 +198,7 @@ impl Album {
             .bind(published_at)
             .bind(published_by_type)
             .bind(created_by_type)
-            .execute(&**db)
+            .execute(executor)
             .await?;
 
         Ok(Self {
@@ -185,14 +210,32 @@ impl Album {
             published_by: (!is_draft).then(|| created_by.clone()),
             created_by,
             published_at,
+            deleted_at: None,
         })
     }
 
+    /// Get an album by its ID.
+    /// Albums in the trash are not returned, see [Self::get_trashed_by_id].
     pub async fn get_by_id<S: AsRef<str> + Sync>(db: &Database, id: S) -> DbResult<Option<Album>> {
-        let album: Option<_Album> = sqlx::query_as("SELECT * FROM album_metadata WHERE id = $1")
-            .bind(id.as_ref())
-            .fetch_optional(&**db)
-            .await?;
+        let album: Option<_Album> =
+            sqlx::query_as("SELECT * FROM album_metadata WHERE id = $1 AND deleted_at IS NULL")
+                .bind(id.as_ref())
+                .fetch_optional(&**db)
+                .await?;
+
+        Ok(album.map(|x| x.into_album()))
+    }
+
+    /// Get an album in the trash by its ID.
+    pub async fn get_trashed_by_id<S: AsRef<str> + Sync>(
+        db: &Database,
+        id: S,
+    ) -> DbResult<Option<Album>> {
+        let album: Option<_Album> =
+            sqlx::query_as("SELECT * FROM album_metadata WHERE id = $1 AND deleted_at IS NOT NULL")
+                .bind(id.as_ref())
+                .fetch_optional(&**db)
+                .await?;
 
         Ok(album.map(|x| x.into_album()))
     }
@@ -212,6 +255,18 @@ impl Album {
         Ok(())
     }
 
+    /// Remove the cover photo of the album.
+    /// The photo itself is not affected.
+    pub async fn unset_cover_photo(&mut self, db: &Database) -> DbResult<()> {
+        sqlx::query("UPDATE album_metadata SET cover_photo_id = NULL WHERE id = $1")
+            .bind(&self.id)
+            .execute(&**db)
+            .await?;
+
+        self.cover_photo_id = None;
+        Ok(())
+    }
+
     pub async fn update_name(
         &mut self,
         new_name: impl Into<Cow<'_, str>>,
@@ -227,12 +282,16 @@ impl Album {
         Ok(())
     }
 
-    pub async fn set_published(&mut self, published_by: UserType, db: &Database) -> DbResult<()> {
+    pub async fn set_published(
+        &mut self,
+        published_by: UserType,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> DbResult<()> {
         let published_at = OffsetDateTime::now_utc().unix_timestamp();
 
         let (published_by_type, published_by_id) = match &published_by {
             UserType::Koala(id) => (_UserType::Koala, *id),
-            UserType::ServiceToken(id) => (_UserType::Service, *id),
+            UserType::ApiKey(id) => (_UserType::Service, *id),
         };
 
         sqlx::query("UPDATE album_metadata SET published_by = $1, published_at = $2, published_by_type = $3, is_draft = false WHERE id = $4")
@@ -240,7 +299,7 @@ impl Album {
             .bind(published_at)
             .bind(published_by_type)
             .bind(&self.id)
-            .execute(&**db)
+            .execute(&mut *tx)
             .await?;
 
         self.is_draft = false;
@@ -250,10 +309,10 @@ impl Album {
         Ok(())
     }
 
-    pub async fn set_draft(&mut self, db: &Database) -> DbResult<()> {
+    pub async fn set_draft(&mut self, tx: &mut Transaction<'_, Postgres>) -> DbResult<()> {
         sqlx::query("UPDATE album_metadata SET published_by = NULL, published_by_type = NULL, published_at = NULL, is_draft = true WHERE id = $1")
             .bind(&self.id)
-            .execute(&**db)
+            .execute(&mut *tx)
             .await?;
 
         self.is_draft = true;
@@ -263,41 +322,268 @@ impl Album {
         Ok(())
     }
 
-    pub async fn delete(self, db: &Database) -> DbResult<()> {
-        let mut tx = db.begin().await?;
+    /// Move the album to the trash.
+    /// The album and its photos are hidden, but can still be restored.
+    pub async fn trash(&mut self, tx: &mut Transaction<'_, Postgres>) -> DbResult<()> {
+        let deleted_at = OffsetDateTime::now_utc().unix_timestamp();
+
+        sqlx::query("UPDATE album_metadata SET deleted_at = $1 WHERE id = $2")
+            .bind(deleted_at)
+            .bind(&self.id)
+            .execute(&mut *tx)
+            .await?;
+
+        self.deleted_at = Some(deleted_at);
+        Ok(())
+    }
+
+    /// Restore the album from the trash.
+    pub async fn restore(&mut self, tx: &mut Transaction<'_, Postgres>) -> DbResult<()> {
+        sqlx::query("UPDATE album_metadata SET deleted_at = NULL WHERE id = $1")
+            .bind(&self.id)
+            .execute(&mut *tx)
+            .await?;
+
+        self.deleted_at = None;
+        Ok(())
+    }
+
+    /// Permanently delete the album and the metadata of all its photos.
+    /// The stored objects of the photos are deleted afterwards, see [StorageTombstone].
+    pub async fn delete(self, tx: &mut Transaction<'_, Postgres>) -> DbResult<()> {
+        let photo_ids: Vec<String> =
+            sqlx::query_scalar("SELECT id FROM photo_metadata WHERE album_id = $1")
+                .bind(&self.id)
+                .fetch_all(&mut *tx)
+                .await?;
+        StorageTombstone::insert_for_photos(tx, &photo_ids).await?;
 
         // Must satisfy the foreign key constraint
         // So unset the cover photo before removing all photoss
         sqlx::query("UPDATE album_metadata SET cover_photo_id = NULL WHERE id = $1")
             .bind(&self.id)
-            .execute(&mut tx)
+            .execute(&mut *tx)
             .await?;
 
         sqlx::query("DELETE FROM photo_metadata WHERE album_id = $1")
             .bind(&self.id)
-            .execute(&mut tx)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM album_collection_members WHERE album_id = $1")
+            .bind(&self.id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM api_key_albums WHERE album_id = $1")
+            .bind(&self.id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM album_members WHERE album_id = $1")
+            .bind(&self.id)
+            .execute(&mut *tx)
             .await?;
 
         sqlx::query("DELETE FROM album_metadata WHERE id = $1")
             .bind(&self.id)
-            .execute(&mut tx)
+            .execute(&mut *tx)
             .await?;
 
-        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Merge this album into another album.
+    /// All photos are moved to `target`, after which this album is deleted.
+    /// `target` is added to all collections this album is part of.
+    /// The cover photo of `target` is kept. If `target` has no cover photo,
+    /// the cover photo of this album is used instead.
+    ///
+    /// # Errors
+    ///
+    /// If a database error occurs. The transaction should not be committed in that case.
+    pub async fn merge_into(
+        self,
+        target: &mut Album,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> DbResult<()> {
+        // Must satisfy the foreign key constraint
+        // So unset the cover photo before moving the photos
+        sqlx::query("UPDATE album_metadata SET cover_photo_id = NULL WHERE id = $1")
+            .bind(&self.id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("UPDATE photo_metadata SET album_id = $1, position = NULL WHERE album_id = $2")
+            .bind(&target.id)
+            .bind(&self.id)
+            .execute(&mut *tx)
+            .await?;
+
+        let cover_photo_id = target
+            .cover_photo_id
+            .clone()
+            .or(self.cover_photo_id.clone());
+
+        sqlx::query("UPDATE album_metadata SET cover_photo_id = $1 WHERE id = $2")
+            .bind(&cover_photo_id)
+            .bind(&target.id)
+            .execute(&mut *tx)
+            .await?;
+
+        // The target album takes over the collections of this album
+        sqlx::query(
+            "INSERT INTO album_collection_members (collection_id, album_id) \
+                SELECT collection_id, $1 FROM album_collection_members WHERE album_id = $2 \
+            ON CONFLICT DO NOTHING",
+        )
+        .bind(&target.id)
+        .bind(&self.id)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query("DELETE FROM album_collection_members WHERE album_id = $1")
+            .bind(&self.id)
+            .execute(&mut *tx)
+            .await?;
+
+        // API keys bound to this album are not granted access to the target album
+        sqlx::query("DELETE FROM api_key_albums WHERE album_id = $1")
+            .bind(&self.id)
+            .execute(&mut *tx)
+            .await?;
+
+        // Neither are the members of this album granted a role in the target album
+        sqlx::query("DELETE FROM album_members WHERE album_id = $1")
+            .bind(&self.id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM album_metadata WHERE id = $1")
+            .bind(&self.id)
+            .execute(&mut *tx)
+            .await?;
+
+        target.cover_photo_id = cover_photo_id;
 
         Ok(())
     }
 
+    /// Split photos off this album into a new album.
+    /// The new album is a draft if this album is a draft,
+    /// otherwise it is published by `created_by`.
+    /// If the cover photo of this album is split off, it becomes the cover photo of the new album.
+    /// The members of this album have the same role in the new album.
+    ///
+    /// Photo IDs in [AlbumSplit::Photos] which are not part of this album are ignored.
+    ///
+    /// # Errors
+    ///
+    /// If a database error occurs. The transaction should not be committed in that case.
+    pub async fn split(
+        &mut self,
+        name: impl Into<Cow<'_, str>>,
+        split: AlbumSplit,
+        created_by: UserType,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> DbResult<Album> {
+        let mut new_album = Self::insert(&mut *tx, name, self.is_draft, created_by).await?;
+
+        match &split {
+            AlbumSplit::Photos(photo_ids) => {
+                sqlx::query("UPDATE photo_metadata SET album_id = $1, position = NULL WHERE album_id = $2 AND id = ANY($3)")
+                    .bind(&new_album.id)
+                    .bind(&self.id)
+                    .bind(photo_ids)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            AlbumSplit::TakenFrom(timestamp) => {
+                sqlx::query("UPDATE photo_metadata SET album_id = $1, position = NULL WHERE album_id = $2 AND created_at >= $3")
+                    .bind(&new_album.id)
+                    .bind(&self.id)
+                    .bind(timestamp)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+        }
+
+        // Check if the cover photo moved along
+        let cover_photo_moved = match &self.cover_photo_id {
+            Some(cover_photo_id) => {
+                let album_id: String =
+                    sqlx::query_scalar("SELECT album_id FROM photo_metadata WHERE id = $1")
+                        .bind(cover_photo_id)
+                        .fetch_one(&mut *tx)
+                        .await?;
+                album_id.eq(&new_album.id)
+            }
+            None => false,
+        };
+
+        // The members of this album keep their role in the photos split off
+        sqlx::query(
+            "INSERT INTO album_members (album_id, koala_id, role, granted_by, granted_at) \
+                SELECT $1, koala_id, role, granted_by, granted_at FROM album_members WHERE album_id = $2",
+        )
+        .bind(&new_album.id)
+        .bind(&self.id)
+        .execute(&mut *tx)
+        .await?;
+
+        if cover_photo_moved {
+            sqlx::query("UPDATE album_metadata SET cover_photo_id = NULL WHERE id = $1")
+                .bind(&self.id)
+                .execute(&mut *tx)
+                .await?;
+
+            sqlx::query("UPDATE album_metadata SET cover_photo_id = $1 WHERE id = $2")
+                .bind(&self.cover_photo_id)
+                .bind(&new_album.id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        if cover_photo_moved {
+            new_album.cover_photo_id = self.cover_photo_id.take();
+        }
+
+        Ok(new_album)
+    }
+
     pub async fn list_ids(db: &Database) -> DbResult<Vec<String>> {
-        sqlx::query_scalar("SELECT id FROM album_metadata")
+        sqlx::query_scalar("SELECT id FROM album_metadata WHERE deleted_at IS NULL")
             .fetch_all(&**db)
             .await
     }
 
     pub async fn list(db: &Database) -> DbResult<Vec<Album>> {
-        let selfs: Vec<_Album> = sqlx::query_as("SELECT * FROM album_metadata")
-            .fetch_all(&**db)
-            .await?;
+        let selfs: Vec<_Album> =
+            sqlx::query_as("SELECT * FROM album_metadata WHERE deleted_at IS NULL")
+                .fetch_all(&**db)
+                .await?;
+
+        Ok(selfs.into_iter().map(|x| x.into_album()).collect())
+    }
+
+    /// List all albums in the trash, most recently deleted first.
+    pub async fn list_trashed(db: &Database) -> DbResult<Vec<Album>> {
+        let selfs: Vec<_Album> = sqlx::query_as(
+            "SELECT * FROM album_metadata WHERE deleted_at IS NOT NULL ORDER BY deleted_at DESC",
+        )
+        .fetch_all(&**db)
+        .await?;
+
+        Ok(selfs.into_iter().map(|x| x.into_album()).collect())
+    }
+
+    /// List all albums which were moved to the trash before `timestamp`.
+    pub async fn list_trashed_before(db: &Database, timestamp: i64) -> DbResult<Vec<Album>> {
+        let selfs: Vec<_Album> =
+            sqlx::query_as("SELECT * FROM album_metadata WHERE deleted_at < $1")
+                .bind(timestamp)
+                .fetch_all(&**db)
+                .await?;
 
         Ok(selfs.into_iter().map(|x| x.into_album()).collect())
     }
@@ -0,0 +1,178 @@
+use sqlx::{FromRow, Postgres, Transaction};
+use strum_macros::{Display, EnumString};
+use time::OffsetDateTime;
+
+use crate::database::{Database, DbResult};
+
+/// The role of a user in a specific album.
+/// Roles are ordered by what they allow, each role allows everything the roles before it allow.
+/// Stored by name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Display, EnumString)]
+pub enum AlbumRole {
+    /// May see the album while it is a draft
+    Viewer,
+    /// May also add photos to and remove photos from the album while it is a draft
+    Contributor,
+    /// May also change and delete the album while it is a draft, and manage its members
+    Owner,
+}
+
+impl AlbumRole {
+    pub fn to_proto(self) -> proto::AlbumRole {
+        match self {
+            Self::Viewer => proto::AlbumRole::Viewer,
+            Self::Contributor => proto::AlbumRole::Contributor,
+            Self::Owner => proto::AlbumRole::Owner,
+        }
+    }
+
+    pub fn from_proto(role: proto::AlbumRole) -> Self {
+        match role {
+            proto::AlbumRole::Viewer => Self::Viewer,
+            proto::AlbumRole::Contributor => Self::Contributor,
+            proto::AlbumRole::Owner => Self::Owner,
+        }
+    }
+}
+
+/// A user with a role in an album.
+/// Members hold the role on top of the scopes they hold for all albums.
+/// Only Koala users may be members, API keys are bound to albums instead.
+#[derive(Debug, Clone)]
+pub struct AlbumMember {
+    pub album_id: String,
+    pub koala_id: i32,
+    pub role: AlbumRole,
+    pub granted_by: i32,
+    pub granted_at: i64,
+}
+
+#[derive(FromRow)]
+struct _AlbumMember {
+    album_id: String,
+    koala_id: i32,
+    role: String,
+    granted_by: i32,
+    granted_at: i64,
+}
+
+impl _AlbumMember {
+    fn into_member(self) -> AlbumMember {
+        AlbumMember {
+            album_id: self.album_id,
+            koala_id: self.koala_id,
+            // Roles which are not known to this version grant the least
+            role: self.role.parse().unwrap_or(AlbumRole::Viewer),
+            granted_by: self.granted_by,
+            granted_at: self.granted_at,
+        }
+    }
+}
+
+impl AlbumMember {
+    pub async fn list_for_album(db: &Database, album_id: &str) -> DbResult<Vec<AlbumMember>> {
+        let members: Vec<_AlbumMember> = sqlx::query_as(
+            "SELECT album_id, koala_id, role, granted_by, granted_at FROM album_members \
+            WHERE album_id = $1 ORDER BY koala_id",
+        )
+        .bind(album_id)
+        .fetch_all(&**db)
+        .await?;
+
+        Ok(members.into_iter().map(_AlbumMember::into_member).collect())
+    }
+
+    /// The role of the user in the album, or `None` if they are not a member.
+    pub async fn get_role(
+        db: &Database,
+        album_id: &str,
+        koala_id: i32,
+    ) -> DbResult<Option<AlbumRole>> {
+        let role: Option<String> = sqlx::query_scalar(
+            "SELECT role FROM album_members WHERE album_id = $1 AND koala_id = $2",
+        )
+        .bind(album_id)
+        .bind(koala_id)
+        .fetch_optional(&**db)
+        .await?;
+
+        Ok(role.map(|role| role.parse().unwrap_or(AlbumRole::Viewer)))
+    }
+
+    /// List the IDs of the albums the user is a member of, regardless of their role.
+    pub async fn list_album_ids_for_user(db: &Database, koala_id: i32) -> DbResult<Vec<String>> {
+        sqlx::query_scalar(
+            "SELECT album_id FROM album_members WHERE koala_id = $1 ORDER BY album_id",
+        )
+        .bind(koala_id)
+        .fetch_all(&**db)
+        .await
+    }
+
+    /// List the IDs of the owners of the album, ordered by ID.
+    /// Their memberships are locked until the transaction ends,
+    /// so concurrent transactions cannot remove the other owners in the meantime.
+    pub async fn lock_owners(
+        tx: &mut Transaction<'_, Postgres>,
+        album_id: &str,
+    ) -> DbResult<Vec<i32>> {
+        sqlx::query_scalar(
+            "SELECT koala_id FROM album_members WHERE album_id = $1 AND role = $2 \
+            ORDER BY koala_id FOR UPDATE",
+        )
+        .bind(album_id)
+        .bind(AlbumRole::Owner.to_string())
+        .fetch_all(&mut *tx)
+        .await
+    }
+
+    /// Give the user a role in the album, replacing their current role if they are a member.
+    pub async fn set(
+        tx: &mut Transaction<'_, Postgres>,
+        album_id: &str,
+        koala_id: i32,
+        role: AlbumRole,
+        granted_by: i32,
+    ) -> DbResult<()> {
+        sqlx::query(
+            "INSERT INTO album_members (album_id, koala_id, role, granted_by, granted_at) \
+            VALUES ($1, $2, $3, $4, $5) \
+            ON CONFLICT (album_id, koala_id) DO UPDATE \
+            SET role = EXCLUDED.role, granted_by = EXCLUDED.granted_by, granted_at = EXCLUDED.granted_at",
+        )
+        .bind(album_id)
+        .bind(koala_id)
+        .bind(role.to_string())
+        .bind(granted_by)
+        .bind(OffsetDateTime::now_utc().unix_timestamp())
+        .execute(&mut *tx)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Remove the user from the album.
+    /// Removing a user who is not a member has no effect.
+    pub async fn remove(
+        tx: &mut Transaction<'_, Postgres>,
+        album_id: &str,
+        koala_id: i32,
+    ) -> DbResult<()> {
+        sqlx::query("DELETE FROM album_members WHERE album_id = $1 AND koala_id = $2")
+            .bind(album_id)
+            .bind(koala_id)
+            .execute(&mut *tx)
+            .await?;
+
+        Ok(())
+    }
+
+    pub fn to_proto(self) -> proto::AlbumMember {
+        proto::AlbumMember {
+            user_id: self.koala_id,
+            role: self.role.to_proto() as i32,
+            granted_by: self.granted_by,
+            granted_at: self.granted_at,
+        }
+    }
+}
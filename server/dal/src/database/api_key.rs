@@ -0,0 +1,289 @@
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use sqlx::{FromRow, Postgres, Transaction};
+use time::OffsetDateTime;
+
+use crate::database::{Database, DbResult};
+
+/// A key with which services authorize.
+/// Only a hash of the key is stored, the key itself is only known when it is created.
+/// Requests are authorized with the header `Authorization: Service <key>`.
+#[derive(Debug, Clone, FromRow)]
+pub struct ApiKey {
+    pub id: i32,
+    pub description: String,
+    /// Admin keys may access everything, regardless of their scopes
+    pub is_admin: bool,
+    /// The Koala ID of the admin who created the key.
+    /// `None` for keys which were migrated from service tokens.
+    pub created_by: Option<i32>,
+    pub created_at: i64,
+    pub expires_at: Option<i64>,
+    pub last_used_at: Option<i64>,
+    pub revoked_at: Option<i64>,
+    /// Whether the key may only act on the albums it is bound to.
+    /// Stored apart from the bindings, so a key does not lose its restriction
+    /// once all albums it is bound to have been deleted.
+    pub album_bound: bool,
+}
+
+impl ApiKey {
+    pub const KEY_PREFIX: &'static str = "chroma_";
+    pub const KEY_LEN: usize = 48;
+
+    /// Create a new key with the provided scopes, bound to the provided albums.
+    /// Returns the key itself alongside its metadata, the key cannot be retrieved afterwards.
+    pub async fn create<S: AsRef<str>>(
+        tx: &mut Transaction<'_, Postgres>,
+        description: S,
+        is_admin: bool,
+        created_by: i32,
+        expires_at: Option<i64>,
+        scopes: &[String],
+        album_ids: &[String],
+    ) -> DbResult<(ApiKey, String)> {
+        let key = format!(
+            "{}{}",
+            Self::KEY_PREFIX,
+            rand::thread_rng()
+                .sample_iter(rand::distributions::Alphanumeric)
+                .take(Self::KEY_LEN)
+                .map(char::from)
+                .collect::<String>()
+        );
+
+        let api_key: ApiKey = sqlx::query_as(
+            "INSERT INTO api_keys (key_hash, description, is_admin, created_by, created_at, expires_at, album_bound) \
+            VALUES ($1, $2, $3, $4, $5, $6, $7) \
+            RETURNING id, description, is_admin, created_by, created_at, expires_at, last_used_at, revoked_at, album_bound",
+        )
+        .bind(Self::hash_key(&key))
+        .bind(description.as_ref())
+        .bind(is_admin)
+        .bind(created_by)
+        .bind(OffsetDateTime::now_utc().unix_timestamp())
+        .bind(expires_at)
+        .bind(!album_ids.is_empty())
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            "INSERT INTO api_key_scopes (api_key_id, scope) SELECT $1, UNNEST($2::VARCHAR[]) \
+            ON CONFLICT DO NOTHING",
+        )
+        .bind(api_key.id)
+        .bind(scopes)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            "INSERT INTO api_key_albums (api_key_id, album_id) SELECT $1, UNNEST($2::VARCHAR[]) \
+            ON CONFLICT DO NOTHING",
+        )
+        .bind(api_key.id)
+        .bind(album_ids)
+        .execute(&mut *tx)
+        .await?;
+
+        Ok((api_key, key))
+    }
+
+    /// Create an admin key from a service token, the way service tokens were migrated to keys.
+    /// Returns `false` if a key with the token already exists, e.g. because it has been used
+    /// before service tokens were migrated. Such keys are left untouched, even if they have been revoked.
+    pub async fn create_from_service_token<S: AsRef<str>>(
+        db: &Database,
+        token: S,
+    ) -> DbResult<bool> {
+        let result = sqlx::query(
+            "INSERT INTO api_keys (key_hash, description, is_admin, created_at) \
+            VALUES ($1, 'Service token', TRUE, $2) \
+            ON CONFLICT (key_hash) DO NOTHING",
+        )
+        .bind(Self::hash_key(token.as_ref()))
+        .bind(OffsetDateTime::now_utc().unix_timestamp())
+        .execute(&**db)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn get_by_id(db: &Database, id: i32) -> DbResult<Option<ApiKey>> {
+        sqlx::query_as(
+            "SELECT id, description, is_admin, created_by, created_at, expires_at, last_used_at, revoked_at, album_bound \
+            FROM api_keys WHERE id = $1",
+        )
+        .bind(id)
+        .fetch_optional(&**db)
+        .await
+    }
+
+    /// Find the key with which a request was authorized.
+    /// The key may have expired or have been revoked, see [Self::is_active].
+    pub async fn get_by_key<S: AsRef<str>>(db: &Database, key: S) -> DbResult<Option<ApiKey>> {
+        sqlx::query_as(
+            "SELECT id, description, is_admin, created_by, created_at, expires_at, last_used_at, revoked_at, album_bound \
+            FROM api_keys WHERE key_hash = $1",
+        )
+        .bind(Self::hash_key(key.as_ref()))
+        .fetch_optional(&**db)
+        .await
+    }
+
+    /// List all keys, including those which have expired or have been revoked.
+    pub async fn list(db: &Database) -> DbResult<Vec<ApiKey>> {
+        sqlx::query_as(
+            "SELECT id, description, is_admin, created_by, created_at, expires_at, last_used_at, revoked_at, album_bound \
+            FROM api_keys ORDER BY created_at DESC",
+        )
+        .fetch_all(&**db)
+        .await
+    }
+
+    /// Whether the key may still be used, i.e. it has not expired or been revoked.
+    pub fn is_active(&self) -> bool {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        self.revoked_at.is_none() && self.expires_at.map(|v| v > now).unwrap_or(true)
+    }
+
+    pub async fn list_scopes(&self, db: &Database) -> DbResult<Vec<String>> {
+        sqlx::query_scalar("SELECT scope FROM api_key_scopes WHERE api_key_id = $1 ORDER BY scope")
+            .bind(self.id)
+            .fetch_all(&**db)
+            .await
+    }
+
+    /// Replace the scopes of the key.
+    pub async fn set_scopes(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        scopes: &[String],
+    ) -> DbResult<()> {
+        sqlx::query("DELETE FROM api_key_scopes WHERE api_key_id = $1")
+            .bind(self.id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query(
+            "INSERT INTO api_key_scopes (api_key_id, scope) SELECT $1, UNNEST($2::VARCHAR[]) \
+            ON CONFLICT DO NOTHING",
+        )
+        .bind(self.id)
+        .bind(scopes)
+        .execute(&mut *tx)
+        .await?;
+
+        Ok(())
+    }
+
+    /// List the albums the key is bound to.
+    /// Only meaningful if the key is [Self::album_bound].
+    pub async fn list_albums(&self, db: &Database) -> DbResult<Vec<String>> {
+        sqlx::query_scalar(
+            "SELECT album_id FROM api_key_albums WHERE api_key_id = $1 ORDER BY album_id",
+        )
+        .bind(self.id)
+        .fetch_all(&**db)
+        .await
+    }
+
+    /// Whether the key may act on the album.
+    /// A key which is bound to albums may only act on those albums,
+    /// even if all of them have been deleted since.
+    pub async fn may_access_album(&self, db: &Database, album_id: &str) -> DbResult<bool> {
+        if !self.album_bound {
+            return Ok(true);
+        }
+
+        sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM api_key_albums WHERE api_key_id = $1 AND album_id = $2)",
+        )
+        .bind(self.id)
+        .bind(album_id)
+        .fetch_one(&**db)
+        .await
+    }
+
+    /// Replace the albums the key is bound to.
+    /// Binding a key to no albums allows it to act on all albums.
+    pub async fn set_albums(
+        &mut self,
+        tx: &mut Transaction<'_, Postgres>,
+        album_ids: &[String],
+    ) -> DbResult<()> {
+        let album_bound = !album_ids.is_empty();
+        sqlx::query("UPDATE api_keys SET album_bound = $1 WHERE id = $2")
+            .bind(album_bound)
+            .bind(self.id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM api_key_albums WHERE api_key_id = $1")
+            .bind(self.id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query(
+            "INSERT INTO api_key_albums (api_key_id, album_id) SELECT $1, UNNEST($2::VARCHAR[]) \
+            ON CONFLICT DO NOTHING",
+        )
+        .bind(self.id)
+        .bind(album_ids)
+        .execute(&mut *tx)
+        .await?;
+
+        self.album_bound = album_bound;
+        Ok(())
+    }
+
+    /// Record that the key was used just now.
+    pub async fn touch(&mut self, db: &Database) -> DbResult<()> {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        sqlx::query("UPDATE api_keys SET last_used_at = $1 WHERE id = $2")
+            .bind(now)
+            .bind(self.id)
+            .execute(&**db)
+            .await?;
+
+        self.last_used_at = Some(now);
+        Ok(())
+    }
+
+    /// Revoke the key, it can no longer be used afterwards.
+    /// Revoking a key which was already revoked has no effect.
+    pub async fn revoke(&mut self, tx: &mut Transaction<'_, Postgres>) -> DbResult<()> {
+        if self.revoked_at.is_some() {
+            return Ok(());
+        }
+
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        sqlx::query("UPDATE api_keys SET revoked_at = $1 WHERE id = $2")
+            .bind(now)
+            .bind(self.id)
+            .execute(&mut *tx)
+            .await?;
+
+        self.revoked_at = Some(now);
+        Ok(())
+    }
+
+    pub async fn to_proto(self, db: &Database) -> DbResult<proto::ApiKey> {
+        Ok(proto::ApiKey {
+            scopes: self.list_scopes(db).await?,
+            album_ids: self.list_albums(db).await?,
+            id: self.id,
+            description: self.description,
+            is_admin: self.is_admin,
+            created_by: self.created_by,
+            created_at: self.created_at,
+            expires_at: self.expires_at,
+            last_used_at: self.last_used_at,
+            revoked_at: self.revoked_at,
+            album_bound: self.album_bound,
+        })
+    }
+
+    fn hash_key(key: &str) -> String {
+        format!("{:x}", Sha256::digest(key.as_bytes()))
+    }
+}
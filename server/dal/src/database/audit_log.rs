@@ -0,0 +1,235 @@
+use sqlx::{FromRow, Postgres, Transaction};
+use strum_macros::{Display, EnumString};
+use time::OffsetDateTime;
+
+use crate::database::album::_UserType;
+use crate::database::{Database, DbResult, UserType};
+
+/// An administrative or destructive action.
+/// Stored by name, so entries of actions which are renamed or removed remain readable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display, EnumString)]
+pub enum AuditAction {
+    ScopeGranted,
+    ScopeRevoked,
+    ScopeExpiryChanged,
+    AlbumPublished,
+    AlbumUnpublished,
+    AlbumTrashed,
+    AlbumRestored,
+    AlbumMerged,
+    AlbumSplit,
+    AlbumDeleted,
+    AlbumMemberUpdated,
+    AlbumMemberRemoved,
+    PhotoTrashed,
+    PhotoRestored,
+    PhotoDeleted,
+    PhotoMoved,
+    PhotoCopied,
+    CollectionDeleted,
+    ApiKeyCreated,
+    ApiKeyUpdated,
+    ApiKeyRevoked,
+    RoleCreated,
+    RoleUpdated,
+    RoleDeleted,
+    RoleGranted,
+    RoleRevoked,
+    ScopeExpired,
+    SessionExpired,
+    GroupGrantsSynced,
+}
+
+/// The subject of an action.
+#[derive(Debug, Clone)]
+pub enum AuditTarget {
+    User(i32),
+    Album(String),
+    Photo(String),
+    ApiKey(i32),
+    Role(i32),
+    Collection(String),
+}
+
+impl AuditTarget {
+    pub const USER: &'static str = "User";
+    pub const ALBUM: &'static str = "Album";
+    pub const PHOTO: &'static str = "Photo";
+    pub const API_KEY: &'static str = "ApiKey";
+    pub const ROLE: &'static str = "Role";
+    pub const COLLECTION: &'static str = "Collection";
+
+    fn into_parts(self) -> (&'static str, String) {
+        match self {
+            Self::User(id) => (Self::USER, id.to_string()),
+            Self::Album(id) => (Self::ALBUM, id),
+            Self::Photo(id) => (Self::PHOTO, id),
+            Self::ApiKey(id) => (Self::API_KEY, id.to_string()),
+            Self::Role(id) => (Self::ROLE, id.to_string()),
+            Self::Collection(id) => (Self::COLLECTION, id),
+        }
+    }
+}
+
+/// An entry in the audit log.
+/// The log is append-only, the database rejects changes to existing entries.
+#[derive(Debug, Clone)]
+pub struct AuditLogEntry {
+    pub id: i64,
+    pub created_at: i64,
+    /// `None` if the action was taken by chroma itself, e.g. when purging the trash
+    pub actor: Option<UserType>,
+    pub action: String,
+    pub target_type: String,
+    pub target_id: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+#[derive(FromRow)]
+struct _AuditLogEntry {
+    id: i64,
+    created_at: i64,
+    actor_id: Option<i32>,
+    actor_type: Option<_UserType>,
+    action: String,
+    target_type: String,
+    target_id: String,
+    before: Option<String>,
+    after: Option<String>,
+}
+
+impl _AuditLogEntry {
+    fn into_entry(self) -> AuditLogEntry {
+        AuditLogEntry {
+            id: self.id,
+            created_at: self.created_at,
+            actor: match (self.actor_type, self.actor_id) {
+                (Some(_UserType::Koala), Some(id)) => Some(UserType::Koala(id)),
+                (Some(_UserType::Service), Some(id)) => Some(UserType::ApiKey(id)),
+                _ => None,
+            },
+            action: self.action,
+            target_type: self.target_type,
+            target_id: self.target_id,
+            before: self.before,
+            after: self.after,
+        }
+    }
+}
+
+/// Restricts which entries are listed. Unset fields match every entry.
+#[derive(Debug, Default)]
+pub struct AuditLogFilter {
+    pub actor: Option<UserType>,
+    pub action: Option<AuditAction>,
+    pub target_type: Option<String>,
+    pub target_id: Option<String>,
+    /// Only entries created at or after this UNIX timestamp
+    pub since: Option<i64>,
+    /// Only entries created before this UNIX timestamp
+    pub until: Option<i64>,
+    /// Only entries with an ID lower than this, used to page through the log
+    pub before_id: Option<i64>,
+}
+
+impl AuditLogEntry {
+    /// Record an action as part of the transaction in which it is carried out,
+    /// so an action is never committed without its entry, nor the other way around.
+    pub async fn record(
+        tx: &mut Transaction<'_, Postgres>,
+        actor: Option<&UserType>,
+        action: AuditAction,
+        target: AuditTarget,
+        before: Option<String>,
+        after: Option<String>,
+    ) -> DbResult<()> {
+        let (actor_type, actor_id) = match actor {
+            Some(UserType::Koala(id)) => (Some(_UserType::Koala), Some(*id)),
+            Some(UserType::ApiKey(id)) => (Some(_UserType::Service), Some(*id)),
+            None => (None, None),
+        };
+        let (target_type, target_id) = target.into_parts();
+
+        sqlx::query(
+            "INSERT INTO audit_log \
+                (created_at, actor_id, actor_type, action, target_type, target_id, before, after) \
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+        )
+        .bind(OffsetDateTime::now_utc().unix_timestamp())
+        .bind(actor_id)
+        .bind(actor_type)
+        .bind(action.to_string())
+        .bind(target_type)
+        .bind(target_id)
+        .bind(before)
+        .bind(after)
+        .execute(&mut *tx)
+        .await?;
+
+        Ok(())
+    }
+
+    /// List at most `limit` entries matching the filter, newest first.
+    pub async fn list(
+        db: &Database,
+        filter: &AuditLogFilter,
+        limit: i64,
+    ) -> DbResult<Vec<AuditLogEntry>> {
+        let (actor_type, actor_id) = match &filter.actor {
+            Some(UserType::Koala(id)) => (Some(_UserType::Koala), Some(*id)),
+            Some(UserType::ApiKey(id)) => (Some(_UserType::Service), Some(*id)),
+            None => (None, None),
+        };
+
+        let entries: Vec<_AuditLogEntry> = sqlx::query_as(
+            "SELECT * FROM audit_log WHERE \
+                ($1::user_type IS NULL OR actor_type = $1) \
+                AND ($2::INT IS NULL OR actor_id = $2) \
+                AND ($3::TEXT IS NULL OR action = $3) \
+                AND ($4::TEXT IS NULL OR target_type = $4) \
+                AND ($5::TEXT IS NULL OR target_id = $5) \
+                AND ($6::BIGINT IS NULL OR created_at >= $6) \
+                AND ($7::BIGINT IS NULL OR created_at < $7) \
+                AND ($8::BIGINT IS NULL OR id < $8) \
+            ORDER BY id DESC \
+            LIMIT $9",
+        )
+        .bind(actor_type)
+        .bind(actor_id)
+        .bind(filter.action.map(|action| action.to_string()))
+        .bind(&filter.target_type)
+        .bind(&filter.target_id)
+        .bind(filter.since)
+        .bind(filter.until)
+        .bind(filter.before_id)
+        .bind(limit)
+        .fetch_all(&**db)
+        .await?;
+
+        Ok(entries
+            .into_iter()
+            .map(_AuditLogEntry::into_entry)
+            .collect())
+    }
+
+    pub fn to_proto(self) -> proto::AuditLogEntry {
+        let (actor_type, actor_id) = match self.actor {
+            Some(UserType::Koala(id)) => (Some(proto::UserType::Koala as i32), Some(id)),
+            Some(UserType::ApiKey(id)) => (Some(proto::UserType::Service as i32), Some(id)),
+            None => (None, None),
+        };
+
+        proto::AuditLogEntry {
+            id: self.id,
+            created_at: self.created_at,
+            actor_type,
+            actor_id,
+            action: self.action,
+            target_type: self.target_type,
+            target_id: self.target_id,
+            before: self.before,
+            after: self.after,
+        }
+    }
+}
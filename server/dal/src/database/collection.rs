@@ -0,0 +1,201 @@
+use std::borrow::Cow;
+
+use rand::Rng;
+use sqlx::{FromRow, Postgres, Transaction};
+use time::OffsetDateTime;
+
+use crate::database::{Database, DbResult};
+
+/// A collection of albums, e.g. all albums of an academic year or of a committee.
+/// Collections can be nested, an album can be part of multiple collections.
+#[derive(Clone, Debug, FromRow)]
+pub struct AlbumCollection {
+    pub id: String,
+    pub name: String,
+    pub parent_id: Option<String>,
+    pub created_at: i64,
+}
+
+impl AlbumCollection {
+    pub const MAX_NAME_LENGTH: usize = 64;
+    pub const ID_PREFIX: &'static str = "COL_";
+    pub const MAX_ID_LEN: usize = 32;
+
+    fn generate_id() -> String {
+        let random: String = rand::thread_rng()
+            .sample_iter(rand::distributions::Alphanumeric)
+            .take(Self::MAX_ID_LEN - Self::ID_PREFIX.len())
+            .map(char::from)
+            .collect();
+        format!("{}{random}", Self::ID_PREFIX)
+    }
+
+    pub fn to_proto(self) -> proto::AlbumCollection {
+        proto::AlbumCollection {
+            id: self.id,
+            name: self.name,
+            parent_id: self.parent_id,
+            created_at: self.created_at,
+        }
+    }
+
+    pub async fn create(
+        db: &Database,
+        name: impl Into<Cow<'_, str>>,
+        parent: Option<&AlbumCollection>,
+    ) -> DbResult<AlbumCollection> {
+        let name = name.into();
+        let id = Self::generate_id();
+        let created_at = OffsetDateTime::now_utc().unix_timestamp();
+        let parent_id = parent.map(|parent| parent.id.clone());
+
+        sqlx::query(
+            "INSERT INTO album_collections (id, name, parent_id, created_at) VALUES ($1, $2, $3, $4)",
+        )
+        .bind(&id)
+        .bind(&name)
+        .bind(&parent_id)
+        .bind(created_at)
+        .execute(&**db)
+        .await?;
+
+        Ok(Self {
+            id,
+            name: name.to_string(),
+            parent_id,
+            created_at,
+        })
+    }
+
+    pub async fn get_by_id<S: AsRef<str>>(
+        db: &Database,
+        id: S,
+    ) -> DbResult<Option<AlbumCollection>> {
+        sqlx::query_as("SELECT * FROM album_collections WHERE id = $1")
+            .bind(id.as_ref())
+            .fetch_optional(&**db)
+            .await
+    }
+
+    /// List the direct children of a collection.
+    /// If `parent_id` is `None`, all top-level collections are returned.
+    pub async fn list(db: &Database, parent_id: Option<&str>) -> DbResult<Vec<AlbumCollection>> {
+        sqlx::query_as(
+            "SELECT * FROM album_collections WHERE parent_id IS NOT DISTINCT FROM $1 ORDER BY name",
+        )
+        .bind(parent_id)
+        .fetch_all(&**db)
+        .await
+    }
+
+    /// List the IDs of this collection and all collections above it.
+    pub async fn list_ancestor_ids(&self, db: &Database) -> DbResult<Vec<String>> {
+        sqlx::query_scalar(
+            "WITH RECURSIVE ancestors AS ( \
+                SELECT id, parent_id FROM album_collections WHERE id = $1 \
+                UNION \
+                SELECT c.id, c.parent_id FROM album_collections c JOIN ancestors a ON c.id = a.parent_id \
+            ) SELECT id FROM ancestors",
+        )
+        .bind(&self.id)
+        .fetch_all(&**db)
+        .await
+    }
+
+    pub async fn update_name(
+        &mut self,
+        tx: &mut Transaction<'_, Postgres>,
+        new_name: impl Into<Cow<'_, str>>,
+    ) -> DbResult<()> {
+        let new_name = new_name.into();
+        sqlx::query("UPDATE album_collections SET name = $1 WHERE id = $2")
+            .bind(&new_name)
+            .bind(&self.id)
+            .execute(&mut *tx)
+            .await?;
+        self.name = new_name.to_string();
+        Ok(())
+    }
+
+    /// Move this collection to a new parent.
+    /// If `parent` is `None`, the collection becomes a top-level collection.
+    /// The caller must make sure this does not introduce a cycle,
+    /// see [Self::list_ancestor_ids].
+    pub async fn set_parent(
+        &mut self,
+        tx: &mut Transaction<'_, Postgres>,
+        parent: Option<&AlbumCollection>,
+    ) -> DbResult<()> {
+        let parent_id = parent.map(|parent| parent.id.clone());
+        sqlx::query("UPDATE album_collections SET parent_id = $1 WHERE id = $2")
+            .bind(&parent_id)
+            .bind(&self.id)
+            .execute(&mut *tx)
+            .await?;
+        self.parent_id = parent_id;
+        Ok(())
+    }
+
+    /// Delete the collection.
+    /// Albums in the collection are not deleted.
+    /// Child collections are moved to the parent of this collection.
+    pub async fn delete(self, tx: &mut Transaction<'_, Postgres>) -> DbResult<()> {
+        sqlx::query("UPDATE album_collections SET parent_id = $1 WHERE parent_id = $2")
+            .bind(&self.parent_id)
+            .bind(&self.id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM album_collection_members WHERE collection_id = $1")
+            .bind(&self.id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM album_collections WHERE id = $1")
+            .bind(&self.id)
+            .execute(&mut *tx)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Add an album to the collection.
+    /// Adding an album which is already part of the collection has no effect.
+    pub async fn add_album<S: AsRef<str>>(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        album_id: S,
+    ) -> DbResult<()> {
+        sqlx::query(
+            "INSERT INTO album_collection_members (collection_id, album_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+        )
+        .bind(&self.id)
+        .bind(album_id.as_ref())
+        .execute(&mut *tx)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn remove_album<S: AsRef<str>>(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        album_id: S,
+    ) -> DbResult<()> {
+        sqlx::query(
+            "DELETE FROM album_collection_members WHERE collection_id = $1 AND album_id = $2",
+        )
+        .bind(&self.id)
+        .bind(album_id.as_ref())
+        .execute(&mut *tx)
+        .await?;
+        Ok(())
+    }
+
+    /// List the IDs of all albums directly in this collection.
+    pub async fn list_album_ids(&self, db: &Database) -> DbResult<Vec<String>> {
+        sqlx::query_scalar("SELECT album_id FROM album_collection_members WHERE collection_id = $1")
+            .bind(&self.id)
+            .fetch_all(&**db)
+            .await
+    }
+}
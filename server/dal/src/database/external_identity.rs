@@ -0,0 +1,54 @@
+use sqlx::{Postgres, Transaction};
+
+use crate::database::{Database, DbResult};
+
+/// Links a user of an OpenID Connect provider to a chroma user.
+/// Koala users need no link, their Koala ID is their user ID.
+/// Linked users have negative IDs, so they never collide with Koala IDs.
+pub struct ExternalIdentity;
+
+impl ExternalIdentity {
+    /// Find the ID of the user the subject of the issuer is linked to.
+    pub async fn get_user_id<S1: AsRef<str>, S2: AsRef<str>>(
+        db: &Database,
+        issuer: S1,
+        subject: S2,
+    ) -> DbResult<Option<i32>> {
+        sqlx::query_scalar(
+            "SELECT koala_id FROM external_identities WHERE issuer = $1 AND subject = $2",
+        )
+        .bind(issuer.as_ref())
+        .bind(subject.as_ref())
+        .fetch_optional(&**db)
+        .await
+    }
+
+    /// Reserve an ID for a new user, which should be linked with [Self::link] once created.
+    pub async fn next_user_id(tx: &mut Transaction<'_, Postgres>) -> DbResult<i32> {
+        sqlx::query_scalar("SELECT nextval('external_user_ids')::INT")
+            .fetch_one(&mut *tx)
+            .await
+    }
+
+    /// Link the subject of the issuer to a user created in the same transaction.
+    /// Returns `false` if the subject is linked already,
+    /// e.g. because a concurrent login created a user for it first.
+    pub async fn link<S1: AsRef<str>, S2: AsRef<str>>(
+        tx: &mut Transaction<'_, Postgres>,
+        issuer: S1,
+        subject: S2,
+        user_id: i32,
+    ) -> DbResult<bool> {
+        let result = sqlx::query(
+            "INSERT INTO external_identities (issuer, subject, koala_id) VALUES ($1, $2, $3) \
+            ON CONFLICT (issuer, subject) DO NOTHING",
+        )
+        .bind(issuer.as_ref())
+        .bind(subject.as_ref())
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+}
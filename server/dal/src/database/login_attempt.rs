@@ -0,0 +1,63 @@
+use sqlx::FromRow;
+use time::OffsetDateTime;
+
+use crate::database::{Database, DbResult};
+
+/// A login with an OpenID Connect provider which has started, but not completed yet.
+/// Identified by the state sent to the provider, which the provider sends back once
+/// the user has logged in.
+#[derive(Debug, FromRow)]
+pub struct LoginAttempt {
+    pub state: String,
+    pub pkce_verifier: String,
+    pub nonce: String,
+    pub created_at: i64,
+}
+
+impl LoginAttempt {
+    /// How long a user has to complete a login
+    pub const MAX_AGE_SECS: i64 = 10 * 60;
+
+    pub async fn create<S1: AsRef<str>, S2: AsRef<str>, S3: AsRef<str>>(
+        db: &Database,
+        state: S1,
+        pkce_verifier: S2,
+        nonce: S3,
+    ) -> DbResult<()> {
+        sqlx::query(
+            "INSERT INTO login_attempts (state, pkce_verifier, nonce, created_at) \
+            VALUES ($1, $2, $3, $4)",
+        )
+        .bind(state.as_ref())
+        .bind(pkce_verifier.as_ref())
+        .bind(nonce.as_ref())
+        .bind(OffsetDateTime::now_utc().unix_timestamp())
+        .execute(&**db)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Take the attempt with the state, so it cannot be completed twice.
+    /// Returns `None` if there is no such attempt, or if it is too old.
+    pub async fn take<S: AsRef<str>>(db: &Database, state: S) -> DbResult<Option<LoginAttempt>> {
+        let attempt: Option<LoginAttempt> =
+            sqlx::query_as("DELETE FROM login_attempts WHERE state = $1 RETURNING *")
+                .bind(state.as_ref())
+                .fetch_optional(&**db)
+                .await?;
+
+        let cutoff = OffsetDateTime::now_utc().unix_timestamp() - Self::MAX_AGE_SECS;
+        Ok(attempt.filter(|attempt| attempt.created_at > cutoff))
+    }
+
+    /// Delete all attempts which are too old to be completed.
+    /// Returns the number of deleted attempts.
+    pub async fn delete_expired(db: &Database) -> DbResult<u64> {
+        let result = sqlx::query("DELETE FROM login_attempts WHERE created_at <= $1")
+            .bind(OffsetDateTime::now_utc().unix_timestamp() - Self::MAX_AGE_SECS)
+            .execute(&**db)
+            .await?;
+        Ok(result.rows_affected())
+    }
+}
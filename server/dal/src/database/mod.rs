@@ -8,13 +8,35 @@ use sqlx::{Pool, Postgres};
 use thiserror::Error;
 
 pub use album::*;
+pub use album_member::*;
+pub use api_key::*;
+pub use audit_log::*;
+pub use collection::*;
+pub use external_identity::*;
+pub use login_attempt::*;
 pub use photo::*;
-pub use service_token_user::*;
+pub use photo_object::*;
+pub use role::*;
+pub use session::*;
+pub use storage_incident::*;
+pub use synced_grants::*;
+pub use tombstone::*;
 pub use user::*;
 
 mod album;
+mod album_member;
+mod api_key;
+mod audit_log;
+mod collection;
+mod external_identity;
+mod login_attempt;
 mod photo;
-mod service_token_user;
+mod photo_object;
+mod role;
+mod session;
+mod storage_incident;
+mod synced_grants;
+mod tombstone;
 mod user;
 
 pub type DbResult<T> = Result<T, DatabaseError>;
@@ -1,11 +1,12 @@
 use rand::Rng;
-use sqlx::{FromRow, Type};
+use sqlx::{FromRow, Postgres, Transaction, Type};
 use strum_macros::Display;
+use time::OffsetDateTime;
 
 use proto::photo_respone::Response;
 use proto::PhotoRespone;
 
-use crate::database::{Album, Database, DbResult};
+use crate::database::{Album, Database, DbResult, PhotoObject, StorageTombstone};
 use crate::storage_engine::Storage;
 use crate::DalError;
 
@@ -15,6 +16,7 @@ pub struct Photo<'a> {
     pub id: String,
     pub album_id: String,
     pub created_at: i64,
+    pub deleted_at: Option<i64>,
 }
 
 #[derive(FromRow)]
@@ -22,9 +24,10 @@ struct _Photo {
     pub id: String,
     pub album_id: String,
     pub created_at: i64,
+    pub deleted_at: Option<i64>,
 }
 
-#[derive(Debug, Clone, Type, Display, PartialEq, Eq)]
+#[derive(Debug, Clone, Type, Display, PartialEq, Eq, Hash)]
 #[sqlx(type_name = "photo_quality")]
 pub enum PhotoQuality {
     Original,
@@ -56,6 +59,7 @@ impl _Photo {
             id: self.id,
             album_id: self.album_id,
             created_at: self.created_at,
+            deleted_at: self.deleted_at,
         }
     }
 }
@@ -81,7 +85,7 @@ impl<'a> Photo<'a> {
             if let Some(s3_url) = PhotoS3Url::get_for_photo(self.db, &self.id, &quality).await? {
                 s3_url.s3_url
             } else {
-                let url = storage.get_photo_url_by_id(&self.id, &quality).await?;
+                let url = storage.get_photo_url(&self.object_key(&quality).await?);
                 let _ = PhotoS3Url::new(self.db, self.id.clone(), url.clone(), quality).await;
                 url
             };
@@ -90,6 +94,7 @@ impl<'a> Photo<'a> {
             id: self.id.clone(),
             album_id: self.album_id.clone(),
             created_at: self.created_at,
+            deleted_at: self.deleted_at,
             data_type: proto::PhotoResponseType::Url as i32,
             data: Some(PhotoRespone {
                 response: Some(Response::Url(url)),
@@ -114,11 +119,14 @@ impl<'a> Photo<'a> {
             PhotoQuality::Original
         };
 
-        let photo_bytes = storage.get_photo_bytes_by_id(&self.id, &quality).await?;
+        let photo_bytes = storage
+            .get_photo_bytes(&self.object_key(&quality).await?)
+            .await?;
         Ok(proto::Photo {
             id: self.id,
             album_id: self.album_id,
             created_at: self.created_at,
+            deleted_at: self.deleted_at,
             data_type: proto::PhotoResponseType::InResponse as i32,
             data: Some(PhotoRespone {
                 response: Some(Response::Bytes(photo_bytes)),
@@ -138,56 +146,220 @@ impl<'a> Photo<'a> {
     pub async fn create(db: &'a Database, album: &Album, created_at: i64) -> DbResult<Photo<'a>> {
         let id = Self::generate_id();
 
-        sqlx::query("INSERT INTO photo_metadata (id, album_id, created_at) VALUES ($1, $2, $3)")
-            .bind(&id)
-            .bind(&album.id)
-            .bind(created_at)
-            .execute(&**db)
-            .await?;
+        sqlx::query(
+            "INSERT INTO photo_metadata (id, album_id, created_at, uploaded_at) VALUES ($1, $2, $3, $4)",
+        )
+        .bind(&id)
+        .bind(&album.id)
+        .bind(created_at)
+        .bind(OffsetDateTime::now_utc().unix_timestamp())
+        .execute(&**db)
+        .await?;
 
         Ok(Self {
             db,
             id,
             album_id: album.id.clone(),
             created_at,
+            deleted_at: None,
         })
     }
 
+    /// Get a photo by its ID.
+    /// Photos in the trash, or in an album in the trash, are not returned.
     pub async fn get_by_id<S: AsRef<str>>(db: &'a Database, id: S) -> DbResult<Option<Photo<'a>>> {
-        let photo: Option<_Photo> =
-            sqlx::query_as("SELECT id, album_id, created_at FROM photo_metadata WHERE id = $1")
-                .bind(id.as_ref())
-                .fetch_optional(&**db)
-                .await?;
+        let photo: Option<_Photo> = sqlx::query_as(
+            "SELECT id, album_id, created_at, deleted_at FROM photo_metadata \
+            WHERE \
+                id = $1 \
+                AND deleted_at IS NULL \
+                AND album_id IN (SELECT id FROM album_metadata WHERE deleted_at IS NULL)",
+        )
+        .bind(id.as_ref())
+        .fetch_optional(&**db)
+        .await?;
 
         Ok(photo.map(|photo| photo.into_photo(db)))
     }
 
-    pub async fn delete(self) -> DbResult<()> {
-        let mut tx = self.db.begin().await?;
+    /// Get a photo in the trash by its ID.
+    pub async fn get_trashed_by_id<S: AsRef<str>>(
+        db: &'a Database,
+        id: S,
+    ) -> DbResult<Option<Photo<'a>>> {
+        let photo: Option<_Photo> = sqlx::query_as(
+            "SELECT id, album_id, created_at, deleted_at FROM photo_metadata WHERE id = $1 AND deleted_at IS NOT NULL",
+        )
+        .bind(id.as_ref())
+        .fetch_optional(&**db)
+        .await?;
+
+        Ok(photo.map(|photo| photo.into_photo(db)))
+    }
+
+    /// Move the photo to the trash.
+    /// If the photo is the cover of its album, the album will no longer have a cover.
+    /// Restoring the photo does not make it the album cover again.
+    pub async fn trash(&mut self, tx: &mut Transaction<'_, Postgres>) -> DbResult<()> {
+        let deleted_at = OffsetDateTime::now_utc().unix_timestamp();
+
         // Remove the photo from the album cover
         sqlx::query(
             "UPDATE album_metadata SET cover_photo_id = NULL WHERE id = $1 AND cover_photo_id = $2",
         )
         .bind(&self.album_id)
         .bind(&self.id)
-        .execute(&mut tx)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query("UPDATE photo_metadata SET deleted_at = $1 WHERE id = $2")
+            .bind(deleted_at)
+            .bind(&self.id)
+            .execute(&mut *tx)
+            .await?;
+
+        self.deleted_at = Some(deleted_at);
+        Ok(())
+    }
+
+    /// Restore the photo from the trash.
+    pub async fn restore(&mut self, tx: &mut Transaction<'_, Postgres>) -> DbResult<()> {
+        sqlx::query("UPDATE photo_metadata SET deleted_at = NULL WHERE id = $1")
+            .bind(&self.id)
+            .execute(&mut *tx)
+            .await?;
+
+        self.deleted_at = None;
+        Ok(())
+    }
+
+    /// Permanently delete the photo's metadata.
+    /// The stored objects of the photo are deleted afterwards, see [StorageTombstone].
+    pub async fn delete(self, tx: &mut Transaction<'_, Postgres>) -> DbResult<()> {
+        StorageTombstone::insert_for_photos(tx, &[self.id.clone()]).await?;
+
+        // Remove the photo from the album cover
+        sqlx::query(
+            "UPDATE album_metadata SET cover_photo_id = NULL WHERE id = $1 AND cover_photo_id = $2",
+        )
+        .bind(&self.album_id)
+        .bind(&self.id)
+        .execute(&mut *tx)
         .await?;
 
         // Remove the photo metadata
         sqlx::query("DELETE FROM photo_metadata WHERE id = $1")
             .bind(&self.id)
-            .execute(&mut tx)
+            .execute(&mut *tx)
             .await?;
 
-        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Move a set of photos to another album.
+    /// Any explicit ordering of the photos is discarded.
+    /// Photos that are the cover of their current album are removed as cover from that album.
+    ///
+    /// # Errors
+    ///
+    /// If a database error occurs. The transaction should not be committed in that case.
+    pub async fn move_to_album(
+        tx: &mut Transaction<'_, Postgres>,
+        photo_ids: &[String],
+        album: &Album,
+    ) -> DbResult<()> {
+        // A photo can only be the cover of the album it is in
+        sqlx::query(
+            "UPDATE album_metadata SET cover_photo_id = NULL WHERE cover_photo_id = ANY($1) AND id <> $2",
+        )
+        .bind(photo_ids)
+        .bind(&album.id)
+        .execute(&mut *tx)
+        .await?;
+
+        // The photos are placed at the end of the album, ordered by their timestamp
+        sqlx::query("UPDATE photo_metadata SET album_id = $1, position = NULL WHERE id = ANY($2)")
+            .bind(&album.id)
+            .bind(photo_ids)
+            .execute(&mut *tx)
+            .await?;
 
         Ok(())
     }
 
+    /// Copy the stored objects of this photo for a copy in another album.
+    /// The objects of all qualities are copied, so the copy does not have to be processed again.
+    /// The copy itself is not recorded yet, see [PhotoCopy].
+    /// If copying fails, the objects which were already copied are deleted afterwards.
+    ///
+    /// # Errors
+    ///
+    /// - If a database error occurs
+    /// - If copying the stored objects fails
+    pub async fn copy_objects(
+        &self,
+        storage: &Storage,
+        album: &Album,
+    ) -> Result<PhotoCopy, DalError> {
+        let mut copy = PhotoCopy {
+            id: Self::generate_id(),
+            source_id: self.id.clone(),
+            album_id: album.id.clone(),
+            created_at: self.created_at,
+            objects: Vec::new(),
+        };
+
+        for quality in [
+            PhotoQuality::Original,
+            PhotoQuality::W400,
+            PhotoQuality::W1600,
+        ] {
+            let object = self.copy_object(storage, &copy, &quality).await;
+            match object {
+                Ok(Some(object)) => copy.objects.push(object),
+                Ok(None) => {}
+                Err(e) => {
+                    // Don't leave objects behind which no photo refers to
+                    let mut tx = self.db.begin().await?;
+                    copy.discard(&mut tx).await?;
+                    tx.commit().await?;
+                    return Err(e);
+                }
+            }
+        }
+
+        Ok(copy)
+    }
+
+    /// Copy the stored object of a quality of this photo for a copy.
+    /// Returns `None` if the quality has not been created.
+    async fn copy_object(
+        &self,
+        storage: &Storage,
+        copy: &PhotoCopy,
+        quality: &PhotoQuality,
+    ) -> Result<Option<CopiedObject>, DalError> {
+        let is_created = self.is_quality_created(quality).await?;
+
+        // The original is always uploaded, other qualities only once processing has finished
+        if quality.ne(&PhotoQuality::Original) && !is_created {
+            return Ok(None);
+        }
+
+        let from_key = self.object_key(quality).await?;
+        let key =
+            PhotoObject::transfer(storage, &from_key, &copy.album_id, &copy.id, quality).await?;
+
+        Ok(Some(CopiedObject {
+            quality: quality.clone(),
+            url: is_created.then(|| storage.get_photo_url(&key)),
+            key,
+        }))
+    }
+
     pub async fn list(db: &'a Database) -> DbResult<Vec<Photo<'a>>> {
         let selfs: Vec<_Photo> =
-            sqlx::query_as("SELECT id, album_id, created_at FROM photo_metadata")
+            sqlx::query_as("SELECT id, album_id, created_at, deleted_at FROM photo_metadata WHERE deleted_at IS NULL AND album_id IN (SELECT id FROM album_metadata WHERE deleted_at IS NULL) ORDER BY album_id, position ASC NULLS LAST, created_at ASC")
                 .fetch_all(&**db)
                 .await?;
         Ok(selfs
@@ -196,12 +368,15 @@ impl<'a> Photo<'a> {
             .collect())
     }
 
+    /// List all photos in an album.
+    /// Photos with an explicit position come first, in order of that position.
+    /// All other photos follow, ordered by their timestamp.
     pub async fn list_in_album<S: AsRef<str>>(
         db: &'a Database,
         album_id: S,
     ) -> DbResult<Vec<Photo<'a>>> {
         let selfs: Vec<_Photo> = sqlx::query_as(
-            "SELECT id, album_id, created_at FROM photo_metadata WHERE album_id = $1",
+            "SELECT id, album_id, created_at, deleted_at FROM photo_metadata WHERE album_id = $1 AND deleted_at IS NULL ORDER BY position ASC NULLS LAST, created_at ASC",
         )
         .bind(album_id.as_ref())
         .fetch_all(&**db)
@@ -212,6 +387,138 @@ impl<'a> Photo<'a> {
             .collect())
     }
 
+    /// List all photos in an album, including the photos in the trash.
+    pub async fn list_in_album_including_trashed<S: AsRef<str>>(
+        db: &'a Database,
+        album_id: S,
+    ) -> DbResult<Vec<Photo<'a>>> {
+        let selfs: Vec<_Photo> = sqlx::query_as(
+            "SELECT id, album_id, created_at, deleted_at FROM photo_metadata WHERE album_id = $1",
+        )
+        .bind(album_id.as_ref())
+        .fetch_all(&**db)
+        .await?;
+        Ok(selfs
+            .into_iter()
+            .map(|photo| photo.into_photo(db))
+            .collect())
+    }
+
+    /// List all photos in the trash, most recently deleted first.
+    /// Photos in an album that is in the trash are not included, unless they were deleted on their own.
+    pub async fn list_trashed(db: &'a Database) -> DbResult<Vec<Photo<'a>>> {
+        let selfs: Vec<_Photo> = sqlx::query_as(
+            "SELECT id, album_id, created_at, deleted_at FROM photo_metadata WHERE deleted_at IS NOT NULL ORDER BY deleted_at DESC",
+        )
+        .fetch_all(&**db)
+        .await?;
+        Ok(selfs
+            .into_iter()
+            .map(|photo| photo.into_photo(db))
+            .collect())
+    }
+
+    /// List all photos which were moved to the trash before `timestamp`.
+    pub async fn list_trashed_before(db: &'a Database, timestamp: i64) -> DbResult<Vec<Photo<'a>>> {
+        let selfs: Vec<_Photo> = sqlx::query_as(
+            "SELECT id, album_id, created_at, deleted_at FROM photo_metadata WHERE deleted_at < $1",
+        )
+        .bind(timestamp)
+        .fetch_all(&**db)
+        .await?;
+        Ok(selfs
+            .into_iter()
+            .map(|photo| photo.into_photo(db))
+            .collect())
+    }
+
+    /// List all photos, including photos in the trash.
+    pub async fn list_including_trashed(db: &'a Database) -> DbResult<Vec<Photo<'a>>> {
+        let selfs: Vec<_Photo> =
+            sqlx::query_as("SELECT id, album_id, created_at, deleted_at FROM photo_metadata")
+                .fetch_all(&**db)
+                .await?;
+        Ok(selfs
+            .into_iter()
+            .map(|photo| photo.into_photo(db))
+            .collect())
+    }
+
+    /// List the IDs of all photos uploaded at or after `timestamp`, including trashed photos.
+    /// Photos uploaded before their upload time was recorded are not listed.
+    pub async fn list_ids_uploaded_since(db: &Database, timestamp: i64) -> DbResult<Vec<String>> {
+        sqlx::query_scalar("SELECT id FROM photo_metadata WHERE uploaded_at >= $1")
+            .bind(timestamp)
+            .fetch_all(&**db)
+            .await
+    }
+
+    /// List at most `limit` photos ordered by ID, including trashed photos.
+    /// Only photos with an ID after `after` are listed, so all photos can be paged through.
+    pub async fn list_page_including_trashed(
+        db: &'a Database,
+        after: Option<&str>,
+        limit: i64,
+    ) -> DbResult<Vec<Photo<'a>>> {
+        let selfs: Vec<_Photo> = sqlx::query_as(
+            "SELECT id, album_id, created_at, deleted_at FROM photo_metadata \
+            WHERE $1::VARCHAR IS NULL OR id > $1 ORDER BY id ASC LIMIT $2",
+        )
+        .bind(after)
+        .bind(limit)
+        .fetch_all(&**db)
+        .await?;
+        Ok(selfs
+            .into_iter()
+            .map(|photo| photo.into_photo(db))
+            .collect())
+    }
+
+    /// Set the explicit order of the photos in an album.
+    /// The position of each photo is its index in `photo_ids`.
+    /// Photos in the album which are not in `photo_ids` lose their explicit position.
+    ///
+    /// # Errors
+    ///
+    /// If a database error occurs
+    pub async fn set_order_in_album<S: AsRef<str>>(
+        db: &Database,
+        album_id: S,
+        photo_ids: &[String],
+    ) -> DbResult<()> {
+        let mut tx = db.begin().await?;
+
+        sqlx::query("UPDATE photo_metadata SET position = NULL WHERE album_id = $1")
+            .bind(album_id.as_ref())
+            .execute(&mut tx)
+            .await?;
+
+        sqlx::query(
+            "UPDATE photo_metadata \
+                SET position = ordering.position - 1 \
+            FROM \
+                UNNEST($1::VARCHAR[]) WITH ORDINALITY AS ordering(id, position) \
+            WHERE photo_metadata.id = ordering.id AND photo_metadata.album_id = $2",
+        )
+        .bind(photo_ids)
+        .bind(album_id.as_ref())
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// The key under which a quality of the photo is stored.
+    ///
+    /// # Errors
+    ///
+    /// If a database error occurs
+    pub async fn object_key(&self, quality: &PhotoQuality) -> DbResult<String> {
+        PhotoObject::key_for(self.db, &self.id, quality).await
+    }
+
     /// Check whether an image quality has been created yet.
     ///
     /// # Errors
@@ -224,6 +531,80 @@ impl<'a> Photo<'a> {
     }
 }
 
+/// A copy of a photo of which the stored objects have been copied, but which has not been recorded yet.
+/// A copy should either be recorded with [Self::insert],
+/// or its objects should be deleted with [Self::discard].
+pub struct PhotoCopy {
+    /// The ID of the copy
+    pub id: String,
+    /// The ID of the photo which was copied
+    pub source_id: String,
+    pub album_id: String,
+    created_at: i64,
+    objects: Vec<CopiedObject>,
+}
+
+struct CopiedObject {
+    quality: PhotoQuality,
+    key: String,
+    /// `None` if the quality has not been created yet, i.e. processing has not finished
+    url: Option<String>,
+}
+
+impl PhotoCopy {
+    /// Record the copy and the keys of its objects.
+    ///
+    /// # Errors
+    ///
+    /// If a database error occurs. The transaction should not be committed in that case.
+    pub async fn insert(&self, tx: &mut Transaction<'_, Postgres>) -> DbResult<()> {
+        let copied_at = OffsetDateTime::now_utc().unix_timestamp();
+        sqlx::query(
+            "INSERT INTO photo_metadata (id, album_id, created_at, uploaded_at) VALUES ($1, $2, $3, $4)",
+        )
+        .bind(&self.id)
+        .bind(&self.album_id)
+        .bind(self.created_at)
+        .bind(copied_at)
+        .execute(&mut *tx)
+        .await?;
+
+        for object in &self.objects {
+            sqlx::query(
+                "INSERT INTO photo_objects (photo_id, quality, object_key, created_at) VALUES ($1, $2, $3, $4)",
+            )
+            .bind(&self.id)
+            .bind(&object.quality)
+            .bind(&object.key)
+            .bind(copied_at)
+            .execute(&mut *tx)
+            .await?;
+
+            if let Some(url) = &object.url {
+                sqlx::query(
+                    "INSERT INTO photo_s3_urls (photo_id, s3_url, quality) VALUES ($1, $2, $3)",
+                )
+                .bind(&self.id)
+                .bind(url)
+                .bind(&object.quality)
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Delete the copied objects afterwards, see [StorageTombstone].
+    pub async fn discard(self, tx: &mut Transaction<'_, Postgres>) -> DbResult<()> {
+        for object in &self.objects {
+            StorageTombstone::insert_in(tx, &object.key).await?;
+        }
+
+        Ok(())
+    }
+}
+
 impl PhotoS3Url {
     pub async fn new(
         driver: &Database,
@@ -256,4 +637,19 @@ impl PhotoS3Url {
             .fetch_optional(&**driver)
             .await
     }
+
+    pub async fn list(driver: &Database) -> DbResult<Vec<Self>> {
+        sqlx::query_as("SELECT * FROM photo_s3_urls")
+            .fetch_all(&**driver)
+            .await
+    }
+
+    pub async fn delete(self, driver: &Database) -> DbResult<()> {
+        sqlx::query("DELETE FROM photo_s3_urls WHERE photo_id = $1 AND quality = $2")
+            .bind(&self.photo_id)
+            .bind(&self.quality)
+            .execute(&**driver)
+            .await?;
+        Ok(())
+    }
 }
@@ -0,0 +1,197 @@
+use sqlx::FromRow;
+use time::OffsetDateTime;
+
+use crate::database::{Database, DbResult, PhotoQuality, StorageTombstone};
+use crate::storage_engine::error::StorageError;
+use crate::storage_engine::{KeyLayout, Storage};
+use crate::DalError;
+
+/// The key under which a variant of a photo is stored.
+/// Variants stored before keys were recorded have no [PhotoObject],
+/// they are stored in the [KeyLayout::LEGACY] layout.
+#[derive(Debug, FromRow)]
+pub struct PhotoObject {
+    pub photo_id: String,
+    pub quality: PhotoQuality,
+    pub object_key: String,
+    pub created_at: i64,
+}
+
+impl PhotoObject {
+    /// The key under which a variant of a photo is stored.
+    pub async fn key_for(
+        db: &Database,
+        photo_id: &str,
+        quality: &PhotoQuality,
+    ) -> DbResult<String> {
+        let key: Option<String> = sqlx::query_scalar(
+            "SELECT object_key FROM photo_objects WHERE photo_id = $1 AND quality = $2",
+        )
+        .bind(photo_id)
+        .bind(quality)
+        .fetch_optional(&**db)
+        .await?;
+
+        Ok(key.unwrap_or_else(|| Self::legacy_key(photo_id, quality)))
+    }
+
+    /// The key under which a variant of a photo is stored if it has no recorded key.
+    pub fn legacy_key(photo_id: &str, quality: &PhotoQuality) -> String {
+        KeyLayout::legacy().render("", photo_id, quality, None)
+    }
+
+    pub async fn list(db: &Database) -> DbResult<Vec<PhotoObject>> {
+        sqlx::query_as("SELECT * FROM photo_objects")
+            .fetch_all(&**db)
+            .await
+    }
+
+    /// Store a new variant of a photo in the configured key layout.
+    /// Returns the key under which the variant was stored.
+    ///
+    /// # Errors
+    ///
+    /// - If storing the object fails
+    /// - If a database error occurs
+    pub async fn upload(
+        db: &Database,
+        storage: &Storage,
+        album_id: &str,
+        photo_id: &str,
+        quality: &PhotoQuality,
+        bytes: Vec<u8>,
+    ) -> Result<String, DalError> {
+        let key = storage
+            .key_layout()
+            .render(album_id, photo_id, quality, Some(&bytes));
+        storage.create_photo(&key, bytes).await?;
+        Self::record(db, storage, photo_id, quality, &key, None).await?;
+
+        Ok(key)
+    }
+
+    /// Move a stored variant of a photo to the key the configured key layout prescribes.
+    /// The object under the previous key is deleted afterwards, see [StorageTombstone].
+    /// Returns the new key, or `None` if the variant is already stored under the right key.
+    ///
+    /// # Errors
+    ///
+    /// - If copying the object fails
+    /// - If a database error occurs
+    pub async fn relocate(
+        db: &Database,
+        storage: &Storage,
+        album_id: &str,
+        photo_id: &str,
+        quality: &PhotoQuality,
+    ) -> Result<Option<String>, DalError> {
+        let current = Self::key_for(db, photo_id, quality).await?;
+        if storage
+            .key_layout()
+            .matches_photo(&current, album_id, photo_id, quality)
+        {
+            return Ok(None);
+        }
+
+        let key = Self::transfer(storage, &current, album_id, photo_id, quality).await?;
+        Self::record(db, storage, photo_id, quality, &key, Some(&current)).await?;
+
+        Ok(Some(key))
+    }
+
+    /// Copy an object to the key the configured key layout prescribes for a variant.
+    /// If the layout is content-addressed, the object has to be downloaded to compute its hash.
+    pub(crate) async fn transfer(
+        storage: &Storage,
+        from_key: &str,
+        album_id: &str,
+        photo_id: &str,
+        quality: &PhotoQuality,
+    ) -> Result<String, StorageError> {
+        let layout = storage.key_layout();
+
+        if layout.uses_hash() {
+            let bytes = storage.get_photo_bytes(from_key).await?;
+            let key = layout.render(album_id, photo_id, quality, Some(&bytes));
+            if key.ne(from_key) {
+                storage.create_photo(&key, bytes).await?;
+            }
+            Ok(key)
+        } else {
+            let key = layout.render(album_id, photo_id, quality, None);
+            if key.ne(from_key) {
+                storage.copy_photo(from_key, &key).await?;
+            }
+            Ok(key)
+        }
+    }
+
+    /// Record the key under which a variant of a photo is stored.
+    /// The previously recorded key and `replaces` are deleted if they differ from the new key.
+    /// If the photo no longer exists, the new object is deleted instead.
+    async fn record(
+        db: &Database,
+        storage: &Storage,
+        photo_id: &str,
+        quality: &PhotoQuality,
+        object_key: &str,
+        replaces: Option<&str>,
+    ) -> DbResult<()> {
+        let mut tx = db.begin().await?;
+
+        // Lock the photo, so it cannot be deleted while its key is recorded
+        let photo: Option<String> =
+            sqlx::query_scalar("SELECT id FROM photo_metadata WHERE id = $1 FOR UPDATE")
+                .bind(photo_id)
+                .fetch_optional(&mut tx)
+                .await?;
+
+        if photo.is_none() {
+            StorageTombstone::insert_in(&mut tx, object_key).await?;
+            tx.commit().await?;
+            return Ok(());
+        }
+
+        let previous: Option<String> = sqlx::query_scalar(
+            "SELECT object_key FROM photo_objects WHERE photo_id = $1 AND quality = $2",
+        )
+        .bind(photo_id)
+        .bind(quality)
+        .fetch_optional(&mut tx)
+        .await?;
+
+        sqlx::query(
+            "INSERT INTO photo_objects (photo_id, quality, object_key, created_at) VALUES ($1, $2, $3, $4) \
+            ON CONFLICT (photo_id, quality) DO UPDATE SET object_key = EXCLUDED.object_key, created_at = EXCLUDED.created_at",
+        )
+        .bind(photo_id)
+        .bind(quality)
+        .bind(object_key)
+        .bind(OffsetDateTime::now_utc().unix_timestamp())
+        .execute(&mut tx)
+        .await?;
+
+        // The key may have been used before, e.g. when relaying out to a previous layout
+        sqlx::query("DELETE FROM storage_tombstones WHERE object_key = $1")
+            .bind(object_key)
+            .execute(&mut tx)
+            .await?;
+
+        for old_key in [previous.as_deref(), replaces].into_iter().flatten() {
+            if old_key.ne(object_key) {
+                StorageTombstone::insert_in(&mut tx, old_key).await?;
+            }
+        }
+
+        sqlx::query("UPDATE photo_s3_urls SET s3_url = $1 WHERE photo_id = $2 AND quality = $3")
+            .bind(storage.get_photo_url(object_key))
+            .bind(photo_id)
+            .bind(quality)
+            .execute(&mut tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,217 @@
+use sqlx::{FromRow, Postgres, Transaction};
+use time::OffsetDateTime;
+
+use crate::database::{Database, DbResult};
+
+/// A named set of scopes, which may be assigned to users.
+/// Users hold the scopes of all their roles in addition to the scopes granted to them directly.
+#[derive(Debug, Clone, FromRow)]
+pub struct Role {
+    pub id: i32,
+    pub name: String,
+    pub description: String,
+    pub created_at: i64,
+}
+
+impl Role {
+    /// Create a new role with the provided scopes.
+    ///
+    /// # Errors
+    ///
+    /// - If a role with the name already exists
+    /// - If a database error occurs
+    pub async fn create<S1: AsRef<str>, S2: AsRef<str>>(
+        tx: &mut Transaction<'_, Postgres>,
+        name: S1,
+        description: S2,
+        scopes: &[String],
+    ) -> DbResult<Role> {
+        let role: Role = sqlx::query_as(
+            "INSERT INTO roles (name, description, created_at) VALUES ($1, $2, $3) \
+            RETURNING id, name, description, created_at",
+        )
+        .bind(name.as_ref())
+        .bind(description.as_ref())
+        .bind(OffsetDateTime::now_utc().unix_timestamp())
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            "INSERT INTO role_scopes (role_id, scope) SELECT $1, UNNEST($2::VARCHAR[]) \
+            ON CONFLICT DO NOTHING",
+        )
+        .bind(role.id)
+        .bind(scopes)
+        .execute(&mut *tx)
+        .await?;
+
+        Ok(role)
+    }
+
+    pub async fn get_by_id(db: &Database, id: i32) -> DbResult<Option<Role>> {
+        sqlx::query_as("SELECT id, name, description, created_at FROM roles WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&**db)
+            .await
+    }
+
+    pub async fn get_by_name<S: AsRef<str>>(db: &Database, name: S) -> DbResult<Option<Role>> {
+        sqlx::query_as("SELECT id, name, description, created_at FROM roles WHERE name = $1")
+            .bind(name.as_ref())
+            .fetch_optional(&**db)
+            .await
+    }
+
+    pub async fn list(db: &Database) -> DbResult<Vec<Role>> {
+        sqlx::query_as("SELECT id, name, description, created_at FROM roles ORDER BY name")
+            .fetch_all(&**db)
+            .await
+    }
+
+    /// List the roles assigned to a user.
+    pub async fn list_for_user(db: &Database, koala_id: i32) -> DbResult<Vec<Role>> {
+        sqlx::query_as(
+            "SELECT roles.id, roles.name, roles.description, roles.created_at \
+            FROM user_roles \
+            INNER JOIN roles ON roles.id = user_roles.role_id \
+            WHERE user_roles.koala_id = $1 \
+            ORDER BY roles.name",
+        )
+        .bind(koala_id)
+        .fetch_all(&**db)
+        .await
+    }
+
+    pub async fn list_scopes(&self, db: &Database) -> DbResult<Vec<String>> {
+        sqlx::query_scalar("SELECT scope FROM role_scopes WHERE role_id = $1 ORDER BY scope")
+            .bind(self.id)
+            .fetch_all(&**db)
+            .await
+    }
+
+    /// Replace the scopes of the role.
+    /// The change applies to all users the role is assigned to.
+    pub async fn set_scopes(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        scopes: &[String],
+    ) -> DbResult<()> {
+        sqlx::query("DELETE FROM role_scopes WHERE role_id = $1")
+            .bind(self.id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query(
+            "INSERT INTO role_scopes (role_id, scope) SELECT $1, UNNEST($2::VARCHAR[]) \
+            ON CONFLICT DO NOTHING",
+        )
+        .bind(self.id)
+        .bind(scopes)
+        .execute(&mut *tx)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn update_name<S: AsRef<str>>(
+        &mut self,
+        tx: &mut Transaction<'_, Postgres>,
+        name: S,
+    ) -> DbResult<()> {
+        sqlx::query("UPDATE roles SET name = $1 WHERE id = $2")
+            .bind(name.as_ref())
+            .bind(self.id)
+            .execute(&mut *tx)
+            .await?;
+
+        self.name = name.as_ref().to_string();
+        Ok(())
+    }
+
+    pub async fn update_description<S: AsRef<str>>(
+        &mut self,
+        tx: &mut Transaction<'_, Postgres>,
+        description: S,
+    ) -> DbResult<()> {
+        sqlx::query("UPDATE roles SET description = $1 WHERE id = $2")
+            .bind(description.as_ref())
+            .bind(self.id)
+            .execute(&mut *tx)
+            .await?;
+
+        self.description = description.as_ref().to_string();
+        Ok(())
+    }
+
+    /// Assign the role to a user.
+    /// Assigning a role the user already has has no effect.
+    pub async fn assign(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        koala_id: i32,
+        granted_by: i32,
+    ) -> DbResult<()> {
+        sqlx::query(
+            "INSERT INTO user_roles (koala_id, role_id, granted_by, granted_at) \
+            VALUES ($1, $2, $3, $4) \
+            ON CONFLICT DO NOTHING",
+        )
+        .bind(koala_id)
+        .bind(self.id)
+        .bind(granted_by)
+        .bind(OffsetDateTime::now_utc().unix_timestamp())
+        .execute(&mut *tx)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn unassign(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        koala_id: i32,
+    ) -> DbResult<()> {
+        sqlx::query("DELETE FROM user_roles WHERE koala_id = $1 AND role_id = $2")
+            .bind(koala_id)
+            .bind(self.id)
+            .execute(&mut *tx)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Delete the role, removing it from all users it was assigned to.
+    pub async fn delete(self, tx: &mut Transaction<'_, Postgres>) -> DbResult<()> {
+        sqlx::query("DELETE FROM user_roles WHERE role_id = $1")
+            .bind(self.id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM synced_roles WHERE role_id = $1")
+            .bind(self.id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM role_scopes WHERE role_id = $1")
+            .bind(self.id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM roles WHERE id = $1")
+            .bind(self.id)
+            .execute(&mut *tx)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn to_proto(self, db: &Database) -> DbResult<proto::Role> {
+        Ok(proto::Role {
+            scopes: self.list_scopes(db).await?,
+            id: self.id,
+            name: self.name,
+            description: self.description,
+            created_at: self.created_at,
+        })
+    }
+}
@@ -0,0 +1,67 @@
+use sqlx::{FromRow, Postgres, Transaction};
+use time::OffsetDateTime;
+
+use crate::database::{Database, DbResult};
+
+/// A login session of a Koala user.
+/// The ID of a session is the secret the user authorizes with, it should never be shown to others.
+#[derive(Debug, FromRow)]
+pub struct Session {
+    pub id: String,
+    pub koala_id: i32,
+    pub created_at: i64,
+    pub last_seen_at: Option<i64>,
+    pub expires_at: i64,
+    pub user_agent: Option<String>,
+}
+
+impl Session {
+    /// List the sessions of a user which have not expired yet, most recently used first.
+    pub async fn list_for_user(db: &Database, koala_id: i32) -> DbResult<Vec<Session>> {
+        sqlx::query_as(
+            "SELECT * FROM user_sessions WHERE koala_id = $1 AND expires_at > $2 \
+            ORDER BY COALESCE(last_seen_at, created_at) DESC",
+        )
+        .bind(koala_id)
+        .bind(OffsetDateTime::now_utc().unix_timestamp())
+        .fetch_all(&**db)
+        .await
+    }
+
+    /// Record that the session was used just now.
+    pub async fn touch<S: AsRef<str>>(db: &Database, id: S) -> DbResult<()> {
+        sqlx::query("UPDATE user_sessions SET last_seen_at = $1 WHERE id = $2")
+            .bind(OffsetDateTime::now_utc().unix_timestamp())
+            .bind(id.as_ref())
+            .execute(&**db)
+            .await?;
+        Ok(())
+    }
+
+    /// Delete a single session.
+    pub async fn delete<S: AsRef<str>>(db: &Database, id: S) -> DbResult<()> {
+        sqlx::query("DELETE FROM user_sessions WHERE id = $1")
+            .bind(id.as_ref())
+            .execute(&**db)
+            .await?;
+        Ok(())
+    }
+
+    /// Delete all sessions of a user.
+    /// Returns the IDs of the deleted sessions.
+    pub async fn delete_all_for_user(db: &Database, koala_id: i32) -> DbResult<Vec<String>> {
+        sqlx::query_scalar("DELETE FROM user_sessions WHERE koala_id = $1 RETURNING id")
+            .bind(koala_id)
+            .fetch_all(&**db)
+            .await
+    }
+
+    /// Delete all sessions which have expired.
+    /// Returns the Koala ID of the user of every deleted session.
+    pub async fn delete_expired(tx: &mut Transaction<'_, Postgres>) -> DbResult<Vec<i32>> {
+        sqlx::query_scalar("DELETE FROM user_sessions WHERE expires_at <= $1 RETURNING koala_id")
+            .bind(OffsetDateTime::now_utc().unix_timestamp())
+            .fetch_all(&mut *tx)
+            .await
+    }
+}
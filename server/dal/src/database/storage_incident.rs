@@ -0,0 +1,53 @@
+use sqlx::FromRow;
+use time::OffsetDateTime;
+
+use crate::database::{Database, DbResult, PhotoQuality};
+
+/// A cover photo which could not be retrieved from storage.
+/// The cover photo is unset when this happens, the incident is kept
+/// so the consistency checker can report it.
+#[derive(Debug, FromRow)]
+pub struct StorageIncident {
+    pub id: i64,
+    pub album_id: String,
+    pub photo_id: String,
+    pub quality: PhotoQuality,
+    pub object_key: String,
+    pub created_at: i64,
+}
+
+impl StorageIncident {
+    pub async fn record<S1: AsRef<str>, S2: AsRef<str>>(
+        db: &Database,
+        album_id: S1,
+        photo_id: S2,
+        quality: &PhotoQuality,
+        object_key: &str,
+    ) -> DbResult<()> {
+        sqlx::query(
+            "INSERT INTO storage_incidents (album_id, photo_id, quality, object_key, created_at) VALUES ($1, $2, $3, $4, $5)",
+        )
+        .bind(album_id.as_ref())
+        .bind(photo_id.as_ref())
+        .bind(quality)
+        .bind(object_key)
+        .bind(OffsetDateTime::now_utc().unix_timestamp())
+        .execute(&**db)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn list(db: &Database) -> DbResult<Vec<StorageIncident>> {
+        sqlx::query_as("SELECT * FROM storage_incidents ORDER BY created_at ASC")
+            .fetch_all(&**db)
+            .await
+    }
+
+    pub async fn resolve(self, db: &Database) -> DbResult<()> {
+        sqlx::query("DELETE FROM storage_incidents WHERE id = $1")
+            .bind(self.id)
+            .execute(&**db)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,118 @@
+use sqlx::{Postgres, Transaction};
+use time::OffsetDateTime;
+
+use crate::database::{Database, DbResult, Role};
+
+/// The scopes and roles a user holds because of their Koala groups.
+/// These are replaced on every sync and kept apart from the grants made by admins,
+/// so a sync never removes a grant made by hand, nor the other way around.
+#[derive(Debug, Default, Clone)]
+pub struct SyncedGrants {
+    pub scopes: Vec<String>,
+    pub role_ids: Vec<i32>,
+}
+
+impl SyncedGrants {
+    pub async fn get_for_user(db: &Database, koala_id: i32) -> DbResult<SyncedGrants> {
+        Ok(SyncedGrants {
+            scopes: sqlx::query_scalar(
+                "SELECT scope FROM synced_scopes WHERE koala_id = $1 ORDER BY scope",
+            )
+            .bind(koala_id)
+            .fetch_all(&**db)
+            .await?,
+            role_ids: sqlx::query_scalar(
+                "SELECT role_id FROM synced_roles WHERE koala_id = $1 ORDER BY role_id",
+            )
+            .bind(koala_id)
+            .fetch_all(&**db)
+            .await?,
+        })
+    }
+
+    /// Sort and deduplicate the grants, so they can be compared.
+    pub fn sort(&mut self) {
+        self.scopes.sort();
+        self.scopes.dedup();
+        self.role_ids.sort();
+        self.role_ids.dedup();
+    }
+
+    /// Describe the grants for the audit log, e.g. `photo:create,album:* roles:1,2`.
+    pub fn describe(&self) -> String {
+        let role_ids = self
+            .role_ids
+            .iter()
+            .map(i32::to_string)
+            .collect::<Vec<_>>()
+            .join(",");
+        format!("{} roles:{role_ids}", self.scopes.join(","))
+    }
+
+    /// List the roles the user holds because of their Koala groups.
+    pub async fn list_roles(db: &Database, koala_id: i32) -> DbResult<Vec<Role>> {
+        sqlx::query_as(
+            "SELECT roles.id, roles.name, roles.description, roles.created_at \
+            FROM synced_roles \
+            INNER JOIN roles ON roles.id = synced_roles.role_id \
+            WHERE synced_roles.koala_id = $1 \
+            ORDER BY roles.name",
+        )
+        .bind(koala_id)
+        .fetch_all(&**db)
+        .await
+    }
+
+    /// Replace the synced grants of the user, and record that the user was synced just now.
+    pub async fn set_for_user(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        koala_id: i32,
+    ) -> DbResult<()> {
+        sqlx::query("DELETE FROM synced_scopes WHERE koala_id = $1")
+            .bind(koala_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query(
+            "INSERT INTO synced_scopes (koala_id, scope) SELECT $1, UNNEST($2::VARCHAR[]) \
+            ON CONFLICT DO NOTHING",
+        )
+        .bind(koala_id)
+        .bind(&self.scopes)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query("DELETE FROM synced_roles WHERE koala_id = $1")
+            .bind(koala_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query(
+            "INSERT INTO synced_roles (koala_id, role_id) SELECT $1, UNNEST($2::INT[]) \
+            ON CONFLICT DO NOTHING",
+        )
+        .bind(koala_id)
+        .bind(&self.role_ids)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query("UPDATE users SET groups_synced_at = $1 WHERE koala_id = $2")
+            .bind(OffsetDateTime::now_utc().unix_timestamp())
+            .bind(koala_id)
+            .execute(&mut *tx)
+            .await?;
+
+        Ok(())
+    }
+
+    /// When the grants of the user were last synced.
+    /// `None` if they have never been synced.
+    pub async fn last_synced_at(db: &Database, koala_id: i32) -> DbResult<Option<i64>> {
+        sqlx::query_scalar("SELECT groups_synced_at FROM users WHERE koala_id = $1")
+            .bind(koala_id)
+            .fetch_optional(&**db)
+            .await
+            .map(Option::flatten)
+    }
+}
@@ -0,0 +1,152 @@
+use sqlx::{FromRow, Postgres, Transaction};
+use time::OffsetDateTime;
+
+use crate::database::{Database, DbResult};
+
+/// A stored object which should be deleted.
+/// Tombstones are written in the same transaction in which the metadata of a photo is removed,
+/// the objects themselves are deleted afterwards by a background worker.
+/// Deleting an object is idempotent, so a tombstone may safely be processed more than once.
+#[derive(Debug, FromRow)]
+pub struct StorageTombstone {
+    pub object_key: String,
+    pub created_at: i64,
+    pub attempts: i32,
+    pub last_attempt_at: Option<i64>,
+    pub last_error: Option<String>,
+}
+
+impl StorageTombstone {
+    /// The maximum time in seconds between two attempts to delete an object
+    const MAX_RETRY_DELAY: i64 = 24 * 60 * 60;
+
+    /// Record tombstones for every stored variant of the provided photos.
+    /// Variants without a recorded key are assumed to be stored in the legacy layout,
+    /// see [crate::storage_engine::KeyLayout::LEGACY].
+    /// Also removes the stored URLs and keys of the photos, as the objects will no longer be available.
+    pub(crate) async fn insert_for_photos(
+        tx: &mut Transaction<'_, Postgres>,
+        photo_ids: &[String],
+    ) -> DbResult<()> {
+        let created_at = OffsetDateTime::now_utc().unix_timestamp();
+
+        // Lock the photos, so no new keys are recorded for them in the meantime
+        sqlx::query("SELECT id FROM photo_metadata WHERE id = ANY($1) FOR UPDATE")
+            .bind(photo_ids)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query(
+            "INSERT INTO storage_tombstones (object_key, created_at) \
+            SELECT COALESCE(o.object_key, p.id || '_' || q.quality::TEXT), $2 \
+            FROM UNNEST($1::VARCHAR[]) AS p(id) \
+            CROSS JOIN UNNEST(enum_range(NULL::photo_quality)) AS q(quality) \
+            LEFT JOIN photo_objects o ON o.photo_id = p.id AND o.quality = q.quality \
+            ON CONFLICT DO NOTHING",
+        )
+        .bind(photo_ids)
+        .bind(created_at)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query("DELETE FROM photo_s3_urls WHERE photo_id = ANY($1)")
+            .bind(photo_ids)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM photo_objects WHERE photo_id = ANY($1)")
+            .bind(photo_ids)
+            .execute(&mut *tx)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Record a tombstone for a single stored object as part of a transaction.
+    pub(crate) async fn insert_in(
+        tx: &mut Transaction<'_, Postgres>,
+        object_key: &str,
+    ) -> DbResult<()> {
+        sqlx::query(
+            "INSERT INTO storage_tombstones (object_key, created_at) VALUES ($1, $2) \
+            ON CONFLICT DO NOTHING",
+        )
+        .bind(object_key)
+        .bind(OffsetDateTime::now_utc().unix_timestamp())
+        .execute(&mut *tx)
+        .await?;
+        Ok(())
+    }
+
+    /// Record a tombstone for a single stored object.
+    /// Recording a tombstone which already exists has no effect.
+    pub async fn insert<S: AsRef<str>>(db: &Database, object_key: S) -> DbResult<()> {
+        sqlx::query(
+            "INSERT INTO storage_tombstones (object_key, created_at) VALUES ($1, $2) \
+            ON CONFLICT DO NOTHING",
+        )
+        .bind(object_key.as_ref())
+        .bind(OffsetDateTime::now_utc().unix_timestamp())
+        .execute(&**db)
+        .await?;
+        Ok(())
+    }
+
+    /// List all tombstones, including those which are not yet due to be retried.
+    pub async fn list(db: &Database) -> DbResult<Vec<StorageTombstone>> {
+        sqlx::query_as("SELECT * FROM storage_tombstones ORDER BY created_at ASC")
+            .fetch_all(&**db)
+            .await
+    }
+
+    /// List tombstones which are due to be processed.
+    /// Tombstones which failed before are retried with an exponential backoff,
+    /// starting at one minute and capped at [Self::MAX_RETRY_DELAY].
+    pub async fn list_pending(db: &Database, limit: i64) -> DbResult<Vec<StorageTombstone>> {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+
+        sqlx::query_as(
+            "SELECT * FROM storage_tombstones \
+            WHERE last_attempt_at IS NULL \
+                OR last_attempt_at < $1 - LEAST(60 * POWER(2, LEAST(attempts, 20)), $2) \
+            ORDER BY last_attempt_at ASC NULLS FIRST, created_at ASC \
+            LIMIT $3",
+        )
+        .bind(now)
+        .bind(Self::MAX_RETRY_DELAY)
+        .bind(limit)
+        .fetch_all(&**db)
+        .await
+    }
+
+    /// Remove the tombstone after the object has been deleted.
+    pub async fn resolve(self, db: &Database) -> DbResult<()> {
+        sqlx::query("DELETE FROM storage_tombstones WHERE object_key = $1")
+            .bind(&self.object_key)
+            .execute(&**db)
+            .await?;
+        Ok(())
+    }
+
+    /// Record a failed attempt to delete the object.
+    pub async fn record_failure(&mut self, db: &Database, error: &str) -> DbResult<()> {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+
+        sqlx::query(
+            "UPDATE storage_tombstones \
+            SET attempts = attempts + 1, last_attempt_at = $1, last_error = $2 \
+            WHERE object_key = $3",
+        )
+        .bind(now)
+        .bind(error)
+        .bind(&self.object_key)
+        .execute(&**db)
+        .await?;
+
+        self.attempts += 1;
+        self.last_attempt_at = Some(now);
+        self.last_error = Some(error.to_string());
+
+        Ok(())
+    }
+}
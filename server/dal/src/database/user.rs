@@ -1,5 +1,5 @@
 use rand::Rng;
-use sqlx::FromRow;
+use sqlx::{FromRow, Postgres, Transaction};
 use time::{Duration, OffsetDateTime};
 
 use crate::database::{Database, DbResult};
@@ -14,6 +14,7 @@ pub struct User<'a> {
     pub name: String,
 }
 
+#[derive(Clone)]
 pub struct OAuthAccess {
     pub access_token: String,
     pub refresh_token: String,
@@ -31,12 +32,13 @@ struct _User {
 }
 
 pub struct ChromaScope<'a> {
-    #[allow(unused)]
     db: &'a Database,
     pub koala_id: i32,
     pub scope: String,
     pub granted_by: i32,
     pub granted_at: i64,
+    /// `None` if the scope does not expire
+    pub expires_at: Option<i64>,
 }
 
 #[derive(FromRow)]
@@ -45,6 +47,7 @@ struct _ChromaScope {
     scope: String,
     granted_by: i32,
     granted_at: i64,
+    expires_at: Option<i64>,
 }
 
 impl _User {
@@ -69,6 +72,7 @@ impl _ChromaScope {
             scope: self.scope,
             granted_by: self.granted_by,
             granted_at: self.granted_at,
+            expires_at: self.expires_at,
         }
     }
 }
@@ -77,18 +81,23 @@ impl<'a> User<'a> {
     pub const SESSION_ID_LEN: usize = 32;
     pub const SESSION_DEFAULT_EXPIRY: Duration = Duration::days(15);
 
+    /// Create a user as part of the transaction.
+    /// Returns `None` if a user with the ID exists already,
+    /// e.g. because it was created by a concurrent login.
     pub async fn create<S: AsRef<str>>(
         db: &'a Database,
+        tx: &mut Transaction<'_, Postgres>,
         koala_id: i32,
         oauth: OAuthAccess,
         admin: bool,
         name: S,
-    ) -> DbResult<User<'a>> {
-        sqlx::query(
+    ) -> DbResult<Option<User<'a>>> {
+        let result = sqlx::query(
             "INSERT INTO users \
                     (koala_id, access_token, refresh_token, expires_at, is_admin, name) \
                 VALUES \
-                    ($1, $2, $3, $4, $5, $6)",
+                    ($1, $2, $3, $4, $5, $6) \
+                ON CONFLICT (koala_id) DO NOTHING",
         )
         .bind(koala_id)
         .bind(&oauth.access_token)
@@ -96,10 +105,14 @@ impl<'a> User<'a> {
         .bind(oauth.expires_at)
         .bind(admin)
         .bind(name.as_ref())
-        .execute(&**db)
+        .execute(&mut *tx)
         .await?;
 
-        Ok(Self {
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        Ok(Some(Self {
             db,
             koala_id,
             access_token: oauth.access_token,
@@ -107,7 +120,7 @@ impl<'a> User<'a> {
             oauth_expires_at: oauth.expires_at,
             is_admin: admin,
             name: name.as_ref().to_string(),
-        })
+        }))
     }
 
     pub async fn set_is_admin(&mut self, is_admin: bool) -> DbResult<()> {
@@ -133,20 +146,28 @@ impl<'a> User<'a> {
         Ok(user.map(|user| user.into_user(db)))
     }
 
-    pub async fn create_session(&self) -> DbResult<String> {
+    /// Create a new session for the user.
+    /// Returns the ID of the session.
+    pub async fn create_session(&self, user_agent: Option<&str>) -> DbResult<String> {
         let session_id: String = rand::thread_rng()
             .sample_iter(rand::distributions::Alphanumeric)
             .take(Self::SESSION_ID_LEN)
             .map(char::from)
             .collect();
-        let expires_at = OffsetDateTime::now_utc() + Self::SESSION_DEFAULT_EXPIRY;
+        let created_at = OffsetDateTime::now_utc();
+        let expires_at = created_at + Self::SESSION_DEFAULT_EXPIRY;
 
-        sqlx::query("INSERT INTO user_sessions (id, koala_id, expires_at) VALUES ($1, $2, $3)")
-            .bind(&session_id)
-            .bind(self.koala_id)
-            .bind(expires_at.unix_timestamp())
-            .execute(&**self.db)
-            .await?;
+        sqlx::query(
+            "INSERT INTO user_sessions (id, koala_id, created_at, expires_at, user_agent) \
+            VALUES ($1, $2, $3, $4, $5)",
+        )
+        .bind(&session_id)
+        .bind(self.koala_id)
+        .bind(created_at.unix_timestamp())
+        .bind(expires_at.unix_timestamp())
+        .bind(user_agent)
+        .execute(&**self.db)
+        .await?;
 
         Ok(session_id)
     }
@@ -198,8 +219,28 @@ impl<'a> User<'a> {
         User::get_by_id(db, session.koala_id).await
     }
 
+    /// Lock the tokens of the user until the transaction ends, and load their current values.
+    /// A refresh token can only be used once, so concurrent refreshes of the tokens of a user
+    /// have to wait for each other. Use [Self::set_tokens] in the same transaction to store
+    /// the refreshed tokens.
+    pub async fn lock_tokens(&mut self, tx: &mut Transaction<'_, Postgres>) -> DbResult<()> {
+        let (access, refresh, expires_at): (String, String, i64) = sqlx::query_as(
+            "SELECT access_token, refresh_token, expires_at FROM users WHERE koala_id = $1 FOR UPDATE",
+        )
+        .bind(self.koala_id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        self.access_token = access;
+        self.refresh_token = refresh;
+        self.oauth_expires_at = expires_at;
+
+        Ok(())
+    }
+
     pub async fn set_tokens(
         &mut self,
+        tx: &mut Transaction<'_, Postgres>,
         access: String,
         refresh: String,
         expires_at: i64,
@@ -209,11 +250,12 @@ impl<'a> User<'a> {
             .bind(&refresh)
             .bind(expires_at)
             .bind(self.koala_id)
-            .execute(&**self.db)
+            .execute(&mut *tx)
             .await?;
 
         self.access_token = access;
         self.refresh_token = refresh;
+        self.oauth_expires_at = expires_at;
 
         Ok(())
     }
@@ -222,29 +264,43 @@ impl<'a> User<'a> {
         ChromaScope::list_for_user(self.db, self.koala_id).await
     }
 
+    /// Grant a scope to the user, optionally until the provided UNIX timestamp.
     pub async fn add_scope<S: AsRef<str>>(
         &self,
+        tx: &mut Transaction<'_, Postgres>,
         scope: S,
         by: &User<'a>,
+        expires_at: Option<i64>,
     ) -> DbResult<ChromaScope<'a>> {
-        ChromaScope::add_scope(self.db, self.koala_id, scope, by.koala_id).await
+        ChromaScope::add_scope(self.db, tx, self.koala_id, scope, by.koala_id, expires_at).await
     }
 
-    pub async fn remove_scope(&self, scope: &ChromaScope<'_>) -> DbResult<()> {
-        ChromaScope::remove_scope(self.db, self.koala_id, &scope.scope).await
+    pub async fn remove_scope(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        scope: &ChromaScope<'_>,
+    ) -> DbResult<()> {
+        ChromaScope::remove_scope(tx, self.koala_id, &scope.scope).await
     }
 
-    pub async fn remove_scope_by_name<S: AsRef<str>>(&self, scope_name: S) -> DbResult<()> {
-        ChromaScope::remove_scope(self.db, self.koala_id, scope_name.as_ref()).await
+    pub async fn remove_scope_by_name<S: AsRef<str>>(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        scope_name: S,
+    ) -> DbResult<()> {
+        ChromaScope::remove_scope(tx, self.koala_id, scope_name.as_ref()).await
     }
 }
 
 impl<'a> ChromaScope<'a> {
+    /// List the scopes granted to the user directly, excluding those which have expired.
     pub async fn list_for_user(db: &'a Database, koala_id: i32) -> DbResult<Vec<ChromaScope<'a>>> {
         let chroma_scopes: Vec<_ChromaScope> = sqlx::query_as(
-            "SELECT koala_id, scope, granted_by, granted_at FROM chroma_scopes WHERE koala_id = $1",
+            "SELECT koala_id, scope, granted_by, granted_at, expires_at FROM chroma_scopes \
+            WHERE koala_id = $1 AND (expires_at IS NULL OR expires_at > $2)",
         )
         .bind(koala_id)
+        .bind(OffsetDateTime::now_utc().unix_timestamp())
         .fetch_all(&**db)
         .await?;
 
@@ -254,34 +310,98 @@ impl<'a> ChromaScope<'a> {
             .collect::<Vec<_>>())
     }
 
-    async fn remove_scope(db: &Database, koala_id: i32, name: &str) -> DbResult<()> {
+    /// List the names of all scopes the user holds,
+    /// both those granted directly and those granted through their roles,
+    /// whether granted by an admin or synced from their Koala groups.
+    /// Expired scopes are excluded.
+    pub async fn list_effective_for_user(db: &Database, koala_id: i32) -> DbResult<Vec<String>> {
+        sqlx::query_scalar(
+            "SELECT scope FROM chroma_scopes \
+            WHERE koala_id = $1 AND (expires_at IS NULL OR expires_at > $2) \
+            UNION \
+            SELECT role_scopes.scope FROM user_roles \
+            INNER JOIN role_scopes ON role_scopes.role_id = user_roles.role_id \
+            WHERE user_roles.koala_id = $1 \
+            UNION \
+            SELECT scope FROM synced_scopes WHERE koala_id = $1 \
+            UNION \
+            SELECT role_scopes.scope FROM synced_roles \
+            INNER JOIN role_scopes ON role_scopes.role_id = synced_roles.role_id \
+            WHERE synced_roles.koala_id = $1 \
+            ORDER BY scope",
+        )
+        .bind(koala_id)
+        .bind(OffsetDateTime::now_utc().unix_timestamp())
+        .fetch_all(&**db)
+        .await
+    }
+
+    /// Change when the scope expires. `None` makes the scope permanent.
+    pub async fn set_expires_at(
+        &mut self,
+        tx: &mut Transaction<'_, Postgres>,
+        expires_at: Option<i64>,
+    ) -> DbResult<()> {
+        sqlx::query("UPDATE chroma_scopes SET expires_at = $1 WHERE koala_id = $2 AND scope = $3")
+            .bind(expires_at)
+            .bind(self.koala_id)
+            .bind(&self.scope)
+            .execute(&mut *tx)
+            .await?;
+
+        self.expires_at = expires_at;
+        Ok(())
+    }
+
+    /// Delete all scopes which have expired.
+    /// Returns the Koala ID of the user and the name of every deleted scope.
+    pub async fn delete_expired(
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> DbResult<Vec<(i32, String)>> {
+        sqlx::query_as("DELETE FROM chroma_scopes WHERE expires_at <= $1 RETURNING koala_id, scope")
+            .bind(OffsetDateTime::now_utc().unix_timestamp())
+            .fetch_all(&mut *tx)
+            .await
+    }
+
+    async fn remove_scope(
+        tx: &mut Transaction<'_, Postgres>,
+        koala_id: i32,
+        name: &str,
+    ) -> DbResult<()> {
         sqlx::query("DELETE FROM chroma_scopes WHERE koala_id = $1 AND scope = $2")
             .bind(koala_id)
             .bind(name)
-            .execute(&**db)
+            .execute(&mut *tx)
             .await?;
         Ok(())
     }
 
     async fn add_scope<S: AsRef<str>>(
         db: &'a Database,
+        tx: &mut Transaction<'_, Postgres>,
         to: i32,
         name: S,
         by: i32,
-    ) -> DbResult<ChromaScope> {
+        expires_at: Option<i64>,
+    ) -> DbResult<ChromaScope<'a>> {
         let ts = OffsetDateTime::now_utc().unix_timestamp();
 
+        // An expired grant which has not been cleaned up yet is replaced
         sqlx::query(
             "INSERT INTO chroma_scopes \
-                (koala_id, scope, granted_by, granted_at) \
+                (koala_id, scope, granted_by, granted_at, expires_at) \
             VALUES \
-                ($1, $2, $3, $4)",
+                ($1, $2, $3, $4, $5) \
+            ON CONFLICT (koala_id, scope) DO UPDATE SET \
+                granted_by = $3, granted_at = $4, expires_at = $5",
         )
         .bind(to)
         .bind(name.as_ref())
         .bind(by)
         .bind(ts)
-        .execute(&**db)
+        .bind(expires_at)
+        .execute(&mut *tx)
         .await?;
 
         Ok(ChromaScope {
@@ -290,6 +410,7 @@ impl<'a> ChromaScope<'a> {
             scope: name.as_ref().to_string(),
             granted_by: by,
             granted_at: ts,
+            expires_at,
         })
     }
 }
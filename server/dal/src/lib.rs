@@ -1,6 +1,9 @@
 use thiserror::Error;
 
+pub mod archive;
+pub mod consistency;
 pub mod database;
+pub mod relayout;
 pub mod storage_engine;
 
 #[derive(Debug, Error)]
@@ -0,0 +1,67 @@
+use crate::database::{Database, Photo, PhotoObject, PhotoQuality};
+use crate::storage_engine::Storage;
+use crate::DalError;
+
+/// The progress of moving stored objects to the configured key layout.
+#[derive(Debug, Default)]
+pub struct RelayoutProgress {
+    /// The number of objects moved in this batch
+    pub moved: usize,
+    /// The cursor from which the next batch continues,
+    /// `None` if all photos have been processed
+    pub next_cursor: Option<String>,
+}
+
+/// Move the stored objects of at most `limit` photos to the keys prescribed by the configured key layout.
+/// Photos are processed in order of their ID, starting after `cursor`.
+/// Objects are copied to their new key, after which their recorded key and URL are updated.
+/// The objects under the old keys are deleted afterwards, see [crate::database::StorageTombstone].
+///
+/// Photos which were moved to another album since they were stored
+/// are moved to the key of their current album.
+///
+/// # Errors
+///
+/// If a database or storage error occurs
+pub async fn relayout(
+    db: &Database,
+    storage: &Storage,
+    cursor: Option<&str>,
+    limit: usize,
+) -> Result<RelayoutProgress, DalError> {
+    let photos = Photo::list_page_including_trashed(db, cursor, limit as i64).await?;
+
+    let mut progress = RelayoutProgress {
+        moved: 0,
+        next_cursor: photos
+            .last()
+            .filter(|_| photos.len() >= limit)
+            .map(|photo| photo.id.clone()),
+    };
+
+    for photo in &photos {
+        for quality in [
+            PhotoQuality::Original,
+            PhotoQuality::W400,
+            PhotoQuality::W1600,
+        ] {
+            let key = photo.object_key(&quality).await?;
+            if storage
+                .key_layout()
+                .matches_photo(&key, &photo.album_id, &photo.id, &quality)
+            {
+                continue;
+            }
+
+            // Qualities which were never created have nothing to move
+            if !storage.photo_exists(&key).await? {
+                continue;
+            }
+
+            PhotoObject::relocate(db, storage, &photo.album_id, &photo.id, &quality).await?;
+            progress.moved += 1;
+        }
+    }
+
+    Ok(progress)
+}
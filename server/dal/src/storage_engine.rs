@@ -7,17 +7,20 @@ use aws_smithy_http::result::SdkError;
 use aws_types::region::Region;
 use tracing::{info, instrument};
 
-use crate::database::PhotoQuality;
 use crate::storage_engine::error::StorageError;
 
+pub use key_layout::{KeyLayout, KeyLayoutError};
+
+mod key_layout;
+
 pub mod aws_error {
     pub use aws_sdk_s3::error::*;
 }
 
 pub mod error {
     use aws_sdk_s3::error::{
-        CreateBucketError, DeleteObjectError, GetObjectError, HeadBucketError,
-        PutBucketPolicyError, PutObjectError,
+        CopyObjectError, CreateBucketError, DeleteObjectError, GetObjectError, HeadBucketError,
+        HeadObjectError, ListObjectsV2Error, PutBucketPolicyError, PutObjectError,
     };
     pub use aws_sdk_s3::types::SdkError;
     use thiserror::Error;
@@ -32,10 +35,16 @@ pub mod error {
         PutBucketPolicy(#[from] SdkError<PutBucketPolicyError>),
         #[error("couldn't retrieve object ({0})")]
         GetObject(#[from] SdkError<GetObjectError>),
+        #[error("couldn't retrieve object information ({0})")]
+        HeadObject(#[from] SdkError<HeadObjectError>),
         #[error("couldn't upload object ({0})")]
         PutObject(#[from] SdkError<PutObjectError>),
         #[error("couldn't delete object ({0})")]
         DeleteObject(#[from] SdkError<DeleteObjectError>),
+        #[error("couldn't copy object ({0})")]
+        CopyObject(#[from] SdkError<CopyObjectError>),
+        #[error("couldn't list objects ({0})")]
+        ListObjects(#[from] SdkError<ListObjectsV2Error>),
         #[error("couldn't to convert ByteStream ({0})")]
         ByteStream(#[from] aws_smithy_http::byte_stream::error::Error),
         #[error("couldn't create presigning config ({0})")]
@@ -51,6 +60,15 @@ pub struct S3Config {
     pub secret_access_key: String,
     pub use_path_style: bool,
     pub create_bucket: bool,
+    pub key_layout: KeyLayout,
+}
+
+/// An object stored in the bucket
+#[derive(Debug, Clone)]
+pub struct StoredObject {
+    pub key: String,
+    /// UNIX timestamp of when the object was last modified
+    pub last_modified: Option<i64>,
 }
 
 #[derive(Debug, Clone)]
@@ -59,6 +77,7 @@ pub struct Storage {
     bucket_name: String,
     use_path_style: bool,
     endpoint_url: String,
+    key_layout: KeyLayout,
 }
 
 impl Deref for Storage {
@@ -95,36 +114,28 @@ impl Storage {
             bucket_name: config.bucket_name,
             endpoint_url: config.endpoint_url,
             use_path_style: config.use_path_style,
+            key_layout: config.key_layout,
         })
     }
 
-    pub async fn get_photo_url_by_id<S: AsRef<str>>(
-        &self,
-        photo_id: S,
-        photo_quality: &PhotoQuality,
-    ) -> Result<String, StorageError> {
-        let qstring = Self::format_id_with_quality(photo_id.as_ref(), photo_quality);
-        let url = if self.use_path_style {
-            format!("{}/{}/{}", self.endpoint_url, self.bucket_name, qstring)
-        } else {
-            format!("{}/{}", self.endpoint_url, qstring)
-        };
+    /// The layout of the keys new objects are stored under.
+    pub fn key_layout(&self) -> &KeyLayout {
+        &self.key_layout
+    }
 
-        Ok(url)
+    pub fn get_photo_url(&self, key: &str) -> String {
+        if self.use_path_style {
+            format!("{}/{}/{}", self.endpoint_url, self.bucket_name, key)
+        } else {
+            format!("{}/{}", self.endpoint_url, key)
+        }
     }
 
-    pub async fn get_photo_bytes_by_id<S: AsRef<str>>(
-        &self,
-        photo_id: S,
-        photo_quality: &PhotoQuality,
-    ) -> Result<Vec<u8>, StorageError> {
+    pub async fn get_photo_bytes(&self, key: &str) -> Result<Vec<u8>, StorageError> {
         let photo = self
             .get_object()
             .bucket(&self.bucket_name)
-            .key(Self::format_id_with_quality(
-                photo_id.as_ref(),
-                photo_quality,
-            ))
+            .key(key)
             .send()
             .await?;
 
@@ -134,20 +145,52 @@ impl Storage {
         Ok(bytes)
     }
 
-    pub async fn create_photo<S: AsRef<str>>(
-        &self,
-        photo_id: S,
-        photo_quality: &PhotoQuality,
-        bytes: Vec<u8>,
-    ) -> Result<(), StorageError> {
+    /// Retrieve the contents of an object as a stream,
+    /// so it does not have to be held in memory entirely.
+    pub async fn get_photo_stream(&self, key: &str) -> Result<ByteStream, StorageError> {
+        let photo = self
+            .get_object()
+            .bucket(&self.bucket_name)
+            .key(key)
+            .send()
+            .await?;
+
+        Ok(photo.body)
+    }
+
+    /// Retrieve the size of an object in bytes.
+    pub async fn get_photo_size(&self, key: &str) -> Result<u64, StorageError> {
+        let head = self
+            .head_object()
+            .bucket(&self.bucket_name)
+            .key(key)
+            .send()
+            .await?;
+
+        Ok(head.content_length().max(0) as u64)
+    }
+
+    /// Whether an object is stored under the key.
+    pub async fn photo_exists(&self, key: &str) -> Result<bool, StorageError> {
+        match self
+            .head_object()
+            .bucket(&self.bucket_name)
+            .key(key)
+            .send()
+            .await
+        {
+            Ok(_) => Ok(true),
+            Err(SdkError::ServiceError(e)) if e.err().is_not_found() => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub async fn create_photo(&self, key: &str, bytes: Vec<u8>) -> Result<(), StorageError> {
         let byte_stream = ByteStream::from(bytes);
 
         self.put_object()
             .bucket(&self.bucket_name)
-            .key(Self::format_id_with_quality(
-                photo_id.as_ref(),
-                photo_quality,
-            ))
+            .key(key)
             .body(byte_stream)
             .content_type("image/webp")
             .send()
@@ -156,23 +199,66 @@ impl Storage {
         Ok(())
     }
 
-    pub async fn delete_photo<S: AsRef<str>>(
-        &self,
-        photo_id: S,
-        photo_quality: &PhotoQuality,
-    ) -> Result<(), StorageError> {
+    pub async fn delete_photo(&self, key: &str) -> Result<(), StorageError> {
         self.delete_object()
             .bucket(&self.bucket_name)
-            .key(Self::format_id_with_quality(
-                photo_id.as_ref(),
-                photo_quality,
-            ))
+            .key(key)
             .send()
             .await?;
 
         Ok(())
     }
 
+    /// Copy an object to a new key.
+    /// The original object is left untouched.
+    pub async fn copy_photo(&self, from_key: &str, to_key: &str) -> Result<(), StorageError> {
+        self.copy_object()
+            .bucket(&self.bucket_name)
+            .copy_source(format!("{}/{}", self.bucket_name, from_key))
+            .key(to_key)
+            .send()
+            .await?;
+
+        Ok(())
+    }
+
+    /// List all objects in the bucket.
+    pub async fn list_objects(&self) -> Result<Vec<StoredObject>, StorageError> {
+        let mut objects = Vec::new();
+        let mut continuation_token = None;
+
+        loop {
+            let response = self
+                .list_objects_v2()
+                .bucket(&self.bucket_name)
+                .set_continuation_token(continuation_token)
+                .send()
+                .await?;
+
+            objects.extend(
+                response
+                    .contents()
+                    .unwrap_or_default()
+                    .iter()
+                    .filter_map(|object| {
+                        object.key().map(|key| StoredObject {
+                            key: key.to_string(),
+                            last_modified: object.last_modified().map(|time| time.secs()),
+                        })
+                    }),
+            );
+
+            match response.next_continuation_token() {
+                Some(token) if response.is_truncated() => {
+                    continuation_token = Some(token.to_string())
+                }
+                _ => break,
+            }
+        }
+
+        Ok(objects)
+    }
+
     async fn create_bucket(client: &Client, bucket_name: &String) -> Result<(), StorageError> {
         client.create_bucket().bucket(bucket_name).send().await?;
         Ok(())
@@ -220,8 +306,4 @@ impl Storage {
 
         Ok(())
     }
-
-    fn format_id_with_quality(photo_id: &str, photo_quality: &PhotoQuality) -> String {
-        format!("{}_{}", photo_id, photo_quality)
-    }
 }
@@ -0,0 +1,298 @@
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::database::PhotoQuality;
+
+#[derive(Debug, Error)]
+pub enum KeyLayoutError {
+    #[error("key layout must contain the '{0}' placeholder")]
+    MissingPlaceholder(&'static str),
+    #[error("key layout contains unknown placeholder '{{{0}}}'")]
+    UnknownPlaceholder(String),
+    #[error("key layout contains an unterminated placeholder")]
+    Unterminated,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    AlbumId,
+    PhotoId,
+    Quality,
+    Hash,
+}
+
+/// The layout of the keys under which photos are stored in the bucket.
+/// A layout is a template containing the placeholders:
+/// - `{album_id}`: The ID of the album the photo was in when it was stored
+/// - `{photo_id}`: The ID of the photo, required
+/// - `{quality}`: The quality of the stored variant, required
+/// - `{hash}`: The SHA-256 of the stored variant, making the key content-addressed
+///
+/// E.g. `albums/{album_id}/{photo_id}/{quality}.webp`.
+///
+/// The key of every stored variant is recorded when it is stored,
+/// so changing the layout does not affect existing objects until they are relayed out.
+#[derive(Debug, Clone)]
+pub struct KeyLayout {
+    template: String,
+    segments: Vec<Segment>,
+}
+
+impl KeyLayout {
+    /// The layout in which all objects were stored before layouts were configurable.
+    pub const LEGACY: &'static str = "{photo_id}_{quality}";
+
+    pub fn new(template: impl Into<String>) -> Result<Self, KeyLayoutError> {
+        let template = template.into();
+        let segments = Self::parse(&template)?;
+
+        if !segments.contains(&Segment::PhotoId) {
+            return Err(KeyLayoutError::MissingPlaceholder("{photo_id}"));
+        }
+
+        if !segments.contains(&Segment::Quality) {
+            return Err(KeyLayoutError::MissingPlaceholder("{quality}"));
+        }
+
+        Ok(Self { template, segments })
+    }
+
+    /// The layout in which all objects were stored before layouts were configurable.
+    pub fn legacy() -> Self {
+        Self::new(Self::LEGACY).expect("Legacy key layout is valid")
+    }
+
+    fn parse(template: &str) -> Result<Vec<Segment>, KeyLayoutError> {
+        let mut segments = Vec::new();
+        let mut rest = template;
+
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                segments.push(Segment::Literal(rest[..start].to_string()));
+            }
+
+            let end = rest[start..]
+                .find('}')
+                .ok_or(KeyLayoutError::Unterminated)?
+                + start;
+
+            let segment = match &rest[start + 1..end] {
+                "album_id" => Segment::AlbumId,
+                "photo_id" => Segment::PhotoId,
+                "quality" => Segment::Quality,
+                "hash" => Segment::Hash,
+                other => return Err(KeyLayoutError::UnknownPlaceholder(other.to_string())),
+            };
+
+            segments.push(segment);
+            rest = &rest[end + 1..];
+        }
+
+        if !rest.is_empty() {
+            segments.push(Segment::Literal(rest.to_string()));
+        }
+
+        Ok(segments)
+    }
+
+    pub fn template(&self) -> &str {
+        &self.template
+    }
+
+    /// Whether keys depend on the content of the stored variant.
+    /// If so, the content must be provided when rendering a key.
+    pub fn uses_hash(&self) -> bool {
+        self.segments.contains(&Segment::Hash)
+    }
+
+    /// Render the key under which a variant of a photo should be stored.
+    /// `content` is only used if the layout contains `{hash}`, see [Self::uses_hash].
+    pub fn render(
+        &self,
+        album_id: &str,
+        photo_id: &str,
+        quality: &PhotoQuality,
+        content: Option<&[u8]>,
+    ) -> String {
+        let hash = content
+            .filter(|_| self.uses_hash())
+            .map(|content| format!("{:x}", Sha256::digest(content)))
+            .unwrap_or_default();
+
+        self.segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Literal(literal) => literal.clone(),
+                Segment::AlbumId => album_id.to_string(),
+                Segment::PhotoId => photo_id.to_string(),
+                Segment::Quality => quality.to_string(),
+                Segment::Hash => hash.clone(),
+            })
+            .collect()
+    }
+
+    /// Whether `key` could have been rendered by this layout for any photo.
+    pub fn matches(&self, key: &str) -> bool {
+        matches_segments(&self.segments, key, None)
+    }
+
+    /// Whether `key` is the key this layout renders for the provided photo.
+    /// The content hash is not verified.
+    pub fn matches_photo(
+        &self,
+        key: &str,
+        album_id: &str,
+        photo_id: &str,
+        quality: &PhotoQuality,
+    ) -> bool {
+        matches_segments(
+            &self.segments,
+            key,
+            Some((album_id, photo_id, &quality.to_string())),
+        )
+    }
+}
+
+/// Match a key against the segments of a layout.
+/// If `photo` is provided, the album ID, photo ID and quality must match exactly.
+/// Other placeholders match at least one character, but never a `/`.
+fn matches_segments(segments: &[Segment], key: &str, photo: Option<(&str, &str, &str)>) -> bool {
+    let (segment, rest) = match segments.split_first() {
+        Some(split) => split,
+        None => return key.is_empty(),
+    };
+
+    let fixed = match (segment, photo) {
+        (Segment::Literal(literal), _) => Some(literal.as_str()),
+        (Segment::AlbumId, Some((album_id, _, _))) => Some(album_id),
+        (Segment::PhotoId, Some((_, photo_id, _))) => Some(photo_id),
+        (Segment::Quality, Some((_, _, quality))) => Some(quality),
+        _ => None,
+    };
+
+    match fixed {
+        Some(fixed) => key
+            .strip_prefix(fixed)
+            .map(|key| matches_segments(rest, key, photo))
+            .unwrap_or(false),
+        None => (1..=key.len())
+            .filter(|&idx| key.is_char_boundary(idx))
+            .take_while(|&idx| !key[..idx].contains('/'))
+            .any(|idx| matches_segments(rest, &key[idx..], photo)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{KeyLayout, KeyLayoutError, Segment};
+    use crate::database::PhotoQuality;
+
+    #[test]
+    fn parses_legacy_layout() {
+        let layout = KeyLayout::legacy();
+        assert_eq!(layout.template(), KeyLayout::LEGACY);
+        assert_eq!(
+            layout.segments,
+            vec![
+                Segment::PhotoId,
+                Segment::Literal("_".to_string()),
+                Segment::Quality,
+            ]
+        );
+        assert!(!layout.uses_hash());
+    }
+
+    #[test]
+    fn parses_all_placeholders() {
+        let layout = KeyLayout::new("albums/{album_id}/{photo_id}/{quality}-{hash}.webp").unwrap();
+        assert_eq!(
+            layout.segments,
+            vec![
+                Segment::Literal("albums/".to_string()),
+                Segment::AlbumId,
+                Segment::Literal("/".to_string()),
+                Segment::PhotoId,
+                Segment::Literal("/".to_string()),
+                Segment::Quality,
+                Segment::Literal("-".to_string()),
+                Segment::Hash,
+                Segment::Literal(".webp".to_string()),
+            ]
+        );
+        assert!(layout.uses_hash());
+    }
+
+    #[test]
+    fn rejects_malformed_templates() {
+        assert!(matches!(
+            KeyLayout::new("{quality}"),
+            Err(KeyLayoutError::MissingPlaceholder("{photo_id}"))
+        ));
+        assert!(matches!(
+            KeyLayout::new("{photo_id}"),
+            Err(KeyLayoutError::MissingPlaceholder("{quality}"))
+        ));
+        assert!(matches!(
+            KeyLayout::new("{photo_id}/{quality}/{size}"),
+            Err(KeyLayoutError::UnknownPlaceholder(placeholder)) if placeholder == "size"
+        ));
+        assert!(matches!(
+            KeyLayout::new("{photo_id}/{quality"),
+            Err(KeyLayoutError::Unterminated)
+        ));
+        assert!(matches!(
+            KeyLayout::new(""),
+            Err(KeyLayoutError::MissingPlaceholder(_))
+        ));
+    }
+
+    #[test]
+    fn renders_keys() {
+        assert_eq!(
+            KeyLayout::legacy().render("AL_a", "PH_a", &PhotoQuality::W400, None),
+            "PH_a_W400"
+        );
+
+        let layout = KeyLayout::new("albums/{album_id}/{photo_id}/{quality}.webp").unwrap();
+        assert_eq!(
+            layout.render("AL_a", "PH_a", &PhotoQuality::Original, Some(b"content")),
+            "albums/AL_a/PH_a/Original.webp"
+        );
+
+        let layout = KeyLayout::new("{photo_id}/{quality}/{hash}").unwrap();
+        assert_eq!(
+            layout.render("AL_a", "PH_a", &PhotoQuality::W1600, Some(b"abc")),
+            "PH_a/W1600/ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn matches_keys_of_any_photo() {
+        let layout = KeyLayout::new("albums/{album_id}/{photo_id}/{quality}.webp").unwrap();
+        assert!(layout.matches("albums/AL_a/PH_a/Original.webp"));
+        assert!(!layout.matches("albums/AL_a/PH_a/Original.png"));
+        // Placeholders match at least one character
+        assert!(!layout.matches("albums//PH_a/Original.webp"));
+        // Placeholders never match a '/'
+        assert!(!layout.matches("albums/AL_a/b/PH_a/Original.webp"));
+
+        let legacy = KeyLayout::legacy();
+        assert!(legacy.matches("PH_a_W400"));
+        assert!(!legacy.matches("albums/AL_a/PH_a/W400.webp"));
+    }
+
+    #[test]
+    fn matches_keys_of_a_photo() {
+        let layout = KeyLayout::new("albums/{album_id}/{photo_id}/{quality}-{hash}").unwrap();
+        let key = layout.render("AL_a", "PH_a", &PhotoQuality::W400, Some(b"content"));
+        assert!(layout.matches_photo(&key, "AL_a", "PH_a", &PhotoQuality::W400));
+        assert!(!layout.matches_photo(&key, "AL_b", "PH_a", &PhotoQuality::W400));
+        assert!(!layout.matches_photo(&key, "AL_a", "PH_b", &PhotoQuality::W400));
+        assert!(!layout.matches_photo(&key, "AL_a", "PH_a", &PhotoQuality::W1600));
+
+        let legacy = KeyLayout::legacy();
+        assert!(legacy.matches_photo("PH_a_W400", "AL_a", "PH_a", &PhotoQuality::W400));
+        assert!(!legacy.matches_photo(&key, "AL_a", "PH_a", &PhotoQuality::W400));
+    }
+}
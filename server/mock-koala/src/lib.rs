@@ -0,0 +1,194 @@
+//! A fake Koala, implementing the parts of Koala's OAuth2 API chroma uses.
+//! Used for running chroma locally without a Koala installation,
+//! and for testing the login and authorization flows.
+//!
+//! Users log in without a password, by picking one of the configured users.
+//! Tests can skip the browser entirely with [MockKoala::issue_code].
+
+use std::io;
+use std::net::ToSocketAddrs;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use actix_web::dev::ServerHandle;
+use actix_web::{web, App, HttpServer};
+use serde::Deserialize;
+
+use crate::state::State;
+
+mod routes;
+mod state;
+
+/// A member of the fake Koala.
+#[derive(Debug, Clone, Deserialize)]
+pub struct MockUser {
+    pub koala_id: i32,
+    pub first_name: String,
+    #[serde(default)]
+    pub infix: Option<String>,
+    pub last_name: String,
+    pub email: String,
+    #[serde(default)]
+    pub is_admin: bool,
+    /// The groups and committees the user is a member of
+    #[serde(default)]
+    pub groups: Vec<String>,
+    /// Leave the groups out of the userinfo response
+    #[serde(default)]
+    pub omit_groups: bool,
+    /// Koala refuses to give out the details of forbidden users,
+    /// e.g. because their membership has ended
+    #[serde(default)]
+    pub forbidden: bool,
+}
+
+impl MockUser {
+    pub fn new<S1: AsRef<str>, S2: AsRef<str>>(
+        koala_id: i32,
+        first_name: S1,
+        last_name: S2,
+    ) -> Self {
+        Self {
+            koala_id,
+            first_name: first_name.as_ref().to_string(),
+            infix: None,
+            last_name: last_name.as_ref().to_string(),
+            email: format!("user{koala_id}@example.com"),
+            is_admin: false,
+            groups: Vec::new(),
+            omit_groups: false,
+            forbidden: false,
+        }
+    }
+
+    pub fn admin(mut self) -> Self {
+        self.is_admin = true;
+        self
+    }
+
+    pub fn with_groups(mut self, groups: &[&str]) -> Self {
+        self.groups = groups.iter().map(|group| group.to_string()).collect();
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MockKoalaConfig {
+    /// The OAuth2 client ID chroma should use
+    pub client_id: String,
+    /// The OAuth2 client secret chroma should use
+    pub client_secret: String,
+    /// How long issued access tokens are valid
+    pub access_token_lifetime: Duration,
+    pub users: Vec<MockUser>,
+}
+
+impl Default for MockKoalaConfig {
+    fn default() -> Self {
+        Self {
+            client_id: "chroma".to_string(),
+            client_secret: "chroma".to_string(),
+            access_token_lifetime: Duration::from_secs(2 * 60 * 60),
+            users: Vec::new(),
+        }
+    }
+}
+
+/// A running fake Koala.
+/// The server runs in the background until [MockKoala::stop] is called.
+pub struct MockKoala {
+    base_uri: String,
+    state: Arc<Mutex<State>>,
+    handle: ServerHandle,
+}
+
+impl MockKoala {
+    /// Start the server on the address.
+    /// Use port `0` to let the OS pick a free port, the chosen port is part of [MockKoala::base_uri].
+    ///
+    /// # Errors
+    ///
+    /// If the address could not be bound
+    pub async fn start<A: ToSocketAddrs>(config: MockKoalaConfig, addr: A) -> io::Result<Self> {
+        let state = Arc::new(Mutex::new(State::new(config)));
+
+        let app_state = web::Data::from(state.clone());
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(app_state.clone())
+                .configure(routes::configure)
+        })
+        .workers(1)
+        .bind(addr)?;
+
+        let base_uri = format!("http://{}", server.addrs()[0]);
+        let server = server.run();
+        let handle = server.handle();
+        tokio::spawn(server);
+
+        Ok(Self {
+            base_uri,
+            state,
+            handle,
+        })
+    }
+
+    /// The URI the server is reachable at, without a trailing `/`.
+    /// Chroma's `KOALA_BASE_URI` and `KOALA_BASE_REDIRECT_URI` should be set to it.
+    pub fn base_uri(&self) -> &str {
+        &self.base_uri
+    }
+
+    /// Issue an authorization code for the user, as if they had logged in.
+    ///
+    /// # Panics
+    ///
+    /// If the user does not exist
+    pub fn issue_code(&self, koala_id: i32) -> String {
+        let mut state = self.state();
+        assert!(
+            state.users.contains_key(&koala_id),
+            "user {koala_id} does not exist"
+        );
+        state.issue_code(koala_id)
+    }
+
+    /// Add a user, replacing the user with the same ID if it exists.
+    pub fn add_user(&self, user: MockUser) {
+        self.state().users.insert(user.koala_id, user);
+    }
+
+    /// Change a user, e.g. to make them an admin or move them to other groups.
+    /// Changes are visible to chroma the next time it fetches the user's details.
+    ///
+    /// # Panics
+    ///
+    /// If the user does not exist
+    pub fn update_user<F: FnOnce(&mut MockUser)>(&self, koala_id: i32, f: F) {
+        let mut state = self.state();
+        let user = state
+            .users
+            .get_mut(&koala_id)
+            .unwrap_or_else(|| panic!("user {koala_id} does not exist"));
+        f(user);
+    }
+
+    /// Let all access tokens of the user expire, their refresh tokens stay valid.
+    pub fn expire_access_tokens(&self, koala_id: i32) {
+        self.state().expire_access_tokens(koala_id);
+    }
+
+    /// Revoke all access and refresh tokens of the user, as if they logged out of Koala.
+    pub fn revoke_tokens(&self, koala_id: i32) {
+        self.state().revoke_tokens(koala_id);
+    }
+
+    /// Stop the server, waiting for running requests to complete.
+    pub async fn stop(self) {
+        self.handle.stop(true).await;
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().expect("state lock is poisoned")
+    }
+}
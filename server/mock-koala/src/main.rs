@@ -0,0 +1,91 @@
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use dotenv::dotenv;
+use serde::Deserialize;
+use tracing::level_filters::LevelFilter;
+use tracing::{info, warn};
+use tracing_subscriber::fmt::layer;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::EnvFilter;
+
+use mock_koala::{MockKoala, MockKoalaConfig, MockUser};
+
+/// The configuration of the mock, read from `MOCK_KOALA_` prefixed environmental variables.
+#[derive(Debug, Deserialize)]
+struct Config {
+    /// The port to listen on.
+    /// If not provided, the default [Config::DEFAULT_PORT] will be used.
+    port: Option<u16>,
+    /// Should match chroma's `KOALA_CLIENT_ID`.
+    /// If not provided, `chroma` will be used.
+    client_id: Option<String>,
+    /// Should match chroma's `KOALA_CLIENT_SECRET`.
+    /// If not provided, `chroma` will be used.
+    client_secret: Option<String>,
+    /// A JSON file with an array of users.
+    /// If not provided, an admin with ID 1 and a member with ID 2 are available.
+    users: Option<PathBuf>,
+}
+
+impl Config {
+    /// The default port, which is the port Koala listens on in development
+    const DEFAULT_PORT: u16 = 3000;
+}
+
+/// Run a fake Koala for local development, until stopped with Ctrl-C.
+#[tokio::main]
+async fn main() -> Result<()> {
+    let dotenv_err = dotenv().err();
+
+    init_tracing();
+
+    if let Some(err) = dotenv_err {
+        warn!("failed to load .env file: {:#}", err);
+    }
+
+    let config: Config = envy::prefixed("MOCK_KOALA_")
+        .from_env()
+        .map_err(|err| anyhow!("failed to parse config: {:#}", err))?;
+
+    let users = match &config.users {
+        Some(path) => serde_json::from_slice(&std::fs::read(path)?)
+            .map_err(|err| anyhow!("failed to parse users file: {:#}", err))?,
+        None => vec![
+            MockUser::new(1, "Admin", "User").admin(),
+            MockUser::new(2, "Member", "User"),
+        ],
+    };
+
+    let defaults = MockKoalaConfig::default();
+    let koala = MockKoala::start(
+        MockKoalaConfig {
+            client_id: config.client_id.unwrap_or(defaults.client_id),
+            client_secret: config.client_secret.unwrap_or(defaults.client_secret),
+            access_token_lifetime: defaults.access_token_lifetime,
+            users,
+        },
+        ("0.0.0.0", config.port.unwrap_or(Config::DEFAULT_PORT)),
+    )
+    .await?;
+
+    info!("mock Koala is listening on {}", koala.base_uri());
+    tokio::signal::ctrl_c().await?;
+
+    info!("stopping mock Koala");
+    koala.stop().await;
+
+    Ok(())
+}
+
+fn init_tracing() {
+    tracing_subscriber::registry()
+        .with(layer().compact())
+        .with(
+            EnvFilter::builder()
+                .with_default_directive(LevelFilter::INFO.into())
+                .from_env_lossy(),
+        )
+        .init();
+}
@@ -0,0 +1,267 @@
+use std::sync::{Mutex, MutexGuard};
+
+use actix_web::http::header::{AUTHORIZATION, CONTENT_TYPE, LOCATION};
+use actix_web::{web, HttpRequest, HttpResponse};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::Deserialize;
+use serde_json::json;
+use url::Url;
+
+use crate::state::{State, Tokens};
+use crate::MockUser;
+
+/// The paths of Koala's OAuth2 endpoints
+const AUTHORIZE_PATH: &str = "/api/oauth/authorize";
+const TOKEN_PATH: &str = "/api/oauth/token";
+const USERINFO_PATH: &str = "/oauth/userinfo";
+/// The scopes Koala grants chroma
+const SCOPES: &str = "member-read openid email profile";
+
+type StateData = web::Data<Mutex<State>>;
+
+pub fn configure(config: &mut web::ServiceConfig) {
+    config
+        .route(AUTHORIZE_PATH, web::get().to(authorize))
+        .route(TOKEN_PATH, web::post().to(token))
+        .route(USERINFO_PATH, web::get().to(userinfo));
+}
+
+#[derive(Deserialize)]
+struct AuthorizeQuery {
+    client_id: String,
+    redirect_uri: String,
+    response_type: String,
+    state: Option<String>,
+    /// The user to log in as. If absent, a page to pick a user is shown
+    koala_id: Option<i32>,
+}
+
+/// Log in as one of the users and redirect back to the client with a code.
+/// Koala asks for a password here, the mock lets the user pick who they are.
+async fn authorize(
+    state: StateData,
+    req: HttpRequest,
+    query: web::Query<AuthorizeQuery>,
+) -> HttpResponse {
+    let mut state = lock(&state);
+
+    if query.client_id.ne(&state.client_id) {
+        return HttpResponse::BadRequest().body("Unknown client");
+    }
+
+    if query.response_type.ne("code") {
+        return HttpResponse::BadRequest().body("Unsupported response type");
+    }
+
+    let mut redirect_uri = match Url::parse(&query.redirect_uri) {
+        Ok(v) => v,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid redirect URI"),
+    };
+
+    let koala_id = match query.koala_id {
+        Some(id) if state.users.contains_key(&id) => id,
+        Some(_) => return HttpResponse::NotFound().body("Unknown user"),
+        None => return pick_user_page(&state, req.query_string()),
+    };
+
+    let code = state.issue_code(koala_id);
+    {
+        let mut pairs = redirect_uri.query_pairs_mut();
+        pairs.append_pair("code", &code);
+        if let Some(oauth_state) = &query.state {
+            pairs.append_pair("state", oauth_state);
+        }
+    }
+
+    HttpResponse::Found()
+        .insert_header((LOCATION, redirect_uri.to_string()))
+        .finish()
+}
+
+fn pick_user_page(state: &State, query_string: &str) -> HttpResponse {
+    let mut users = state.users.values().collect::<Vec<_>>();
+    users.sort_by_key(|user| user.koala_id);
+
+    let links = users
+        .into_iter()
+        .map(|user| {
+            format!(
+                "<li><a href=\"{AUTHORIZE_PATH}?{}&amp;koala_id={}\">{}</a>{}</li>",
+                escape(query_string),
+                user.koala_id,
+                escape(&full_name(user)),
+                if user.is_admin { " (admin)" } else { "" },
+            )
+        })
+        .collect::<String>();
+
+    HttpResponse::Ok()
+        .insert_header((CONTENT_TYPE, "text/html; charset=utf-8"))
+        .body(format!(
+            "<!DOCTYPE html><html><head><title>Mock Koala</title></head>\
+            <body><h1>Log in as</h1><ul>{links}</ul></body></html>"
+        ))
+}
+
+#[derive(Deserialize)]
+struct TokenRequest {
+    grant_type: String,
+    code: Option<String>,
+    refresh_token: Option<String>,
+    client_id: Option<String>,
+    client_secret: Option<String>,
+}
+
+/// Exchange an authorization code or refresh token for a new token pair.
+/// Parameters are accepted as form, JSON or query parameters, like Koala does.
+/// Client credentials are accepted as parameters or with basic authentication.
+async fn token(state: StateData, req: HttpRequest, body: web::Bytes) -> HttpResponse {
+    let request = match parse_token_request(&req, &body) {
+        Some(v) => v,
+        None => return oauth_error("invalid_request", "Missing or malformed parameters"),
+    };
+
+    let mut state = lock(&state);
+
+    let (client_id, client_secret) = match (&request.client_id, &request.client_secret) {
+        (Some(id), Some(secret)) => (id.clone(), secret.clone()),
+        _ => match basic_auth(&req) {
+            Some(v) => v,
+            None => return invalid_client(),
+        },
+    };
+
+    if client_id.ne(&state.client_id) || client_secret.ne(&state.client_secret) {
+        return invalid_client();
+    }
+
+    let exchanged = match (
+        request.grant_type.as_str(),
+        &request.code,
+        &request.refresh_token,
+    ) {
+        ("authorization_code", Some(code), _) => state.exchange_code(code),
+        ("refresh_token", _, Some(refresh_token)) => state.exchange_refresh_token(refresh_token),
+        _ => return oauth_error("unsupported_grant_type", "Unsupported grant type"),
+    };
+
+    match exchanged {
+        Some((koala_id, tokens)) => token_response(koala_id, tokens),
+        None => oauth_error("invalid_grant", "The provided grant is invalid or expired"),
+    }
+}
+
+fn parse_token_request(req: &HttpRequest, body: &[u8]) -> Option<TokenRequest> {
+    let content_type = req
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+
+    if body.is_empty() {
+        serde_urlencoded::from_str(req.query_string()).ok()
+    } else if content_type.starts_with("application/json") {
+        serde_json::from_slice(body).ok()
+    } else {
+        serde_urlencoded::from_bytes(body).ok()
+    }
+}
+
+fn basic_auth(req: &HttpRequest) -> Option<(String, String)> {
+    let encoded = req
+        .headers()
+        .get(AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Basic ")?;
+    let decoded = String::from_utf8(STANDARD.decode(encoded).ok()?).ok()?;
+    let (id, secret) = decoded.split_once(':')?;
+
+    Some((id.to_string(), secret.to_string()))
+}
+
+fn token_response(koala_id: i32, tokens: Tokens) -> HttpResponse {
+    HttpResponse::Ok().json(json!({
+        "access_token": tokens.access_token,
+        "token_type": "Bearer",
+        "expires_in": tokens.expires_in,
+        "refresh_token": tokens.refresh_token,
+        "scope": SCOPES,
+        "created_at": tokens.created_at,
+        "credentials_type": "Member",
+        "credentials_id": koala_id,
+    }))
+}
+
+/// Get the details of the user the access token belongs to.
+async fn userinfo(state: StateData, req: HttpRequest) -> HttpResponse {
+    let access_token = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+
+    let state = lock(&state);
+    let user = match access_token.and_then(|token| state.user_for_access_token(token)) {
+        Some(v) => v,
+        None => {
+            return HttpResponse::Unauthorized().json(json!({
+                "error": "invalid_token",
+                "error_description": "The access token is invalid or expired",
+            }))
+        }
+    };
+
+    if user.forbidden {
+        return HttpResponse::Forbidden().finish();
+    }
+
+    let mut userinfo = json!({
+        "sub": user.koala_id.to_string(),
+        "email": user.email,
+        "given_name": user.first_name,
+        "infix": user.infix,
+        "family_name": user.last_name,
+        "name": full_name(user),
+        "is_admin": user.is_admin,
+    });
+    if !user.omit_groups {
+        userinfo["groups"] = json!(user.groups);
+    }
+
+    HttpResponse::Ok().json(userinfo)
+}
+
+fn invalid_client() -> HttpResponse {
+    HttpResponse::Unauthorized().json(json!({
+        "error": "invalid_client",
+        "error_description": "Client authentication failed",
+    }))
+}
+
+fn oauth_error(error: &str, description: &str) -> HttpResponse {
+    HttpResponse::BadRequest().json(json!({
+        "error": error,
+        "error_description": description,
+    }))
+}
+
+fn full_name(user: &MockUser) -> String {
+    match &user.infix {
+        Some(infix) => format!("{} {infix} {}", user.first_name, user.last_name),
+        None => format!("{} {}", user.first_name, user.last_name),
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn lock(state: &StateData) -> MutexGuard<'_, State> {
+    state.lock().expect("state lock is poisoned")
+}
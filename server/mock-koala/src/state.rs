@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use time::OffsetDateTime;
+
+use crate::{MockKoalaConfig, MockUser};
+
+const TOKEN_LEN: usize = 43;
+
+pub struct State {
+    pub client_id: String,
+    pub client_secret: String,
+    pub access_token_lifetime: i64,
+    pub users: HashMap<i32, MockUser>,
+    /// Authorization codes and the user they were issued to
+    codes: HashMap<String, i32>,
+    access_tokens: HashMap<String, AccessToken>,
+    /// Refresh tokens and the user they were issued to
+    refresh_tokens: HashMap<String, i32>,
+}
+
+struct AccessToken {
+    koala_id: i32,
+    expires_at: i64,
+}
+
+/// A newly issued token pair.
+pub struct Tokens {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub expires_in: i64,
+    pub created_at: i64,
+}
+
+impl State {
+    pub fn new(config: MockKoalaConfig) -> Self {
+        Self {
+            client_id: config.client_id,
+            client_secret: config.client_secret,
+            access_token_lifetime: config.access_token_lifetime.as_secs() as i64,
+            users: config
+                .users
+                .into_iter()
+                .map(|user| (user.koala_id, user))
+                .collect(),
+            codes: HashMap::new(),
+            access_tokens: HashMap::new(),
+            refresh_tokens: HashMap::new(),
+        }
+    }
+
+    pub fn issue_code(&mut self, koala_id: i32) -> String {
+        let code = random_token();
+        self.codes.insert(code.clone(), koala_id);
+        code
+    }
+
+    /// Exchange a code for tokens. Codes can be used once.
+    pub fn exchange_code(&mut self, code: &str) -> Option<(i32, Tokens)> {
+        let koala_id = self.codes.remove(code)?;
+        Some((koala_id, self.issue_tokens(koala_id)))
+    }
+
+    /// Exchange a refresh token for new tokens. Refresh tokens are rotated, so can be used once.
+    pub fn exchange_refresh_token(&mut self, refresh_token: &str) -> Option<(i32, Tokens)> {
+        let koala_id = self.refresh_tokens.remove(refresh_token)?;
+        Some((koala_id, self.issue_tokens(koala_id)))
+    }
+
+    /// The user the access token belongs to, if it is valid.
+    pub fn user_for_access_token(&self, access_token: &str) -> Option<&MockUser> {
+        self.access_tokens
+            .get(access_token)
+            .filter(|token| token.expires_at > now())
+            .and_then(|token| self.users.get(&token.koala_id))
+    }
+
+    pub fn expire_access_tokens(&mut self, koala_id: i32) {
+        let expired_at = now() - 1;
+        self.access_tokens
+            .values_mut()
+            .filter(|token| token.koala_id == koala_id)
+            .for_each(|token| token.expires_at = expired_at);
+    }
+
+    pub fn revoke_tokens(&mut self, koala_id: i32) {
+        self.access_tokens
+            .retain(|_, token| token.koala_id != koala_id);
+        self.refresh_tokens.retain(|_, id| *id != koala_id);
+    }
+
+    fn issue_tokens(&mut self, koala_id: i32) -> Tokens {
+        let tokens = Tokens {
+            access_token: random_token(),
+            refresh_token: random_token(),
+            expires_in: self.access_token_lifetime,
+            created_at: now(),
+        };
+
+        self.access_tokens.insert(
+            tokens.access_token.clone(),
+            AccessToken {
+                koala_id,
+                expires_at: tokens.created_at + tokens.expires_in,
+            },
+        );
+        self.refresh_tokens
+            .insert(tokens.refresh_token.clone(), koala_id);
+
+        tokens
+    }
+}
+
+fn random_token() -> String {
+    rand::thread_rng()
+        .sample_iter(Alphanumeric)
+        .take(TOKEN_LEN)
+        .map(char::from)
+        .collect()
+}
+
+fn now() -> i64 {
+    OffsetDateTime::now_utc().unix_timestamp()
+}